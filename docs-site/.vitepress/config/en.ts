@@ -33,7 +33,10 @@ export default defineConfig({
           { text: "Instant", link: "instant" },
           { text: "Duration", link: "duration" },
           { text: "FsService", link: "FsService" },
+          { text: "FileHandle", link: "FileHandle" },
           { text: "DateTime", link: "DateTime" },
+          { text: "Module", link: "Module" },
+          { text: "Snapshot", link: "Snapshot" },
         ],
       },
     ],
@@ -19,6 +19,12 @@ pub enum Error {
     // Config(#[from] crate::config::Error),
     #[error("Memory module error: {0}")]
     Memory(#[from] crate::memory::MemoryError),
+    #[error("C definition error: {0}")]
+    Cdef(#[from] crate::memory::CdefError),
+    #[error("Signature error: {0}")]
+    Signature(#[from] crate::memory::SignatureError),
+    #[error("PE image error: {0}")]
+    Pe(#[from] crate::memory::PeError),
     #[error("Http request error: {0}")]
     Reqwest(#[from] reqwest::Error),
 
@@ -40,6 +46,20 @@ pub enum Error {
     // SingletonNotFound(String),
     #[error("Memory patch already exists at 0x{0:x}")]
     PatchAlreadyExists(usize),
+    #[error(
+        "Memory patch at 0x{0:x} overlaps patches by {1}. Pass `allow_overlap` to patch anyway"
+    )]
+    PatchOverlap(usize, String),
+    #[error("Memory patch verification failed at 0x{0:x}: expected [{1}], found [{2}]")]
+    PatchVerifyFailed(usize, String, String),
+    #[error("Patch group '{0}' already exists")]
+    PatchGroupAlreadyExists(String),
+    #[error("Patch group '{0}' not found")]
+    PatchGroupNotFound(String),
+    #[error("Patch group entries at 0x{0:x} and 0x{1:x} overlap")]
+    PatchGroupOverlap(usize, usize),
+    #[error("Module '{0}' is not loaded")]
+    ModuleNotLoaded(String),
     // #[error("Path not allowed: {0}")]
     // PathNotAllowed(String),
     // #[error("Proc address '{0}' not found")]
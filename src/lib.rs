@@ -9,6 +9,8 @@ use log::{error, info};
 mod error;
 mod memory;
 mod module;
+#[cfg(test)]
+mod test_util;
 mod util;
 
 pub static TOKIO_RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
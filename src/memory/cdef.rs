@@ -0,0 +1,1274 @@
+//! C 结构体定义解析
+//!
+//! 支持常用的 C 子集：struct、union、typedef、定长数组、指针、函数指针、
+//! stdint 类型、`#pragma pack` 以及注释中的显式偏移（如 `// 0x10`）。
+//!
+//! 内存布局遵循 MSVC x64 规则（LLP64，`long` 为 4 字节）。
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+/// 解析错误，包含行列号（从 1 开始）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdefError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl CdefError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for CdefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for CdefError {}
+
+type Result<T> = std::result::Result<T, CdefError>;
+
+/// C 类型
+#[derive(Debug, Clone)]
+pub enum CType {
+    Void,
+    Bool,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+    Pointer(Box<CType>),
+    Array(Box<CType>, usize),
+    /// 已定义的 struct 或 union
+    Record(Arc<Layout>),
+    /// 未定义的 struct 或 union，仅可通过指针引用
+    Opaque(String),
+}
+
+impl CType {
+    /// 类型大小，不完整类型或大小溢出时返回 None
+    pub fn size(&self) -> Option<usize> {
+        let size = match self {
+            CType::Void | CType::Opaque(_) => return None,
+            CType::Bool | CType::I8 | CType::U8 => 1,
+            CType::I16 | CType::U16 => 2,
+            CType::I32 | CType::U32 | CType::F32 => 4,
+            CType::I64 | CType::U64 | CType::F64 | CType::Pointer(_) => 8,
+            CType::Array(elem, count) => elem.size()?.checked_mul(*count)?,
+            CType::Record(layout) => layout.size,
+        };
+        Some(size)
+    }
+
+    /// 类型对齐，不完整类型返回 None
+    pub fn align(&self) -> Option<usize> {
+        match self {
+            CType::Array(elem, _) => elem.align(),
+            CType::Record(layout) => Some(layout.align),
+            other => other.size(),
+        }
+    }
+}
+
+/// 记录类型种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Struct,
+    Union,
+}
+
+/// 结构体字段
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: CType,
+    pub offset: usize,
+}
+
+/// struct 或 union 的内存布局
+#[derive(Debug)]
+pub struct Layout {
+    pub name: String,
+    pub kind: RecordKind,
+    pub size: usize,
+    pub align: usize,
+    /// 字段列表，匿名成员的字段已展开
+    pub fields: Vec<Field>,
+}
+
+impl Layout {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// 已注册的类型
+#[derive(Debug, Clone, Default)]
+pub struct CTypeRegistry {
+    records: HashMap<String, Arc<Layout>>,
+    typedefs: HashMap<String, CType>,
+    anonymous_count: usize,
+}
+
+impl CTypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 解析 C 定义并注册其中的类型，返回新定义的类型名。
+    ///
+    /// 解析失败时不会注册任何类型。
+    pub fn define(&mut self, source: &str) -> Result<Vec<String>> {
+        let tokens = Lexer::new(source).tokenize()?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            registry: self.clone(),
+            pack_stack: vec![],
+            pack: DEFAULT_PACK,
+            defined: vec![],
+        };
+        parser.parse_translation_unit()?;
+
+        let mut defined = std::mem::take(&mut parser.defined);
+        defined.dedup();
+        *self = parser.registry;
+        Ok(defined)
+    }
+
+    /// 按名称获取 struct 或 union 布局，支持 typedef 别名
+    pub fn record(&self, name: &str) -> Option<Arc<Layout>> {
+        let name = name
+            .trim()
+            .trim_start_matches("struct ")
+            .trim_start_matches("union ")
+            .trim();
+        if let Some(layout) = self.records.get(name) {
+            return Some(layout.clone());
+        }
+        match self.typedefs.get(name) {
+            Some(CType::Record(layout)) => Some(layout.clone()),
+            Some(CType::Opaque(tag)) => self.records.get(tag).cloned(),
+            _ => None,
+        }
+    }
+
+    /// 解析类型名，如 `Player`、`struct Player*`、`uint32_t[4]`
+    pub fn parse_type_name(&self, name: &str) -> Result<CType> {
+        let tokens = Lexer::new(name).tokenize()?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            registry: self.clone(),
+            pack_stack: vec![],
+            pack: DEFAULT_PACK,
+            defined: vec![],
+        };
+        let base = parser.parse_specifiers()?;
+        let (declarator_name, ty) = parser.parse_declarator(base, true)?;
+        if let Some(name) = declarator_name {
+            return Err(parser.error_at_prev(format!("unexpected identifier '{}'", name)));
+        }
+        parser.expect_eof()?;
+        Ok(ty)
+    }
+
+    /// 将不完整类型解析为已定义的记录类型
+    pub fn resolve(&self, ty: &CType) -> CType {
+        match ty {
+            CType::Opaque(tag) => match self.records.get(tag) {
+                Some(layout) => CType::Record(layout.clone()),
+                None => ty.clone(),
+            },
+            other => other.clone(),
+        }
+    }
+
+    /// 类型大小
+    pub fn sizeof(&self, name: &str) -> Result<usize> {
+        let ty = self.resolve(&self.parse_type_name(name)?);
+        ty.size()
+            .ok_or_else(|| CdefError::new(1, 1, format!("incomplete type '{}'", name.trim())))
+    }
+
+    /// 字段偏移，`path` 支持 `a.b` 与 `arr[2]` 形式
+    pub fn offsetof(&self, name: &str, path: &str) -> Result<usize> {
+        let not_found = |msg: String| CdefError::new(1, 1, msg);
+
+        let mut ty = self.resolve(&self.parse_type_name(name)?);
+        let mut offset = 0;
+        for segment in path.split('.') {
+            let (field_name, indices) = match segment.find('[') {
+                Some(pos) => (&segment[..pos], &segment[pos..]),
+                None => (segment, ""),
+            };
+
+            let CType::Record(layout) = &ty else {
+                return Err(not_found(format!("'{}' is not a struct or union", segment)));
+            };
+            let field = layout
+                .field(field_name.trim())
+                .ok_or_else(|| not_found(format!("field '{}' not found", field_name)))?;
+            offset += field.offset;
+            ty = self.resolve(&field.ty);
+
+            for index in indices.split('[').skip(1) {
+                let index = index
+                    .strip_suffix(']')
+                    .and_then(|i| i.trim().parse::<usize>().ok())
+                    .ok_or_else(|| not_found(format!("invalid array index in '{}'", segment)))?;
+                let CType::Array(elem, count) = &ty else {
+                    return Err(not_found(format!("'{}' is not an array", segment)));
+                };
+                if index >= *count {
+                    return Err(not_found(format!("index {} out of bounds", index)));
+                }
+                let elem = self.resolve(elem);
+                offset += index * elem.size().unwrap_or(0);
+                ty = elem;
+            }
+        }
+
+        Ok(offset)
+    }
+}
+
+/// MSVC x64 默认对齐
+const DEFAULT_PACK: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Number(u64),
+    Punct(char),
+    /// 注释中的显式偏移
+    OffsetComment(usize),
+    /// `#pragma pack(...)`，参数为括号内的内容
+    PragmaPack(String),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !pred(c) {
+                break;
+            }
+            s.push(c);
+            self.bump();
+        }
+        s
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>> {
+        let mut tokens = vec![];
+        let mut line_start = true;
+
+        while let Some(&c) = self.chars.peek() {
+            let (line, column) = (self.line, self.column);
+            let token = |kind| Token { kind, line, column };
+
+            if c == '\n' {
+                line_start = true;
+                self.bump();
+                continue;
+            }
+            if c.is_whitespace() {
+                self.bump();
+                continue;
+            }
+
+            if c == '#' && line_start {
+                let directive = self.take_while(|c| c != '\n');
+                if let Some(args) = parse_pragma_pack(&directive) {
+                    tokens.push(token(TokenKind::PragmaPack(args)));
+                }
+                // 其他预处理指令忽略
+                continue;
+            }
+            line_start = false;
+
+            if c == '/' {
+                self.bump();
+                match self.chars.peek() {
+                    Some('/') => {
+                        self.bump();
+                        let text = self.take_while(|c| c != '\n');
+                        if let Some(offset) = parse_offset_comment(&text) {
+                            tokens.push(token(TokenKind::OffsetComment(offset)));
+                        }
+                    }
+                    Some('*') => {
+                        self.bump();
+                        let mut text = String::new();
+                        loop {
+                            match self.bump() {
+                                Some('*') if self.chars.peek() == Some(&'/') => {
+                                    self.bump();
+                                    break;
+                                }
+                                Some(c) => text.push(c),
+                                None => {
+                                    return Err(CdefError::new(
+                                        line,
+                                        column,
+                                        "unterminated block comment",
+                                    ));
+                                }
+                            }
+                        }
+                        if let Some(offset) = parse_offset_comment(&text) {
+                            tokens.push(token(TokenKind::OffsetComment(offset)));
+                        }
+                    }
+                    _ => return Err(CdefError::new(line, column, "unexpected character '/'")),
+                }
+                continue;
+            }
+
+            if c.is_ascii_alphabetic() || c == '_' {
+                let ident = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                tokens.push(token(TokenKind::Ident(ident)));
+                continue;
+            }
+
+            if c.is_ascii_digit() {
+                let text = self.take_while(|c| c.is_ascii_alphanumeric());
+                let number = parse_integer(&text).ok_or_else(|| {
+                    CdefError::new(line, column, format!("invalid number '{}'", text))
+                })?;
+                tokens.push(token(TokenKind::Number(number)));
+                continue;
+            }
+
+            if c.is_ascii_punctuation() {
+                self.bump();
+                tokens.push(token(TokenKind::Punct(c)));
+                continue;
+            }
+
+            return Err(CdefError::new(
+                line,
+                column,
+                format!("unexpected character '{}'", c),
+            ));
+        }
+
+        tokens.push(Token {
+            kind: TokenKind::Eof,
+            line: self.line,
+            column: self.column,
+        });
+        Ok(tokens)
+    }
+}
+
+/// 解析整数字面量，支持 16 进制与整数后缀
+fn parse_integer(text: &str) -> Option<u64> {
+    let text = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// 解析偏移注释，如 `0x10`、`+0x10`、`offset: 0x10`
+fn parse_offset_comment(text: &str) -> Option<usize> {
+    let mut text = text.trim();
+    if text.len() >= 6 && text[..6].eq_ignore_ascii_case("offset") {
+        text = text[6..].trim_start_matches([' ', '\t', ':', '=']);
+    }
+    let text = text.strip_prefix('+').unwrap_or(text);
+    let hex = text.strip_prefix("0x").or(text.strip_prefix("0X"))?;
+    let end = hex
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(hex.len());
+    if end == 0 || hex[end..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    usize::from_str_radix(&hex[..end], 16).ok()
+}
+
+/// 解析 `#pragma pack(...)` 指令，返回括号内的参数
+fn parse_pragma_pack(directive: &str) -> Option<String> {
+    let rest = directive.trim_start_matches('#').trim_start();
+    let rest = rest.strip_prefix("pragma")?.trim_start();
+    let rest = rest.strip_prefix("pack")?.trim();
+    let args = rest.strip_prefix('(')?.split(')').next()?;
+    Some(args.trim().to_string())
+}
+
+/// 结构体成员声明
+struct Member {
+    /// 匿名 struct/union 成员为 None
+    name: Option<String>,
+    ty: CType,
+    explicit_offset: Option<usize>,
+    line: usize,
+    column: usize,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    registry: CTypeRegistry,
+    pack_stack: Vec<usize>,
+    pack: usize,
+    defined: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn peek_kind(&self) -> &TokenKind {
+        &self.peek().kind
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error_here(&self, message: impl Into<String>) -> CdefError {
+        let token = self.peek();
+        CdefError::new(token.line, token.column, message)
+    }
+
+    fn error_at_prev(&self, message: impl Into<String>) -> CdefError {
+        let token = &self.tokens[self.pos.saturating_sub(1)];
+        CdefError::new(token.line, token.column, message)
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek_kind() == &TokenKind::Punct(c)
+    }
+
+    fn is_ident(&self, s: &str) -> bool {
+        matches!(self.peek_kind(), TokenKind::Ident(ident) if ident == s)
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<Token> {
+        if self.is_punct(c) {
+            Ok(self.next())
+        } else {
+            Err(self.error_here(format!("expected '{}', found {}", c, self.describe())))
+        }
+    }
+
+    fn expect_eof(&self) -> Result<()> {
+        if self.peek_kind() == &TokenKind::Eof {
+            Ok(())
+        } else {
+            Err(self.error_here(format!("unexpected {}", self.describe())))
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek_kind() {
+            TokenKind::Ident(ident) => format!("'{}'", ident),
+            TokenKind::Number(n) => format!("'{}'", n),
+            TokenKind::Punct(c) => format!("'{}'", c),
+            TokenKind::OffsetComment(_) => "offset comment".to_string(),
+            TokenKind::PragmaPack(_) => "#pragma pack".to_string(),
+            TokenKind::Eof => "end of input".to_string(),
+        }
+    }
+
+    fn handle_pragma(&mut self, args: &str) -> Result<()> {
+        let parse_value = |s: &str| -> Result<usize> {
+            match s.trim().parse::<usize>() {
+                Ok(n) if n.is_power_of_two() && n <= 16 => Ok(n),
+                _ => Err(self.error_here(format!("invalid pack value '{}'", s.trim()))),
+            }
+        };
+
+        let parts = args.split(',').map(str::trim).collect::<Vec<_>>();
+        match parts.as_slice() {
+            [""] => self.pack = DEFAULT_PACK,
+            ["push"] => self.pack_stack.push(self.pack),
+            ["push", value] => {
+                let value = parse_value(value)?;
+                self.pack_stack.push(self.pack);
+                self.pack = value;
+            }
+            ["pop"] => self.pack = self.pack_stack.pop().unwrap_or(DEFAULT_PACK),
+            [value] => self.pack = parse_value(value)?,
+            _ => return Err(self.error_here(format!("unsupported #pragma pack({})", args))),
+        }
+        Ok(())
+    }
+
+    fn parse_translation_unit(&mut self) -> Result<()> {
+        loop {
+            match self.peek_kind().clone() {
+                TokenKind::Eof => return Ok(()),
+                TokenKind::PragmaPack(args) => {
+                    self.handle_pragma(&args)?;
+                    self.next();
+                }
+                TokenKind::OffsetComment(_) => {
+                    self.next();
+                }
+                TokenKind::Punct(';') => {
+                    self.next();
+                }
+                _ => self.parse_declaration()?,
+            }
+        }
+    }
+
+    /// 顶层声明：struct/union 定义、前置声明或 typedef
+    fn parse_declaration(&mut self) -> Result<()> {
+        let is_typedef = self.is_ident("typedef");
+        if is_typedef {
+            self.next();
+        }
+
+        let base = self.parse_specifiers()?;
+        if !is_typedef {
+            // 顶层只关心类型定义，变量声明视为错误
+            if !self.is_punct(';') {
+                return Err(self.error_here("only type definitions are supported at top level"));
+            }
+            self.next();
+            return Ok(());
+        }
+
+        loop {
+            let (name, ty) = self.parse_declarator(base.clone(), false)?;
+            let name = name.ok_or_else(|| self.error_here("typedef requires a name"))?;
+            self.registry.typedefs.insert(name.clone(), ty);
+            self.defined.push(name);
+
+            if self.is_punct(',') {
+                self.next();
+                continue;
+            }
+            self.expect_punct(';')?;
+            return Ok(());
+        }
+    }
+
+    /// 解析类型说明符
+    fn parse_specifiers(&mut self) -> Result<CType> {
+        let start = self.peek().clone();
+        let mut signed = None;
+        let mut short = false;
+        let mut long_count = 0;
+        let mut base: Option<&'static str> = None;
+
+        while let TokenKind::Ident(ident) = self.peek_kind().clone() {
+            match ident.as_str() {
+                "const" | "volatile" => {}
+                "signed" => signed = Some(true),
+                "unsigned" => signed = Some(false),
+                "short" => short = true,
+                "long" => long_count += 1,
+                "int" => base = Some("int"),
+                "char" => base = Some("char"),
+                "struct" | "union" => {
+                    self.next();
+                    return self.parse_record(ident == "union");
+                }
+                "enum" => {
+                    self.next();
+                    return self.parse_enum();
+                }
+                _ => {
+                    if base.is_some() || signed.is_some() || short || long_count > 0 {
+                        break;
+                    }
+                    if let Some(ty) = builtin_type(&ident) {
+                        self.next();
+                        self.skip_qualifiers();
+                        return Ok(ty);
+                    }
+                    if let Some(ty) = self.registry.typedefs.get(&ident).cloned() {
+                        self.next();
+                        self.skip_qualifiers();
+                        return Ok(ty);
+                    }
+                    // 与 C++ 一致，结构体名可直接作为类型名
+                    if let Some(layout) = self.registry.records.get(&ident).cloned() {
+                        self.next();
+                        self.skip_qualifiers();
+                        return Ok(CType::Record(layout));
+                    }
+                    return Err(self.error_here(format!("unknown type '{}'", ident)));
+                }
+            }
+            self.next();
+        }
+
+        let unsigned = signed == Some(false);
+        let ty = match (base, short, long_count) {
+            (Some("char"), false, 0) => {
+                if unsigned {
+                    CType::U8
+                } else {
+                    CType::I8
+                }
+            }
+            (None | Some("int"), true, 0) => pick(unsigned, CType::U16, CType::I16),
+            (None | Some("int"), false, 0) if signed.is_some() || base.is_some() => {
+                pick(unsigned, CType::U32, CType::I32)
+            }
+            (None | Some("int"), false, 1) => pick(unsigned, CType::U32, CType::I32),
+            (None | Some("int"), false, 2) => pick(unsigned, CType::U64, CType::I64),
+            (None, false, 0) => {
+                return Err(CdefError::new(
+                    start.line,
+                    start.column,
+                    "expected type specifier",
+                ));
+            }
+            _ => {
+                return Err(CdefError::new(
+                    start.line,
+                    start.column,
+                    "invalid combination of type specifiers",
+                ));
+            }
+        };
+        Ok(ty)
+    }
+
+    fn skip_qualifiers(&mut self) {
+        while self.is_ident("const") || self.is_ident("volatile") {
+            self.next();
+        }
+    }
+
+    /// `enum` 视为 32 位整数，枚举项被忽略
+    fn parse_enum(&mut self) -> Result<CType> {
+        if let TokenKind::Ident(_) = self.peek_kind() {
+            self.next();
+        }
+        if self.is_punct('{') {
+            while !self.is_punct('}') {
+                if self.peek_kind() == &TokenKind::Eof {
+                    return Err(self.error_here("unterminated enum body"));
+                }
+                self.next();
+            }
+            self.next();
+        }
+        Ok(CType::I32)
+    }
+
+    /// 解析 struct/union 说明符，可能包含定义体
+    fn parse_record(&mut self, is_union: bool) -> Result<CType> {
+        let kind = if is_union {
+            RecordKind::Union
+        } else {
+            RecordKind::Struct
+        };
+
+        let tag = match self.peek_kind().clone() {
+            TokenKind::Ident(ident) => {
+                self.next();
+                Some(ident)
+            }
+            _ => None,
+        };
+
+        if !self.is_punct('{') {
+            let Some(tag) = tag else {
+                return Err(self.error_here("expected struct name or '{'"));
+            };
+            return Ok(match self.registry.records.get(&tag) {
+                Some(layout) => CType::Record(layout.clone()),
+                None => CType::Opaque(tag),
+            });
+        }
+
+        let open = self.expect_punct('{')?;
+        let members = self.parse_members()?;
+        self.expect_punct('}')?;
+
+        let name = match tag {
+            Some(tag) => tag,
+            None => {
+                self.registry.anonymous_count += 1;
+                format!("<anonymous#{}>", self.registry.anonymous_count)
+            }
+        };
+        let layout = Arc::new(self.compute_layout(&name, kind, members, &open)?);
+        if !name.starts_with('<') {
+            self.registry.records.insert(name.clone(), layout.clone());
+            self.defined.push(name);
+        }
+
+        Ok(CType::Record(layout))
+    }
+
+    fn parse_members(&mut self) -> Result<Vec<Member>> {
+        let mut members: Vec<Member> = vec![];
+        let mut pending_offset = None;
+
+        loop {
+            match self.peek_kind().clone() {
+                TokenKind::Punct('}') => break,
+                TokenKind::Eof => return Err(self.error_here("expected '}'")),
+                TokenKind::PragmaPack(args) => {
+                    self.handle_pragma(&args)?;
+                    self.next();
+                    continue;
+                }
+                TokenKind::OffsetComment(offset) => {
+                    self.next();
+                    pending_offset = Some(offset);
+                    continue;
+                }
+                TokenKind::Punct(';') => {
+                    self.next();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let start = self.peek().clone();
+            let base = self.parse_specifiers()?;
+            let first_member = members.len();
+
+            if self.is_punct(';') {
+                // 匿名 struct/union 成员
+                if !matches!(&base, CType::Record(layout) if layout.name.starts_with('<')) {
+                    return Err(CdefError::new(
+                        start.line,
+                        start.column,
+                        "declaration does not declare anything",
+                    ));
+                }
+                members.push(Member {
+                    name: None,
+                    ty: base,
+                    explicit_offset: pending_offset.take(),
+                    line: start.line,
+                    column: start.column,
+                });
+            } else {
+                loop {
+                    let token = self.peek().clone();
+                    let (name, ty) = self.parse_declarator(base.clone(), false)?;
+                    let name = name.ok_or_else(|| {
+                        CdefError::new(token.line, token.column, "expected field name")
+                    })?;
+                    if self.is_punct(':') {
+                        return Err(self.error_here("bit fields are not supported"));
+                    }
+                    members.push(Member {
+                        name: Some(name),
+                        ty,
+                        explicit_offset: pending_offset.take(),
+                        line: token.line,
+                        column: token.column,
+                    });
+
+                    if self.is_punct(',') {
+                        self.next();
+                        continue;
+                    }
+                    break;
+                }
+            }
+
+            let semi = self.expect_punct(';')?;
+            // 同一行的尾随偏移注释属于该声明
+            if let TokenKind::OffsetComment(offset) = *self.peek_kind()
+                && self.peek().line == semi.line
+            {
+                self.next();
+                if members[first_member].explicit_offset.is_none() {
+                    members[first_member].explicit_offset = Some(offset);
+                }
+            }
+        }
+
+        Ok(members)
+    }
+
+    /// 解析声明符：指针、名称、数组及函数指针
+    fn parse_declarator(
+        &mut self,
+        base: CType,
+        abstract_allowed: bool,
+    ) -> Result<(Option<String>, CType)> {
+        let mut ty = base;
+        while self.is_punct('*') {
+            self.next();
+            self.skip_qualifiers();
+            ty = CType::Pointer(Box::new(ty));
+        }
+
+        // 函数指针 `ret (*name)(args)`，整体视为指针
+        if self.is_punct('(') {
+            self.next();
+            self.expect_punct('*')?;
+            let name = match self.peek_kind().clone() {
+                TokenKind::Ident(ident) => {
+                    self.next();
+                    Some(ident)
+                }
+                _ => None,
+            };
+            let mut ty = CType::Pointer(Box::new(CType::Void));
+            let dims = self.parse_array_dims()?;
+            self.expect_punct(')')?;
+            self.skip_parens()?;
+            for count in dims.into_iter().rev() {
+                ty = CType::Array(Box::new(ty), count);
+            }
+            if name.is_none() && !abstract_allowed {
+                return Err(self.error_at_prev("expected declarator name"));
+            }
+            return Ok((name, ty));
+        }
+
+        let name = match self.peek_kind().clone() {
+            TokenKind::Ident(ident) => {
+                self.next();
+                Some(ident)
+            }
+            _ if abstract_allowed => None,
+            _ => {
+                return Err(
+                    self.error_here(format!("expected identifier, found {}", self.describe()))
+                );
+            }
+        };
+
+        let dims = self.parse_array_dims()?;
+        for count in dims.into_iter().rev() {
+            ty = CType::Array(Box::new(ty), count);
+        }
+
+        Ok((name, ty))
+    }
+
+    fn parse_array_dims(&mut self) -> Result<Vec<usize>> {
+        let mut dims = vec![];
+        while self.is_punct('[') {
+            self.next();
+            let count = match *self.peek_kind() {
+                TokenKind::Number(n) => n as usize,
+                _ => {
+                    return Err(
+                        self.error_here(format!("expected array size, found {}", self.describe()))
+                    );
+                }
+            };
+            self.next();
+            self.expect_punct(']')?;
+            dims.push(count);
+        }
+        Ok(dims)
+    }
+
+    /// 跳过函数参数列表
+    fn skip_parens(&mut self) -> Result<()> {
+        self.expect_punct('(')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next().kind {
+                TokenKind::Punct('(') => depth += 1,
+                TokenKind::Punct(')') => depth -= 1,
+                TokenKind::Eof => return Err(self.error_here("expected ')'")),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn compute_layout(
+        &self,
+        name: &str,
+        kind: RecordKind,
+        members: Vec<Member>,
+        open: &Token,
+    ) -> Result<Layout> {
+        let mut fields = vec![];
+        let mut cursor = 0;
+        let mut size = 0;
+        let mut align = 1;
+
+        for member in members {
+            let ty = self.registry.resolve(&member.ty);
+            let member_name = member.name.as_deref().unwrap_or("<anonymous>");
+            let too_large = || {
+                CdefError::new(
+                    member.line,
+                    member.column,
+                    format!("field '{}' is too large", member_name),
+                )
+            };
+            // 完整类型总有对齐，此时大小为 None 说明数组大小溢出
+            let Some(member_align) = ty.align() else {
+                return Err(CdefError::new(
+                    member.line,
+                    member.column,
+                    format!("field '{}' has incomplete type", member_name),
+                ));
+            };
+            let member_size = ty.size().ok_or_else(too_large)?;
+            let member_align = member_align.min(self.pack).max(1);
+            align = align.max(member_align);
+
+            let natural = match kind {
+                RecordKind::Struct => align_up(cursor, member_align).ok_or_else(too_large)?,
+                RecordKind::Union => 0,
+            };
+            let offset = match member.explicit_offset {
+                Some(offset) if kind == RecordKind::Struct && offset < cursor => {
+                    return Err(CdefError::new(
+                        member.line,
+                        member.column,
+                        format!(
+                            "explicit offset 0x{:x} overlaps previous field ending at 0x{:x}",
+                            offset, cursor
+                        ),
+                    ));
+                }
+                Some(offset) => offset,
+                None => natural,
+            };
+
+            cursor = offset.checked_add(member_size).ok_or_else(too_large)?;
+            size = size.max(cursor);
+
+            match member.name {
+                Some(name) => {
+                    if fields.iter().any(|f: &Field| f.name == name) {
+                        return Err(CdefError::new(
+                            member.line,
+                            member.column,
+                            format!("duplicate field '{}'", name),
+                        ));
+                    }
+                    fields.push(Field { name, ty, offset });
+                }
+                None => {
+                    let CType::Record(inner) = &ty else {
+                        unreachable!("anonymous member must be a record");
+                    };
+                    for field in &inner.fields {
+                        fields.push(Field {
+                            name: field.name.clone(),
+                            ty: field.ty.clone(),
+                            offset: offset + field.offset,
+                        });
+                    }
+                }
+            }
+        }
+
+        if fields.is_empty() && size == 0 {
+            return Err(CdefError::new(
+                open.line,
+                open.column,
+                format!("'{}' has no fields", name),
+            ));
+        }
+
+        let size = align_up(size, align).ok_or_else(|| {
+            CdefError::new(open.line, open.column, format!("'{}' is too large", name))
+        })?;
+        Ok(Layout {
+            name: name.to_string(),
+            kind,
+            size,
+            align,
+            fields,
+        })
+    }
+}
+
+fn pick(unsigned: bool, u: CType, i: CType) -> CType {
+    if unsigned { u } else { i }
+}
+
+fn align_up(value: usize, align: usize) -> Option<usize> {
+    value.checked_next_multiple_of(align)
+}
+
+/// 内置类型名
+fn builtin_type(name: &str) -> Option<CType> {
+    let ty = match name {
+        "void" => CType::Void,
+        "bool" | "_Bool" => CType::Bool,
+        "float" => CType::F32,
+        "double" => CType::F64,
+        "int8_t" => CType::I8,
+        "uint8_t" => CType::U8,
+        "int16_t" => CType::I16,
+        "uint16_t" | "wchar_t" | "char16_t" => CType::U16,
+        "int32_t" => CType::I32,
+        "uint32_t" | "char32_t" => CType::U32,
+        "int64_t" | "intptr_t" | "ptrdiff_t" => CType::I64,
+        "uint64_t" | "uintptr_t" | "size_t" => CType::U64,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn define(source: &str) -> CTypeRegistry {
+        let mut registry = CTypeRegistry::new();
+        registry.define(source).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_basic_struct() {
+        let registry = define(
+            r#"
+            struct Player {
+                float hp;
+                char pad[4];
+                struct Weapon* weapon;
+            };
+            "#,
+        );
+        assert_eq!(registry.sizeof("Player").unwrap(), 16);
+        assert_eq!(registry.offsetof("Player", "hp").unwrap(), 0);
+        assert_eq!(registry.offsetof("Player", "pad").unwrap(), 4);
+        assert_eq!(registry.offsetof("Player", "weapon").unwrap(), 8);
+        assert_eq!(registry.sizeof("struct Player*").unwrap(), 8);
+    }
+
+    #[test]
+    fn test_natural_alignment() {
+        let registry = define("struct A { uint8_t a; uint64_t b; uint16_t c; };");
+        assert_eq!(registry.offsetof("A", "b").unwrap(), 8);
+        assert_eq!(registry.offsetof("A", "c").unwrap(), 16);
+        assert_eq!(registry.sizeof("A").unwrap(), 24);
+    }
+
+    #[test]
+    fn test_pragma_pack() {
+        let registry = define(
+            r#"
+            #pragma pack(push, 1)
+            struct Packed { uint8_t a; uint32_t b; };
+            #pragma pack(pop)
+            struct Normal { uint8_t a; uint32_t b; };
+            "#,
+        );
+        assert_eq!(registry.sizeof("Packed").unwrap(), 5);
+        assert_eq!(registry.offsetof("Packed", "b").unwrap(), 1);
+        assert_eq!(registry.sizeof("Normal").unwrap(), 8);
+    }
+
+    #[test]
+    fn test_offset_comments() {
+        let registry = define(
+            r#"
+            struct Entity {
+                void* vtable; // 0x0000
+                int32_t id; //0x0008
+                /* 0x0040 */ float pos[3];
+                // offset: 0x60
+                double scale;
+            };
+            "#,
+        );
+        assert_eq!(registry.offsetof("Entity", "id").unwrap(), 8);
+        assert_eq!(registry.offsetof("Entity", "pos").unwrap(), 0x40);
+        assert_eq!(registry.offsetof("Entity", "scale").unwrap(), 0x60);
+        assert_eq!(registry.sizeof("Entity").unwrap(), 0x68);
+    }
+
+    #[test]
+    fn test_offset_comment_overlap() {
+        let mut registry = CTypeRegistry::new();
+        let err = registry
+            .define("struct A {\n  uint64_t a;\n  uint32_t b; // 0x4\n};")
+            .unwrap_err();
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn test_union_and_anonymous_members() {
+        let registry = define(
+            r#"
+            union Value { int32_t i; double d; uint8_t bytes[12]; };
+            struct Holder {
+                uint32_t kind;
+                union { float f; uint64_t u; };
+                struct { int16_t x, y; } point;
+            };
+            "#,
+        );
+        assert_eq!(registry.sizeof("Value").unwrap(), 16);
+        assert_eq!(registry.offsetof("Holder", "f").unwrap(), 8);
+        assert_eq!(registry.offsetof("Holder", "u").unwrap(), 8);
+        assert_eq!(registry.offsetof("Holder", "point.y").unwrap(), 18);
+        assert_eq!(registry.sizeof("Holder").unwrap(), 24);
+    }
+
+    #[test]
+    fn test_arrays_and_nested_paths() {
+        let registry = define(
+            r#"
+            typedef struct Vec3 { float x, y, z; } Vec3;
+            struct Mesh { uint32_t count; Vec3 verts[2][3]; };
+            "#,
+        );
+        assert_eq!(registry.sizeof("Vec3").unwrap(), 12);
+        assert_eq!(registry.sizeof("Mesh").unwrap(), 4 + 72);
+        assert_eq!(
+            registry.offsetof("Mesh", "verts[1][2].z").unwrap(),
+            4 + 60 + 8
+        );
+        assert_eq!(registry.sizeof("uint32_t[4]").unwrap(), 16);
+    }
+
+    #[test]
+    fn test_c_builtin_types() {
+        let registry = define(
+            r#"
+            struct T {
+                unsigned char a;
+                short b;
+                unsigned int c;
+                long d;
+                unsigned long long e;
+                const char* name;
+                void (*callback)(int, void*);
+                enum Kind { KIND_A = 1, KIND_B } kind;
+            };
+            "#,
+        );
+        assert_eq!(registry.offsetof("T", "b").unwrap(), 2);
+        assert_eq!(registry.offsetof("T", "c").unwrap(), 4);
+        assert_eq!(registry.offsetof("T", "d").unwrap(), 8);
+        assert_eq!(registry.offsetof("T", "e").unwrap(), 16);
+        assert_eq!(registry.offsetof("T", "name").unwrap(), 24);
+        assert_eq!(registry.offsetof("T", "callback").unwrap(), 32);
+        assert_eq!(registry.offsetof("T", "kind").unwrap(), 40);
+    }
+
+    #[test]
+    fn test_error_location() {
+        let mut registry = CTypeRegistry::new();
+        let err = registry
+            .define("struct A {\n    int a;\n    foo_t b;\n};")
+            .unwrap_err();
+        assert_eq!((err.line, err.column), (3, 5));
+
+        let err = registry.define("struct B { int a }").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 18));
+
+        let err = registry
+            .define("struct C { struct Missing m; };")
+            .unwrap_err();
+        assert_eq!((err.line, err.column), (1, 27));
+    }
+
+    #[test]
+    fn test_size_overflow() {
+        let mut registry = CTypeRegistry::new();
+        let err = registry
+            .define("struct A {\n    int x[0x7fffffffffffffff];\n};")
+            .unwrap_err();
+        assert_eq!((err.line, err.column), (2, 9));
+        assert!(err.message.contains("too large"));
+
+        let err = registry
+            .define(
+                "struct B { char a[0x7fffffffffffffff]; char b[0x7fffffffffffffff]; char c[2]; };",
+            )
+            .unwrap_err();
+        assert!(err.message.contains("'c' is too large"));
+
+        let err = registry
+            .define("struct C { char a; int b; /* 0xffffffffffffffff */ char c; };")
+            .unwrap_err();
+        assert!(err.message.contains("too large"));
+    }
+
+    #[test]
+    fn test_failed_define_is_atomic() {
+        let mut registry = CTypeRegistry::new();
+        assert!(
+            registry
+                .define("struct A { int a; }; struct B { x y; };")
+                .is_err()
+        );
+        assert!(registry.record("A").is_none());
+    }
+
+    #[test]
+    fn test_forward_declared_pointer_resolves_later() {
+        let registry = define(
+            r#"
+            struct Node { struct Node* next; struct Data* data; };
+            struct Data { uint32_t value; };
+            "#,
+        );
+        let node = registry.record("Node").unwrap();
+        let CType::Pointer(pointee) = &node.field("data").unwrap().ty else {
+            panic!("expected pointer");
+        };
+        assert!(matches!(registry.resolve(pointee), CType::Record(_)));
+    }
+}
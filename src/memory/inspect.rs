@@ -0,0 +1,366 @@
+//! 内存查看辅助：十六进制转储、指针标注与 RTTI 类名解析
+
+use std::fmt::Write;
+
+use super::region::{self, MemoryRegion, ModuleInfo};
+
+/// 十六进制转储选项
+#[derive(Debug, Clone)]
+pub struct HexdumpOptions {
+    /// 每行字节数
+    pub cols: usize,
+    /// 是否显示 ASCII 列
+    pub ascii: bool,
+}
+
+impl Default for HexdumpOptions {
+    fn default() -> Self {
+        Self {
+            cols: 16,
+            ascii: true,
+        }
+    }
+}
+
+/// 生成十六进制转储，`None` 表示不可读的字节
+///
+/// `annotate` 用于标注行内按 8 字节对齐的指针值，返回 None 时不标注。
+pub fn hexdump(
+    address: usize,
+    data: &[Option<u8>],
+    options: &HexdumpOptions,
+    annotate: impl Fn(u64) -> Option<String>,
+) -> String {
+    let cols = options.cols.max(1);
+    let mut output = String::new();
+
+    for (row, chunk) in data.chunks(cols).enumerate() {
+        let row_address = address + row * cols;
+        let _ = write!(output, "{:016X} ", row_address);
+        for i in 0..cols {
+            // 每 8 字节额外分隔
+            if i % 8 == 0 {
+                output.push(' ');
+            }
+            match chunk.get(i) {
+                Some(Some(byte)) => {
+                    let _ = write!(output, "{:02X} ", byte);
+                }
+                Some(None) => output.push_str("?? "),
+                None => output.push_str("   "),
+            }
+        }
+        if options.ascii {
+            output.push_str(" |");
+            for byte in chunk {
+                output.push(match byte {
+                    Some(b @ 0x20..=0x7E) => *b as char,
+                    _ => '.',
+                });
+            }
+            output.push('|');
+        }
+
+        let annotations = pointer_values(row_address, chunk)
+            .filter_map(|(offset, value)| {
+                annotate(value).map(|label| format!("+{:02X} {}", offset, label))
+            })
+            .collect::<Vec<_>>();
+        if !annotations.is_empty() {
+            let _ = write!(output, "  ; {}", annotations.join(", "));
+        }
+
+        output.truncate(output.trim_end().len());
+        output.push('\n');
+    }
+    output
+}
+
+/// 行内按绝对地址 8 字节对齐且完整可读的值，返回 `(行内偏移, 值)`
+fn pointer_values(
+    row_address: usize,
+    chunk: &[Option<u8>],
+) -> impl Iterator<Item = (usize, u64)> + '_ {
+    let first = row_address.next_multiple_of(8) - row_address;
+    (first..chunk.len()).step_by(8).filter_map(move |offset| {
+        let bytes = chunk.get(offset..offset + 8)?;
+        let mut value = [0u8; 8];
+        for (dst, src) in value.iter_mut().zip(bytes) {
+            *dst = (*src)?;
+        }
+        Some((offset, u64::from_le_bytes(value)))
+    })
+}
+
+/// 描述指针值指向的位置，如 `re4.exe+0x1234` 或 `private 0x1F0000+0x10`
+///
+/// `regions` 需按地址升序排列，只标注已提交的区域。
+pub fn pointer_label(
+    value: u64,
+    modules: &[ModuleInfo],
+    regions: &[MemoryRegion],
+) -> Option<String> {
+    let address = usize::try_from(value).ok()?;
+    if let Some(module) = region::module_containing(modules, address) {
+        return Some(format!("{}+0x{:x}", module.name, address - module.base));
+    }
+    let region = region_containing(regions, address)?;
+    if !region.is_committed() {
+        return None;
+    }
+    Some(format!(
+        "{} 0x{:X}+0x{:x}",
+        region.kind_name().unwrap_or("region"),
+        region.allocation_base,
+        address - region.allocation_base
+    ))
+}
+
+/// 二分查找包含地址的区域，`regions` 需按地址升序排列
+pub fn region_containing(regions: &[MemoryRegion], address: usize) -> Option<&MemoryRegion> {
+    let index = regions.partition_point(|r| r.end() <= address);
+    regions.get(index).filter(|r| r.contains(address))
+}
+
+/// 通过 MSVC RTTI 解析对象的类名
+///
+/// 对象首个字段为虚表指针，虚表前一项指向 CompleteObjectLocator。
+/// `read` 读取 `[address, address + len)`，不可读时返回 None。
+pub fn rtti_class_name(
+    object: usize,
+    read: impl Fn(usize, usize) -> Option<Vec<u8>>,
+) -> Option<String> {
+    let read_u64 = |address: usize| -> Option<usize> {
+        let bytes = read(address, 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?) as usize)
+    };
+    let read_u32 = |address: usize| -> Option<u32> {
+        let bytes = read(address, 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    };
+
+    let vtable = read_u64(object)?;
+    let locator = read_u64(vtable.checked_sub(8)?)?;
+    // x64 的 CompleteObjectLocator 签名为 1，其中的描述符以 RVA 表示
+    if read_u32(locator)? != 1 {
+        return None;
+    }
+    // 以下地址均由任意内存中的值计算，不能假设不溢出
+    let type_descriptor_rva = read_u32(locator.checked_add(12)?)? as usize;
+    let self_rva = read_u32(locator.checked_add(20)?)? as usize;
+    let image_base = locator.checked_sub(self_rva)?;
+
+    // TypeDescriptor：虚表指针、保留字段，之后为修饰名
+    let name_address = image_base
+        .checked_add(type_descriptor_rva)?
+        .checked_add(16)?;
+    let mut name = vec![];
+    for offset in (0..MAX_RTTI_NAME).step_by(16) {
+        let chunk = read(name_address.checked_add(offset)?, 16)?;
+        match chunk.iter().position(|&b| b == 0) {
+            Some(end) => {
+                name.extend_from_slice(&chunk[..end]);
+                return demangle_type_name(std::str::from_utf8(&name).ok()?);
+            }
+            None => name.extend_from_slice(&chunk),
+        }
+    }
+    None
+}
+
+/// RTTI 修饰名的最大长度
+const MAX_RTTI_NAME: usize = 1024;
+
+/// 还原 `.?AVName@Namespace@@` 形式的类型名为 `Namespace::Name`
+///
+/// 不是类或结构体时返回 None；模板等复杂名称保留修饰名。
+pub fn demangle_type_name(mangled: &str) -> Option<String> {
+    let name = mangled
+        .strip_prefix(".?AV")
+        .or_else(|| mangled.strip_prefix(".?AU"))?;
+    let Some(name) = name.strip_suffix("@@") else {
+        return Some(mangled.to_string());
+    };
+    if name.is_empty() || name.contains(['?', '$']) {
+        return Some(mangled.to_string());
+    }
+    Some(name.rsplit('@').collect::<Vec<_>>().join("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use windows::Win32::System::Memory::{
+        MEM_COMMIT, MEM_IMAGE, MEM_PRIVATE, MEM_RESERVE, PAGE_READWRITE, PAGE_TYPE,
+        VIRTUAL_ALLOCATION_TYPE,
+    };
+
+    use super::*;
+
+    fn bytes(data: &[u8]) -> Vec<Option<u8>> {
+        data.iter().copied().map(Some).collect()
+    }
+
+    #[test]
+    fn hexdump_rows() {
+        let mut data = bytes(b"Hello, eglib!\x00\x01\x02\x03");
+        data.push(None);
+        let dump = hexdump(0x1000, &data, &HexdumpOptions::default(), |_| None);
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "0000000000001000  48 65 6C 6C 6F 2C 20 65  67 6C 69 62 21 00 01 02  |Hello, eglib!...|"
+        );
+        assert_eq!(
+            lines[1],
+            "0000000000001010  03 ??                                             |..|"
+        );
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn hexdump_without_ascii() {
+        let options = HexdumpOptions {
+            cols: 4,
+            ascii: false,
+        };
+        let dump = hexdump(0x20, &bytes(&[1, 2, 3, 4, 5]), &options, |_| None);
+        assert_eq!(
+            dump,
+            "0000000000000020  01 02 03 04\n0000000000000024  05\n"
+        );
+    }
+
+    #[test]
+    fn hexdump_annotations() {
+        let mut data = vec![0u8; 4];
+        data.extend_from_slice(&0x1_4000_1234u64.to_le_bytes());
+        data.extend_from_slice(&0x1_4000_5678u64.to_le_bytes());
+        let options = HexdumpOptions {
+            cols: 16,
+            ascii: false,
+        };
+        // 起始地址 0x1004，首个对齐值位于行内偏移 4
+        let dump = hexdump(0x1004, &bytes(&data), &options, |value| {
+            (value == 0x1_4000_1234).then(|| "game.exe+0x1234".to_string())
+        });
+        assert!(
+            dump.lines()
+                .next()
+                .unwrap()
+                .ends_with("; +04 game.exe+0x1234")
+        );
+    }
+
+    #[test]
+    fn label_pointers() {
+        let modules = vec![ModuleInfo {
+            name: "game.exe".to_string(),
+            path: String::new(),
+            base: 0x1_4000_0000,
+            size: 0x10000,
+        }];
+        let region = |base: usize, state: VIRTUAL_ALLOCATION_TYPE, kind: PAGE_TYPE| MemoryRegion {
+            base,
+            size: 0x1000,
+            allocation_base: 0x2000_0000,
+            protect: PAGE_READWRITE.0,
+            state: state.0,
+            kind: kind.0,
+        };
+        let regions = vec![
+            region(0x2000_0000, MEM_COMMIT, MEM_PRIVATE),
+            region(0x2000_1000, MEM_RESERVE, MEM_PRIVATE),
+            region(0x1_4000_0000, MEM_COMMIT, MEM_IMAGE),
+        ];
+        assert_eq!(
+            pointer_label(0x1_4000_0010, &modules, &regions).as_deref(),
+            Some("game.exe+0x10")
+        );
+        assert_eq!(
+            pointer_label(0x2000_0020, &modules, &regions).as_deref(),
+            Some("private 0x20000000+0x20")
+        );
+        assert_eq!(pointer_label(0x2000_1000, &modules, &regions), None);
+        assert_eq!(pointer_label(0x1234, &modules, &regions), None);
+        assert_eq!(pointer_label(0x3F80_0000, &modules, &regions), None);
+    }
+
+    #[test]
+    fn demangle_names() {
+        assert_eq!(
+            demangle_type_name(".?AVPlayer@chainsaw@app@@").as_deref(),
+            Some("app::chainsaw::Player")
+        );
+        assert_eq!(demangle_type_name(".?AUNode@@").as_deref(), Some("Node"));
+        assert_eq!(
+            demangle_type_name(".?AV?$vector@HV?$allocator@H@std@@@std@@").as_deref(),
+            Some(".?AV?$vector@HV?$allocator@H@std@@@std@@")
+        );
+        assert_eq!(demangle_type_name(".H"), None);
+    }
+
+    #[test]
+    fn resolve_rtti() {
+        // 映像基址 0x10000，对象位于 0x90000
+        let mut memory = HashMap::new();
+        let mut put = |address: usize, data: &[u8]| {
+            for (i, &b) in data.iter().enumerate() {
+                memory.insert(address + i, b);
+            }
+        };
+        let image_base = 0x10000usize;
+        let vtable = image_base + 0x2008;
+        let locator = image_base + 0x3000;
+        let type_descriptor = image_base + 0x4000;
+
+        put(0x90000, &(vtable as u64).to_le_bytes());
+        put(vtable - 8, &(locator as u64).to_le_bytes());
+        let mut col = vec![0u8; 24];
+        col[0..4].copy_from_slice(&1u32.to_le_bytes());
+        col[12..16].copy_from_slice(&0x4000u32.to_le_bytes());
+        col[20..24].copy_from_slice(&0x3000u32.to_le_bytes());
+        put(locator, &col);
+        let mut name = vec![0u8; 16];
+        name.extend_from_slice(b".?AVEnemy@app@@\0");
+        name.resize(64, 0xCC);
+        put(type_descriptor, &name);
+
+        let read = |address: usize, len: usize| {
+            (address..address + len)
+                .map(|a| memory.get(&a).copied())
+                .collect::<Option<Vec<_>>>()
+        };
+        assert_eq!(
+            rtti_class_name(0x90000, read).as_deref(),
+            Some("app::Enemy")
+        );
+        // 虚表指针指向不可读的内存
+        assert_eq!(rtti_class_name(0x90004, read), None);
+        assert_eq!(rtti_class_name(0x1234, read), None);
+    }
+
+    #[test]
+    fn rtti_address_overflow() {
+        // 定位器位于地址空间末尾，后续字段地址溢出
+        let locator = usize::MAX - 4;
+        let mut memory = HashMap::new();
+        for (i, b) in (0x2008u64).to_le_bytes().into_iter().enumerate() {
+            memory.insert(0x1000 + i, b);
+        }
+        for (i, b) in (locator as u64).to_le_bytes().into_iter().enumerate() {
+            memory.insert(0x2000 + i, b);
+        }
+        for (i, b) in 1u32.to_le_bytes().into_iter().enumerate() {
+            memory.insert(locator + i, b);
+        }
+
+        let read = |address: usize, len: usize| {
+            (0..len)
+                .map(|i| memory.get(&address.checked_add(i)?).copied())
+                .collect::<Option<Vec<_>>>()
+        };
+        assert_eq!(rtti_class_name(0x1000, read), None);
+    }
+}
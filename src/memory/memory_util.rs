@@ -1,15 +1,37 @@
-use std::{io::Cursor, slice};
+use std::{io::Cursor, slice, sync::atomic::AtomicBool};
 
 use super::{
-    MemoryError, pattern_scan,
-    windows_util::{self, VirtualProtectGuard},
+    MemoryError, inspect,
+    parallel_scan::{self, ParallelScanError},
+    pattern_scan,
+    pdata::{FunctionInfo, FunctionTable, RuntimeFunction},
+    pe::{PeError, PeImage},
+    region::{self, MemoryRegion, ModuleInfo, RegionFilter},
+    scan_cache::ImageIdentity,
+    signature::SignatureError,
+    signature_gen, thunk,
+    windows_util::{self, ExecutableMemory, ModulePin, VirtualProtectGuard},
 };
 
-use windows::Win32::System::Memory::PAGE_EXECUTE_READWRITE;
+use windows::Win32::System::Memory::{PAGE_EXECUTE_READWRITE, PAGE_PROTECTION_FLAGS};
 pub use windows_util::MemoryState;
 
 pub struct MemoryUtils;
 
+/// 固定模块后解析的 PE 映像，持有期间模块不会被卸载
+pub struct PinnedImage {
+    // 先于 `_pin` 释放
+    image: PeImage<'static>,
+    _pin: ModulePin,
+}
+
+impl PinnedImage {
+    /// 映像借用的内存只在 `self` 存活期间有效
+    pub fn image(&self) -> &PeImage<'_> {
+        &self.image
+    }
+}
+
 impl MemoryUtils {
     /// 扫描内存，查找匹配的第一个地址
     pub fn scan_first(base: usize, size: usize, pattern: &str) -> Result<usize, MemoryError> {
@@ -42,11 +64,168 @@ impl MemoryUtils {
         }
     }
 
+    /// 多线程扫描内存，`cancel` 置位时尽快返回 [`MemoryError::ScanCancelled`]
+    pub fn scan_parallel(
+        base: usize,
+        size: usize,
+        pattern: &str,
+        all: bool,
+        cancel: &AtomicBool,
+    ) -> Result<Vec<usize>, MemoryError> {
+        let memory_slice = unsafe { slice::from_raw_parts(base as *const u8, size) };
+
+        let result =
+            parallel_scan::scan(memory_slice, pattern, all, cancel).map_err(|e| match e {
+                ParallelScanError::Pattern(e) => MemoryError::PatternScan(e),
+                ParallelScanError::Cancelled => MemoryError::ScanCancelled,
+            })?;
+        if result.is_empty() {
+            return Err(MemoryError::NotFound(pattern.to_string()));
+        }
+        Ok(result.into_iter().map(|v| v + base).collect())
+    }
+
     pub unsafe fn get_base_module_space() -> Result<(usize, usize), MemoryError> {
         let (base, size) = unsafe { windows_util::get_base_module_space() }?;
         Ok((base, size))
     }
 
+    /// 检查地址处的字节是否与特征码匹配
+    pub fn pattern_matches_at(address: usize, pattern: &str) -> Result<bool, MemoryError> {
+        let pattern = pattern.parse::<pattern_scan::Pattern>()?;
+        if pattern.is_empty() {
+            return Ok(false);
+        }
+        let bytes = Self::read(address, pattern.len(), true)?;
+        Ok(pattern_scan::pattern_matches(&bytes, &pattern))
+    }
+
+    /// 为 `address` 生成在 `[base, base + size)` 内唯一的特征码
+    pub fn make_signature(
+        base: usize,
+        size: usize,
+        address: usize,
+        max_len: usize,
+        wildcard_operands: bool,
+    ) -> Result<String, SignatureError> {
+        if address < base || address >= base + size {
+            return Err(SignatureError::OutOfModule(address));
+        }
+        // 多读取一条最长指令，避免截断最后一条指令
+        let code_len = (max_len + 15).min(base + size - address);
+        let code = Self::read(address, code_len, true)?;
+        signature_gen::generate(
+            &code,
+            address,
+            max_len,
+            wildcard_operands,
+            |pattern| Self::scan_all(base, size, pattern),
+            |candidate, pattern| Self::pattern_matches_at(candidate, pattern).unwrap_or(false),
+        )
+    }
+
+    /// 列出已加载的模块，第一个为主模块
+    pub fn modules() -> Result<Vec<ModuleInfo>, MemoryError> {
+        Ok(unsafe { windows_util::enumerate_modules() }?)
+    }
+
+    /// 获取主模块信息
+    pub fn main_module() -> Result<ModuleInfo, MemoryError> {
+        Self::modules()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| MemoryError::NotFound("main module".to_string()))
+    }
+
+    /// 解析 `[base, base + size)` 处已映射的 PE 映像
+    ///
+    /// # Safety
+    ///
+    /// `[base, base + size)` 须为已加载模块的映射范围。映像直接借用模块内存，
+    /// 调用者须保证模块在 `'a` 期间不被卸载，例如持有 [`ModulePin`] 或只用于主模块。
+    pub unsafe fn mapped_image<'a>(base: usize, size: usize) -> Result<PeImage<'a>, PeError> {
+        let image_bytes = unsafe { slice::from_raw_parts(base as *const u8, size) };
+        PeImage::mapped(image_bytes)
+    }
+
+    /// 固定 `module` 并解析其 PE 映像，模块已卸载时返回 `None`
+    pub fn pin_image(module: &ModuleInfo) -> Result<Option<PinnedImage>, PeError> {
+        let Some(pin) = ModulePin::new(module.base) else {
+            return Ok(None);
+        };
+        // 原模块已卸载，同一地址上加载了其他模块
+        if pin.base() != module.base {
+            return Ok(None);
+        }
+        // SAFETY: `pin` 与映像一同保存，映像存活期间模块不会被卸载
+        let image = unsafe { Self::mapped_image(module.base, module.size) }?;
+        Ok(Some(PinnedImage { image, _pin: pin }))
+    }
+
+    /// 查找包含 `address` 的函数，`[base, base + size)` 为已映射的模块
+    ///
+    /// # Safety
+    ///
+    /// 同 [`MemoryUtils::mapped_image`]，模块在调用期间不能被卸载。
+    pub unsafe fn function_at(
+        base: usize,
+        size: usize,
+        address: usize,
+    ) -> Result<Option<FunctionInfo>, PeError> {
+        if address < base || address >= base + size {
+            return Ok(None);
+        }
+        let image = unsafe { Self::mapped_image(base, size) }?;
+        FunctionTable::new(&image).function_at((address - base) as u32)
+    }
+
+    /// 列出已映射模块中的所有主函数
+    ///
+    /// # Safety
+    ///
+    /// 同 [`MemoryUtils::mapped_image`]，模块在调用期间不能被卸载。
+    pub unsafe fn functions(base: usize, size: usize) -> Result<Vec<RuntimeFunction>, PeError> {
+        let image = unsafe { Self::mapped_image(base, size) }?;
+        FunctionTable::new(&image).functions()
+    }
+
+    /// 列出满足条件的内存区域
+    pub fn regions(
+        filter: &RegionFilter,
+    ) -> Result<Vec<(MemoryRegion, Option<ModuleInfo>)>, MemoryError> {
+        let modules = unsafe { windows_util::enumerate_modules() }?;
+        let regions = windows_util::query_regions()
+            .into_iter()
+            .filter_map(|region| {
+                let module = region::module_containing(&modules, region.base);
+                filter
+                    .matches(&region, module)
+                    .then(|| (region, module.cloned()))
+            })
+            .collect();
+        Ok(regions)
+    }
+
+    /// 查询包含地址的内存区域
+    pub fn region_at(address: usize) -> Option<MemoryRegion> {
+        windows_util::query_region(address)
+    }
+
+    /// 通过 RTTI 解析对象的类名
+    pub fn rtti_class_name(object: usize) -> Option<String> {
+        inspect::rtti_class_name(object, |address, size| {
+            Self::read_lossy(address, size).into_iter().collect()
+        })
+    }
+
+    /// 读取已加载映像的 PE 标识
+    pub fn image_identity(base: usize) -> Result<ImageIdentity, MemoryError> {
+        // 映像头部至少占用一页
+        let header = Self::read(base, 0x1000, true)?;
+        ImageIdentity::from_header(&header)
+            .ok_or_else(|| MemoryError::NotFound(format!("PE header at 0x{:x}", base)))
+    }
+
     /// 自动获取主模块地址，并扫描内存，查找匹配的第一个地址
     pub fn auto_scan_first(pattern: &str) -> Result<usize, MemoryError> {
         let (base, size) = unsafe { windows_util::get_base_module_space() }?;
@@ -86,7 +265,7 @@ impl MemoryUtils {
             return Err(MemoryError::InvalidSize(size));
         }
         if safe {
-            Self::check_permission_read(address)?;
+            Self::check_range_read(address, size)?;
         } else if Self::is_in_reserved_range(address) {
             return Err(MemoryError::PagePermNoRead(address));
         }
@@ -95,6 +274,31 @@ impl MemoryUtils {
         Ok(memory_slice.to_vec())
     }
 
+    /// 按内存区域读取，不可读的字节为 None
+    ///
+    /// 结果按 `size` 预先分配，调用者须限制 `size`。
+    pub fn read_lossy(address: usize, size: usize) -> Vec<Option<u8>> {
+        let end = address.saturating_add(size);
+        let mut data = Vec::with_capacity(end - address);
+        let mut current = address;
+        while current < end {
+            let region = windows_util::query_region(current);
+            let chunk_end = match &region {
+                Some(region) if region.end() > current => region.end().min(end),
+                _ => end,
+            };
+            if region.is_some_and(|r| r.is_readable()) {
+                let chunk =
+                    unsafe { slice::from_raw_parts(current as *const u8, chunk_end - current) };
+                data.extend(chunk.iter().copied().map(Some));
+            } else {
+                data.resize(data.len() + (chunk_end - current), None);
+            }
+            current = chunk_end;
+        }
+        data
+    }
+
     /// 读取8字节以内的小内存数据
     pub fn quick_read(address: usize, size: u32, safe: bool) -> Result<[u8; 8], MemoryError> {
         if size == 0 || size > 8 {
@@ -165,6 +369,23 @@ impl MemoryUtils {
         Ok(())
     }
 
+    /// 检查 `[address, address + size)` 经过的所有内存区域是否都可读
+    pub fn check_range_read(address: usize, size: usize) -> Result<(), MemoryError> {
+        let end = address
+            .checked_add(size)
+            .ok_or(MemoryError::InvalidSize(size))?;
+        let mut current = address;
+        while current < end {
+            match windows_util::query_region(current) {
+                Some(region) if region.is_readable() && region.end() > current => {
+                    current = region.end();
+                }
+                _ => return Err(MemoryError::PagePermNoRead(current)),
+            }
+        }
+        Ok(())
+    }
+
     /// 检查内存页是否可执行
     pub fn check_permission_execute(address: usize) -> Result<(), MemoryError> {
         let state = Self::get_page_state(address)?;
@@ -270,6 +491,21 @@ impl MemoryUtils {
         Ok(backup)
     }
 
+    /// 修改页保护，返回首页原来的保护标志，不会自动恢复
+    pub fn set_protection(address: usize, size: usize, protect: u32) -> Result<u32, MemoryError> {
+        if size == 0 {
+            return Err(MemoryError::InvalidSize(size));
+        }
+        let guard = VirtualProtectGuard::try_new(
+            address as *const _,
+            size,
+            PAGE_PROTECTION_FLAGS(protect),
+        )?;
+        let old_protect = guard.old_protect();
+        guard.forget();
+        Ok(old_protect)
+    }
+
     /// 通过特征码扫描获取静态变量的调用点，并通过相对地址计算绝对地址。
     pub fn scan_relative_static(pattern: &str, offset: isize) -> Result<usize, MemoryError> {
         let scan_result = MemoryUtils::auto_scan_first(pattern)?;
@@ -282,6 +518,14 @@ impl MemoryUtils {
         Ok(abs_ptr)
     }
 
+    /// 分配回调跳板，跳板被调用时以 `context` 为首个参数调用 `dispatch`
+    ///
+    /// 调用约定见 [`thunk`] 模块说明。
+    pub fn alloc_thunk(context: usize, dispatch: usize) -> Result<ExecutableMemory, MemoryError> {
+        let code = thunk::build_thunk(context as u64, dispatch as u64);
+        ExecutableMemory::try_new(&code)
+    }
+
     /// 指针是否在可能的保留区范围
     fn is_in_reserved_range(address: usize) -> bool {
         (0..=0x10000).contains(&address) || address > i64::MAX as usize
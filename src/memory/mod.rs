@@ -1,10 +1,33 @@
 #![allow(dead_code)]
 
+mod cdef;
+mod inspect;
 mod memory_util;
+mod parallel_scan;
 mod pattern_scan;
+mod pdata;
+mod pe;
+mod region;
+mod scan_cache;
+mod signature;
+mod signature_gen;
+mod snapshot;
+mod thunk;
 mod windows_util;
 
-pub use memory_util::MemoryUtils;
+pub use cdef::{CType, CTypeRegistry, CdefError, Field, Layout};
+pub use inspect::{HexdumpOptions, hexdump, pointer_label};
+pub use memory_util::{MemoryUtils, PinnedImage};
+pub use pattern_scan::Pattern;
+pub use pdata::{FunctionInfo, RuntimeFunction};
+pub use pe::{Export, PeError, PeImage, Section};
+pub use region::{
+    MemoryRegion, ModuleInfo, RegionFilter, find_module, protection_from_flags, protection_to_flags,
+};
+pub use scan_cache::{CacheSnapshot, ImageIdentity, ScanCache};
+pub use signature::{SignatureDb, SignatureError, SignatureReport};
+pub use snapshot::{ChangedRange, Snapshot, SnapshotError, TypedValue, typed_values};
+pub use windows_util::{ExecutableMemory, VirtualProtectGuard};
 
 #[derive(Debug, thiserror::Error)]
 pub enum MemoryError {
@@ -26,9 +49,13 @@ pub enum MemoryError {
     PageNotCommit(usize),
     #[error("VirtualProtect error: {0}")]
     VirtualProtect(windows::core::Error),
+    #[error("Invalid protection flags: {0}")]
+    InvalidProtection(String),
 
     #[error("pattern scan error: {0}")]
     PatternScan(#[from] pattern_scan::Error),
+    #[error("pattern scan cancelled")]
+    ScanCancelled,
 
     #[error("windows error: {0}")]
     Windows(#[from] windows::core::Error),
@@ -0,0 +1,150 @@
+//! 多线程特征码扫描，可中途取消
+
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::pattern_scan::{self, Pattern};
+
+/// 每次扫描的区块大小，取消标志在区块之间检查
+const SLICE_SIZE: usize = 0x40_0000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParallelScanError {
+    #[error("{0}")]
+    Pattern(#[from] pattern_scan::Error),
+    #[error("scan cancelled")]
+    Cancelled,
+}
+
+/// 将 `bytes` 切分为区块，由多个线程并行扫描，返回匹配位置（升序）
+///
+/// `all` 为 false 时只返回第一个匹配。`cancel` 被置位后尽快返回 [`ParallelScanError::Cancelled`]。
+pub fn scan(
+    bytes: &[u8],
+    pattern: &str,
+    all: bool,
+    cancel: &AtomicBool,
+) -> Result<Vec<usize>, ParallelScanError> {
+    let pattern_len = pattern.parse::<Pattern>()?.len();
+    if pattern_len == 0 || bytes.len() < pattern_len {
+        return Ok(vec![]);
+    }
+
+    let slice_count = bytes.len().div_ceil(SLICE_SIZE);
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(slice_count);
+    let next_slice = AtomicUsize::new(0);
+    // 已知的最靠前匹配，仅查找第一个匹配时用于跳过其后的区块
+    let first_match = AtomicUsize::new(usize::MAX);
+
+    let worker = || -> Result<Vec<usize>, ParallelScanError> {
+        let mut found = vec![];
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(ParallelScanError::Cancelled);
+            }
+            let index = next_slice.fetch_add(1, Ordering::Relaxed);
+            let start = index * SLICE_SIZE;
+            if start >= bytes.len() || (!all && start > first_match.load(Ordering::Relaxed)) {
+                break;
+            }
+            // 区块之间重叠 pattern_len - 1 字节，保证跨区块的匹配不会遗漏或重复
+            let end = (start + SLICE_SIZE + pattern_len - 1).min(bytes.len());
+            let reader = Cursor::new(&bytes[start..end]);
+            if all {
+                found.extend(
+                    pattern_scan::scan(reader, pattern)?
+                        .into_iter()
+                        .map(|i| i + start),
+                );
+            } else if let Some(i) = pattern_scan::scan_first_match(reader, pattern)? {
+                first_match.fetch_min(start + i, Ordering::Relaxed);
+                found.push(start + i);
+            }
+        }
+        Ok(found)
+    };
+
+    let results = std::thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|_| scope.spawn(worker))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("scan worker panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut matches = vec![];
+    for result in results {
+        matches.extend(result?);
+    }
+    matches.sort_unstable();
+    if !all {
+        matches.truncate(1);
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes_with_matches(len: usize, positions: &[usize]) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        for &pos in positions {
+            bytes[pos..pos + 4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn find_all_across_slices() {
+        // 包含跨越区块边界的匹配
+        let positions = [10, SLICE_SIZE - 2, SLICE_SIZE * 2 + 7, SLICE_SIZE * 3 - 1];
+        let bytes = bytes_with_matches(SLICE_SIZE * 3 + 16, &positions);
+        let cancel = AtomicBool::new(false);
+
+        let matches = scan(&bytes, "DE AD ?? EF", true, &cancel).unwrap();
+        assert_eq!(matches, positions);
+    }
+
+    #[test]
+    fn find_first_only() {
+        let positions = [SLICE_SIZE + 5, SLICE_SIZE * 2 + 9];
+        let bytes = bytes_with_matches(SLICE_SIZE * 3, &positions);
+        let cancel = AtomicBool::new(false);
+
+        let matches = scan(&bytes, "DE AD BE EF", false, &cancel).unwrap();
+        assert_eq!(matches, vec![SLICE_SIZE + 5]);
+    }
+
+    #[test]
+    fn no_match() {
+        let bytes = vec![0u8; 1024];
+        let cancel = AtomicBool::new(false);
+        assert!(scan(&bytes, "DE AD", true, &cancel).unwrap().is_empty());
+        assert!(
+            scan(&bytes[..1], "00 00", false, &cancel)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn cancelled() {
+        let bytes = vec![0u8; 1024];
+        let cancel = AtomicBool::new(true);
+        let result = scan(&bytes, "DE AD", true, &cancel);
+        assert!(matches!(result, Err(ParallelScanError::Cancelled)));
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let cancel = AtomicBool::new(false);
+        let result = scan(&[0u8; 16], "XYZ", true, &cancel);
+        assert!(matches!(result, Err(ParallelScanError::Pattern(_))));
+    }
+}
@@ -120,10 +120,36 @@ impl Pattern {
         Self { bytes }
     }
 
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.bytes.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Overlay the fixed bytes of this pattern onto `original`, keeping the original byte at every
+    /// wildcard position.
+    ///
+    /// Only the first `self.len()` bytes of `original` are used.
+    pub fn overlay(&self, original: &[u8]) -> Vec<u8> {
+        Iterator::zip(self.bytes.iter(), original.iter())
+            .map(|(pb, b)| match pb {
+                PatternByte::Byte(n) => *n,
+                PatternByte::Any => *b,
+            })
+            .collect()
+    }
+
+    /// Range of the pattern between its first and last fixed byte, with leading and trailing
+    /// wildcards trimmed. Returns `None` if the pattern has no fixed byte.
+    pub fn fixed_span(&self) -> Option<std::ops::Range<usize>> {
+        let is_fixed = |pb: &PatternByte| matches!(pb, PatternByte::Byte(_));
+        let start = self.bytes.iter().position(is_fixed)?;
+        let end = self.bytes.iter().rposition(is_fixed)? + 1;
+        Some(start..end)
+    }
+
     pub fn scan(self, reader: impl Read) -> Result<Vec<usize>, Error> {
         let matches = Matches::from_pattern(reader, self)?;
         matches.collect()
@@ -458,4 +484,21 @@ mod tests {
             super::CHUNK_SIZE
         );
     }
+
+    #[test]
+    fn overlay_keeps_wildcard_bytes() {
+        let pattern = Pattern::from_str("?? ?? 90 90 EB").unwrap();
+        let original = [0x48, 0x8b, 0x74, 0x05, 0x75, 0xcc];
+
+        assert_eq!(pattern.overlay(&original), vec![0x48, 0x8b, 0x90, 0x90, 0xeb]);
+    }
+
+    #[test]
+    fn fixed_span_trims_wildcards() {
+        let pattern = Pattern::from_str("?? 90 ?? 90 ??").unwrap();
+        assert_eq!(pattern.fixed_span(), Some(1..4));
+
+        let pattern = Pattern::from_str("?? ??").unwrap();
+        assert_eq!(pattern.fixed_span(), None);
+    }
 }
@@ -0,0 +1,241 @@
+//! 基于 x64 `.pdata` 异常目录的函数边界查询
+//!
+//! 异常目录是按起始地址排序的 RUNTIME_FUNCTION 数组。
+//! 编译器拆分出的函数片段带有 `UNW_FLAG_CHAININFO`，其展开信息末尾链接到所属函数的条目。
+
+use super::pe::{DIRECTORY_EXCEPTION, PeError, PeImage};
+
+const RUNTIME_FUNCTION_SIZE: u32 = 12;
+const UNW_FLAG_CHAININFO: u8 = 0x4;
+/// 链式条目的最大深度，防止损坏的映像造成死循环
+const MAX_CHAIN_DEPTH: usize = 32;
+
+/// RUNTIME_FUNCTION，地址均为 RVA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeFunction {
+    pub begin: u32,
+    pub end: u32,
+    pub unwind_info: u32,
+}
+
+/// 函数信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionInfo {
+    /// 所属主函数的条目
+    pub primary: RuntimeFunction,
+    /// 从包含查询地址的片段到主函数的条目链，仅一个元素时表示没有链式条目
+    pub chain: Vec<RuntimeFunction>,
+}
+
+pub struct FunctionTable<'a> {
+    image: &'a PeImage<'a>,
+    rva: u32,
+    count: u32,
+}
+
+impl<'a> FunctionTable<'a> {
+    /// 读取异常目录，映像没有 `.pdata` 时返回空表
+    pub fn new(image: &'a PeImage<'a>) -> Self {
+        let (rva, size) = image.data_directory(DIRECTORY_EXCEPTION).unwrap_or((0, 0));
+        Self {
+            image,
+            rva,
+            count: size / RUNTIME_FUNCTION_SIZE,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// 读取第 `index` 个条目
+    pub fn get(&self, index: usize) -> Result<RuntimeFunction, PeError> {
+        self.read_entry(self.rva + index as u32 * RUNTIME_FUNCTION_SIZE)
+    }
+
+    fn read_entry(&self, rva: u32) -> Result<RuntimeFunction, PeError> {
+        Ok(RuntimeFunction {
+            begin: self.image.read_u32(rva)?,
+            end: self.image.read_u32(rva + 4)?,
+            unwind_info: self.image.read_u32(rva + 8)?,
+        })
+    }
+
+    /// 二分查找包含 `rva` 的条目
+    pub fn lookup(&self, rva: u32) -> Result<Option<RuntimeFunction>, PeError> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry = self.get(mid)?;
+            if rva < entry.begin {
+                high = mid;
+            } else if rva >= entry.end {
+                low = mid + 1;
+            } else {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 查找包含 `rva` 的函数，并沿链式条目找到主函数
+    pub fn function_at(&self, rva: u32) -> Result<Option<FunctionInfo>, PeError> {
+        let Some(mut entry) = self.lookup(rva)? else {
+            return Ok(None);
+        };
+        let mut chain = vec![entry];
+        while let Some(parent) = self.parent(&entry)? {
+            if chain.len() >= MAX_CHAIN_DEPTH || chain.contains(&parent) {
+                break;
+            }
+            chain.push(parent);
+            entry = parent;
+        }
+        Ok(Some(FunctionInfo {
+            primary: entry,
+            chain,
+        }))
+    }
+
+    /// 所有主函数条目，跳过链式片段
+    pub fn functions(&self) -> Result<Vec<RuntimeFunction>, PeError> {
+        let mut functions = Vec::with_capacity(self.len());
+        for index in 0..self.len() {
+            let entry = self.get(index)?;
+            if self.parent(&entry)?.is_none() {
+                functions.push(entry);
+            }
+        }
+        Ok(functions)
+    }
+
+    /// 链式条目指向的上级条目
+    fn parent(&self, entry: &RuntimeFunction) -> Result<Option<RuntimeFunction>, PeError> {
+        // 最低位为 1 时直接指向另一个 RUNTIME_FUNCTION
+        if entry.unwind_info & 1 != 0 {
+            return self.read_entry(entry.unwind_info & !1).map(Some);
+        }
+        let header = self.image.slice(entry.unwind_info, 4)?;
+        let flags = header[0] >> 3;
+        if flags & UNW_FLAG_CHAININFO == 0 {
+            return Ok(None);
+        }
+        // 展开代码数量按 2 对齐，每个 2 字节
+        let code_count = (header[2] as u32 + 1) & !1;
+        let chained = entry.unwind_info + 4 + code_count * 2;
+        self.read_entry(chained).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::pe::test_image::ImageBuilder;
+    use super::*;
+
+    /// 构造含两个主函数与三个链式片段的映像
+    ///
+    /// - 0x1000..0x1040 主函数 A
+    /// - 0x1040..0x1080 主函数 B
+    /// - 0x1080..0x1090 A 的片段（直接链到 A）
+    /// - 0x1090..0x10A0 片段的片段（链到上一个片段）
+    /// - 0x10A0..0x10B0 B 的片段（以最低位标记直接指向 B 的条目）
+    fn build() -> (Vec<u8>, Vec<u8>) {
+        let mut builder = ImageBuilder::new();
+        builder.section(".text", vec![0xCC; 0xB0]);
+        let rdata = 0x2000;
+        let pdata = 0x3000;
+
+        let entry = |begin: u32, end: u32, unwind: u32| {
+            [begin, end, unwind]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+        let a = entry(0x1000, 0x1040, rdata);
+        let fragment = entry(0x1080, 0x1090, rdata + 0x20);
+
+        // 展开信息：版本 1，无标志，1 个展开代码
+        let mut unwind = vec![0u8; 0x60];
+        unwind[0x00..0x06].copy_from_slice(&[0x01, 0x04, 0x01, 0x00, 0x04, 0x42]);
+        // 链式展开信息：3 个展开代码对齐为 4 个，链接条目位于 4 + 8
+        unwind[0x20..0x24].copy_from_slice(&[0x01 | (UNW_FLAG_CHAININFO << 3), 0, 3, 0]);
+        unwind[0x2C..0x38].copy_from_slice(&a);
+        unwind[0x40..0x44].copy_from_slice(&[0x01 | (UNW_FLAG_CHAININFO << 3), 0, 0, 0]);
+        unwind[0x44..0x50].copy_from_slice(&fragment);
+        builder.section(".rdata", unwind);
+
+        let table = [
+            a,
+            entry(0x1040, 0x1080, rdata),
+            fragment,
+            entry(0x1090, 0x10A0, rdata + 0x40),
+            entry(0x10A0, 0x10B0, (pdata + 12) | 1),
+        ]
+        .concat();
+        let len = table.len() as u32;
+        builder.section(".pdata", table);
+        builder.directory(DIRECTORY_EXCEPTION, pdata, len);
+        builder.build()
+    }
+
+    #[test]
+    fn lookup_primary() {
+        let (mapped, file) = build();
+        for image in [
+            PeImage::mapped(&mapped).unwrap(),
+            PeImage::file(&file).unwrap(),
+        ] {
+            let table = FunctionTable::new(&image);
+            assert_eq!(table.len(), 5);
+
+            let info = table.function_at(0x1050).unwrap().unwrap();
+            assert_eq!((info.primary.begin, info.primary.end), (0x1040, 0x1080));
+            assert_eq!(info.chain.len(), 1);
+
+            assert!(table.function_at(0x0FFF).unwrap().is_none());
+            assert!(table.function_at(0x10B0).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn follow_chain() {
+        let (mapped, file) = build();
+        for image in [
+            PeImage::mapped(&mapped).unwrap(),
+            PeImage::file(&file).unwrap(),
+        ] {
+            let table = FunctionTable::new(&image);
+
+            let info = table.function_at(0x1095).unwrap().unwrap();
+            assert_eq!(info.primary.begin, 0x1000);
+            let begins = info.chain.iter().map(|e| e.begin).collect::<Vec<_>>();
+            assert_eq!(begins, vec![0x1090, 0x1080, 0x1000]);
+
+            // 最低位标记的间接条目
+            let info = table.function_at(0x10A0).unwrap().unwrap();
+            assert_eq!(info.primary.begin, 0x1040);
+        }
+    }
+
+    #[test]
+    fn enumerate_primary_functions() {
+        let (mapped, _) = build();
+        let image = PeImage::mapped(&mapped).unwrap();
+        let functions = FunctionTable::new(&image).functions().unwrap();
+        let begins = functions.iter().map(|e| e.begin).collect::<Vec<_>>();
+        assert_eq!(begins, vec![0x1000, 0x1040]);
+    }
+
+    #[test]
+    fn no_exception_directory() {
+        let (mapped, _) = ImageBuilder::new().build();
+        let image = PeImage::mapped(&mapped).unwrap();
+        let table = FunctionTable::new(&image);
+        assert!(table.is_empty());
+        assert!(table.function_at(0x1000).unwrap().is_none());
+    }
+}
@@ -0,0 +1,482 @@
+//! PE 映像解析，支持内存中已映射的映像与磁盘上的 PE 文件
+
+/// PE32+ 可选头的魔数
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+/// 数据目录在 PE32+ 可选头中的偏移
+const DATA_DIRECTORY_OFFSET: usize = 112;
+
+pub const DIRECTORY_EXPORT: usize = 0;
+pub const DIRECTORY_EXCEPTION: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PeError {
+    #[error("invalid PE header")]
+    InvalidHeader,
+    #[error("not a PE32+ image")]
+    NotPe32Plus,
+    #[error("RVA 0x{0:x} is out of bounds")]
+    OutOfBounds(u32),
+    #[error("invalid string at RVA 0x{0:x}")]
+    InvalidString(u32),
+}
+
+/// 节表项
+#[derive(Debug, Clone, Copy)]
+pub struct Section {
+    pub name: [u8; 8],
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    pub fn name(&self) -> String {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(8);
+        String::from_utf8_lossy(&self.name[..len]).into_owned()
+    }
+
+    /// 节在内存中是否包含 `rva`
+    pub fn contains(&self, rva: u32) -> bool {
+        rva >= self.virtual_address && rva - self.virtual_address < self.virtual_size
+    }
+}
+
+/// 导出项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub rva: u32,
+    /// 转发到其他模块的导出，如 `NTDLL.RtlAllocateHeap`，此时 `rva` 指向转发字符串
+    pub forwarder: Option<String>,
+}
+
+/// 只读的 PE 映像视图
+pub struct PeImage<'a> {
+    bytes: &'a [u8],
+    /// true 表示按内存布局映射，RVA 即偏移
+    mapped: bool,
+    optional_header: usize,
+    sections: Vec<Section>,
+}
+
+impl<'a> PeImage<'a> {
+    /// 解析内存中已映射的映像，`bytes` 从模块基址开始
+    pub fn mapped(bytes: &'a [u8]) -> Result<Self, PeError> {
+        Self::parse(bytes, true)
+    }
+
+    /// 解析磁盘上的 PE 文件内容
+    pub fn file(bytes: &'a [u8]) -> Result<Self, PeError> {
+        Self::parse(bytes, false)
+    }
+
+    fn parse(bytes: &'a [u8], mapped: bool) -> Result<Self, PeError> {
+        let read_u16 = |offset: usize| read_u16(bytes, offset).ok_or(PeError::InvalidHeader);
+        let read_u32 = |offset: usize| read_u32(bytes, offset).ok_or(PeError::InvalidHeader);
+
+        if bytes.get(0..2) != Some(b"MZ".as_slice()) {
+            return Err(PeError::InvalidHeader);
+        }
+        let nt = read_u32(0x3C)? as usize;
+        if bytes.get(nt..nt + 4) != Some(b"PE\0\0".as_slice()) {
+            return Err(PeError::InvalidHeader);
+        }
+        let file_header = nt + 4;
+        let section_count = read_u16(file_header + 2)? as usize;
+        let optional_header_size = read_u16(file_header + 16)? as usize;
+        let optional_header = file_header + 20;
+        if read_u16(optional_header)? != PE32_PLUS_MAGIC {
+            return Err(PeError::NotPe32Plus);
+        }
+
+        let section_table = optional_header + optional_header_size;
+        let sections = (0..section_count)
+            .map(|i| {
+                let offset = section_table + i * 40;
+                let name = bytes
+                    .get(offset..offset + 8)
+                    .ok_or(PeError::InvalidHeader)?
+                    .try_into()
+                    .unwrap();
+                Ok(Section {
+                    name,
+                    virtual_size: read_u32(offset + 8)?,
+                    virtual_address: read_u32(offset + 12)?,
+                    raw_size: read_u32(offset + 16)?,
+                    raw_offset: read_u32(offset + 20)?,
+                    characteristics: read_u32(offset + 36)?,
+                })
+            })
+            .collect::<Result<Vec<_>, PeError>>()?;
+
+        Ok(Self {
+            bytes,
+            mapped,
+            optional_header,
+            sections,
+        })
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn timestamp(&self) -> u32 {
+        read_u32(self.bytes, self.optional_header - 20 + 4).unwrap_or(0)
+    }
+
+    pub fn checksum(&self) -> u32 {
+        read_u32(self.bytes, self.optional_header + 64).unwrap_or(0)
+    }
+
+    pub fn size_of_image(&self) -> u32 {
+        read_u32(self.bytes, self.optional_header + 56).unwrap_or(0)
+    }
+
+    pub fn entry_point(&self) -> u32 {
+        read_u32(self.bytes, self.optional_header + 16).unwrap_or(0)
+    }
+
+    /// 数据目录 `(rva, size)`，不存在时返回 None
+    pub fn data_directory(&self, index: usize) -> Option<(u32, u32)> {
+        let count = read_u32(self.bytes, self.optional_header + DATA_DIRECTORY_OFFSET - 4)?;
+        if index >= count as usize {
+            return None;
+        }
+        let offset = self.optional_header + DATA_DIRECTORY_OFFSET + index * 8;
+        let rva = read_u32(self.bytes, offset)?;
+        let size = read_u32(self.bytes, offset + 4)?;
+        (rva != 0 && size != 0).then_some((rva, size))
+    }
+
+    /// 将 RVA 转换为 `bytes` 内的偏移
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        if self.mapped {
+            return ((rva as usize) < self.bytes.len()).then_some(rva as usize);
+        }
+        // 头部不属于任何节，文件偏移与 RVA 相同
+        let first_section = self
+            .sections
+            .iter()
+            .map(|s| s.virtual_address)
+            .min()
+            .unwrap_or(u32::MAX);
+        if rva < first_section {
+            return ((rva as usize) < self.bytes.len()).then_some(rva as usize);
+        }
+        self.sections.iter().find_map(|s| {
+            let offset = rva.checked_sub(s.virtual_address)?;
+            (offset < s.raw_size).then(|| (s.raw_offset + offset) as usize)
+        })
+    }
+
+    /// 读取 RVA 处的字节
+    pub fn slice(&self, rva: u32, len: usize) -> Result<&'a [u8], PeError> {
+        let offset = self.rva_to_offset(rva).ok_or(PeError::OutOfBounds(rva))?;
+        self.bytes
+            .get(offset..offset + len)
+            .ok_or(PeError::OutOfBounds(rva))
+    }
+
+    pub fn read_u32(&self, rva: u32) -> Result<u32, PeError> {
+        let bytes = self.slice(rva, 4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_u16(&self, rva: u32) -> Result<u16, PeError> {
+        let bytes = self.slice(rva, 2)?;
+        Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// 读取 RVA 处以 0 结尾的字符串
+    pub fn read_c_str(&self, rva: u32) -> Result<&'a str, PeError> {
+        let offset = self.rva_to_offset(rva).ok_or(PeError::OutOfBounds(rva))?;
+        let bytes = &self.bytes[offset..];
+        let len = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(PeError::OutOfBounds(rva))?;
+        std::str::from_utf8(&bytes[..len]).map_err(|_| PeError::InvalidString(rva))
+    }
+
+    /// 所有按名称导出的项，按名称排序
+    pub fn exports(&self) -> Result<Vec<Export>, PeError> {
+        let Some(directory) = ExportDirectory::new(self)? else {
+            return Ok(vec![]);
+        };
+        (0..directory.name_count)
+            .map(|index| directory.export(self, index))
+            .collect()
+    }
+
+    /// 按名称查找导出项，区分大小写
+    pub fn export(&self, name: &str) -> Result<Option<Export>, PeError> {
+        let Some(directory) = ExportDirectory::new(self)? else {
+            return Ok(None);
+        };
+        // 名称表按字节序排序，可二分查找
+        let (mut low, mut high) = (0, directory.name_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let name_rva = self.read_u32(directory.names + mid * 4)?;
+            match self.read_c_str(name_rva)?.as_bytes().cmp(name.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return directory.export(self, mid).map(Some),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// IMAGE_EXPORT_DIRECTORY 中用到的字段
+struct ExportDirectory {
+    range: std::ops::Range<u32>,
+    function_count: u32,
+    name_count: u32,
+    functions: u32,
+    names: u32,
+    ordinals: u32,
+}
+
+impl ExportDirectory {
+    fn new(image: &PeImage) -> Result<Option<Self>, PeError> {
+        let Some((rva, size)) = image.data_directory(DIRECTORY_EXPORT) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            range: rva..rva + size,
+            function_count: image.read_u32(rva + 20)?,
+            name_count: image.read_u32(rva + 24)?,
+            functions: image.read_u32(rva + 28)?,
+            names: image.read_u32(rva + 32)?,
+            ordinals: image.read_u32(rva + 36)?,
+        }))
+    }
+
+    /// 名称表中第 `index` 项对应的导出
+    fn export(&self, image: &PeImage, index: u32) -> Result<Export, PeError> {
+        let name = image.read_c_str(image.read_u32(self.names + index * 4)?)?;
+        let ordinal = image.read_u16(self.ordinals + index * 2)? as u32;
+        if ordinal >= self.function_count {
+            return Err(PeError::OutOfBounds(self.ordinals + index * 2));
+        }
+        let rva = image.read_u32(self.functions + ordinal * 4)?;
+        // 地址位于导出目录内时为转发字符串
+        let forwarder = if self.range.contains(&rva) {
+            Some(image.read_c_str(rva)?.to_string())
+        } else {
+            None
+        };
+        Ok(Export {
+            name: name.to_string(),
+            rva,
+            forwarder,
+        })
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// 测试用的最小 PE32+ 映像构造器
+#[cfg(test)]
+pub(crate) mod test_image {
+    /// 节在文件中的对齐，与内存中的对齐（0x1000）不同，以区分两种布局
+    const FILE_ALIGNMENT: u32 = 0x200;
+    const SECTION_ALIGNMENT: u32 = 0x1000;
+
+    pub struct ImageBuilder {
+        sections: Vec<(&'static str, Vec<u8>)>,
+        directories: Vec<(usize, u32, u32)>,
+    }
+
+    impl ImageBuilder {
+        pub fn new() -> Self {
+            Self {
+                sections: vec![],
+                directories: vec![],
+            }
+        }
+
+        /// 添加节，返回其 RVA
+        pub fn section(&mut self, name: &'static str, data: Vec<u8>) -> u32 {
+            let rva = SECTION_ALIGNMENT * (self.sections.len() as u32 + 1);
+            self.sections.push((name, data));
+            rva
+        }
+
+        pub fn directory(&mut self, index: usize, rva: u32, size: u32) {
+            self.directories.push((index, rva, size));
+        }
+
+        /// 返回 `(内存布局, 文件布局)`
+        pub fn build(&self) -> (Vec<u8>, Vec<u8>) {
+            let mut header = vec![0u8; FILE_ALIGNMENT as usize];
+            header[0..2].copy_from_slice(b"MZ");
+            header[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+            header[0x40..0x44].copy_from_slice(b"PE\0\0");
+            let file_header = 0x44;
+            header[file_header..file_header + 2].copy_from_slice(&0x8664u16.to_le_bytes());
+            header[file_header + 2..file_header + 4]
+                .copy_from_slice(&(self.sections.len() as u16).to_le_bytes());
+            let optional_header_size = 112 + 16 * 8;
+            header[file_header + 16..file_header + 18]
+                .copy_from_slice(&(optional_header_size as u16).to_le_bytes());
+            let optional_header = file_header + 20;
+            header[optional_header..optional_header + 2].copy_from_slice(&0x20Bu16.to_le_bytes());
+            let size_of_image = SECTION_ALIGNMENT * (self.sections.len() as u32 + 1);
+            header[optional_header + 56..optional_header + 60]
+                .copy_from_slice(&size_of_image.to_le_bytes());
+            header[optional_header + 108..optional_header + 112]
+                .copy_from_slice(&16u32.to_le_bytes());
+            for &(index, rva, size) in &self.directories {
+                let offset = optional_header + 112 + index * 8;
+                header[offset..offset + 4].copy_from_slice(&rva.to_le_bytes());
+                header[offset + 4..offset + 8].copy_from_slice(&size.to_le_bytes());
+            }
+
+            let mut mapped = header.clone();
+            mapped.resize(size_of_image as usize, 0);
+            let mut file = header;
+            let section_table = optional_header + optional_header_size;
+            for (i, (name, data)) in self.sections.iter().enumerate() {
+                let rva = SECTION_ALIGNMENT * (i as u32 + 1);
+                let raw_offset = file.len() as u32;
+                let raw_size = (data.len() as u32).div_ceil(FILE_ALIGNMENT) * FILE_ALIGNMENT;
+
+                let entry = section_table + i * 40;
+                for (target, value) in [
+                    (8, data.len() as u32),
+                    (12, rva),
+                    (16, raw_size),
+                    (20, raw_offset),
+                ] {
+                    mapped[entry + target..entry + target + 4]
+                        .copy_from_slice(&value.to_le_bytes());
+                }
+                mapped[entry..entry + name.len()].copy_from_slice(name.as_bytes());
+
+                mapped[rva as usize..rva as usize + data.len()].copy_from_slice(data);
+                file.extend_from_slice(data);
+                file.resize((raw_offset + raw_size) as usize, 0);
+            }
+            let header_len = FILE_ALIGNMENT as usize;
+            file[..header_len].copy_from_slice(&mapped[..header_len]);
+            (mapped, file)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_image::ImageBuilder;
+    use super::*;
+
+    #[test]
+    fn parse_both_layouts() {
+        let mut builder = ImageBuilder::new();
+        let text = builder.section(".text", vec![0xC3; 0x10]);
+        let data = builder.section(".data", vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        builder.directory(DIRECTORY_EXCEPTION, data, 8);
+        let (mapped, file) = builder.build();
+
+        for image in [
+            PeImage::mapped(&mapped).unwrap(),
+            PeImage::file(&file).unwrap(),
+        ] {
+            assert_eq!(image.sections().len(), 2);
+            assert_eq!(image.sections()[0].name(), ".text");
+            assert_eq!(image.sections()[1].virtual_address, data);
+            assert_eq!(image.data_directory(DIRECTORY_EXCEPTION), Some((data, 8)));
+            assert_eq!(image.data_directory(DIRECTORY_EXPORT), None);
+            assert_eq!(image.slice(text, 2).unwrap(), &[0xC3, 0xC3]);
+            assert_eq!(image.read_u32(data + 4).unwrap(), 0x0807_0605);
+        }
+        // 文件布局中节的偏移与 RVA 不同
+        let file_image = PeImage::file(&file).unwrap();
+        assert_eq!(file_image.rva_to_offset(data), Some(0x400));
+        assert!(file_image.slice(0x8000, 1).is_err());
+    }
+
+    #[test]
+    fn lookup_exports() {
+        let mut builder = ImageBuilder::new();
+        builder.section(".text", vec![0xC3; 0x20]);
+        let edata = 0x2000u32;
+        let mut data = vec![0u8; 0x80];
+        let mut put =
+            |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        // 目录：3 个函数，2 个名称
+        put(20, &3u32.to_le_bytes());
+        put(24, &2u32.to_le_bytes());
+        put(28, &(edata + 0x28).to_le_bytes());
+        put(32, &(edata + 0x40).to_le_bytes());
+        put(36, &(edata + 0x48).to_le_bytes());
+        // 函数地址表，第 3 项为转发
+        put(0x28, &0x1000u32.to_le_bytes());
+        put(0x2C, &0x1010u32.to_le_bytes());
+        put(0x30, &(edata + 0x70).to_le_bytes());
+        // 名称表与序号表
+        put(0x40, &(edata + 0x60).to_le_bytes());
+        put(0x44, &(edata + 0x68).to_le_bytes());
+        put(0x48, &0u16.to_le_bytes());
+        put(0x4A, &2u16.to_le_bytes());
+        put(0x60, b"Alpha\0");
+        put(0x68, b"Beta\0");
+        put(0x70, b"NTDLL.Foo\0");
+        assert_eq!(builder.section(".edata", data), edata);
+        builder.directory(DIRECTORY_EXPORT, edata, 0x80);
+        let (mapped, file) = builder.build();
+
+        for image in [
+            PeImage::mapped(&mapped).unwrap(),
+            PeImage::file(&file).unwrap(),
+        ] {
+            let alpha = image.export("Alpha").unwrap().unwrap();
+            assert_eq!((alpha.rva, alpha.forwarder), (0x1000, None));
+            let beta = image.export("Beta").unwrap().unwrap();
+            assert_eq!(beta.forwarder.as_deref(), Some("NTDLL.Foo"));
+            assert!(image.export("alpha").unwrap().is_none());
+            assert!(image.export("Gamma").unwrap().is_none());
+
+            let names = image
+                .exports()
+                .unwrap()
+                .into_iter()
+                .map(|e| e.name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["Alpha", "Beta"]);
+        }
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(matches!(
+            PeImage::mapped(b"MZ"),
+            Err(PeError::InvalidHeader)
+        ));
+        let (mut mapped, _) = ImageBuilder::new().build();
+        mapped[0x58] = 0x0B;
+        mapped[0x59] = 0x01;
+        assert!(matches!(
+            PeImage::mapped(&mapped),
+            Err(PeError::NotPe32Plus)
+        ));
+    }
+}
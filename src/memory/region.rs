@@ -0,0 +1,301 @@
+//! 进程地址空间中的内存区域与已加载模块
+
+use windows::Win32::System::Memory::{
+    MEM_COMMIT, MEM_IMAGE, MEM_MAPPED, MEM_PRIVATE, MEM_RESERVE, PAGE_EXECUTE, PAGE_EXECUTE_READ,
+    PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_NOACCESS, PAGE_NOCACHE,
+    PAGE_PROTECTION_FLAGS, PAGE_READONLY, PAGE_READWRITE, PAGE_TYPE, PAGE_WRITECOMBINE,
+    PAGE_WRITECOPY, VIRTUAL_ALLOCATION_TYPE,
+};
+
+/// PAGE_GUARD、PAGE_NOCACHE、PAGE_WRITECOMBINE 等修饰位
+const PAGE_MODIFIERS: u32 = PAGE_GUARD.0 | PAGE_NOCACHE.0 | PAGE_WRITECOMBINE.0;
+
+/// VirtualQuery 返回的一段内存区域
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: usize,
+    pub size: usize,
+    pub allocation_base: usize,
+    /// 原始页保护标志
+    pub protect: u32,
+    pub state: u32,
+    pub kind: u32,
+}
+
+impl MemoryRegion {
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.base && address < self.end()
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.state == MEM_COMMIT.0
+    }
+
+    /// 去除修饰位后的保护标志，未提交或带 PAGE_GUARD 的页视为不可访问
+    fn access(&self) -> PAGE_PROTECTION_FLAGS {
+        if !self.is_committed() || self.protect & PAGE_GUARD.0 != 0 {
+            return PAGE_NOACCESS;
+        }
+        PAGE_PROTECTION_FLAGS(self.protect & !PAGE_MODIFIERS)
+    }
+
+    pub fn is_readable(&self) -> bool {
+        matches!(
+            self.access(),
+            PAGE_READONLY
+                | PAGE_READWRITE
+                | PAGE_WRITECOPY
+                | PAGE_EXECUTE_READ
+                | PAGE_EXECUTE_READWRITE
+                | PAGE_EXECUTE_WRITECOPY
+        )
+    }
+
+    pub fn is_writable(&self) -> bool {
+        matches!(
+            self.access(),
+            PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY
+        )
+    }
+
+    pub fn is_executable(&self) -> bool {
+        matches!(
+            self.access(),
+            PAGE_EXECUTE | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY
+        )
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        match VIRTUAL_ALLOCATION_TYPE(self.state) {
+            MEM_COMMIT => "commit",
+            MEM_RESERVE => "reserve",
+            _ => "free",
+        }
+    }
+
+    /// 区域类型，空闲区域没有类型
+    pub fn kind_name(&self) -> Option<&'static str> {
+        match PAGE_TYPE(self.kind) {
+            MEM_IMAGE => Some("image"),
+            MEM_MAPPED => Some("mapped"),
+            MEM_PRIVATE => Some("private"),
+            _ => None,
+        }
+    }
+}
+
+/// 已加载的模块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub path: String,
+    pub base: usize,
+    pub size: usize,
+}
+
+impl ModuleInfo {
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.base && address < self.base + self.size
+    }
+}
+
+/// 按名称查找模块，忽略大小写
+pub fn find_module<'a>(modules: &'a [ModuleInfo], name: &str) -> Option<&'a ModuleInfo> {
+    modules.iter().find(|m| m.name.eq_ignore_ascii_case(name))
+}
+
+/// 查找包含地址的模块
+pub fn module_containing(modules: &[ModuleInfo], address: usize) -> Option<&ModuleInfo> {
+    modules.iter().find(|m| m.contains(address))
+}
+
+/// 将 `"rwx"` 形式的权限字符串转换为页保护标志
+///
+/// 字符顺序不限，空字符串或 `"-"` 表示不可访问。Windows 不支持只写页，`"w"` 与 `"wx"` 无效。
+pub fn protection_from_flags(flags: &str) -> Option<u32> {
+    let (mut read, mut write, mut execute) = (false, false, false);
+    for c in flags.chars() {
+        let flag = match c.to_ascii_lowercase() {
+            'r' => &mut read,
+            'w' => &mut write,
+            'x' => &mut execute,
+            '-' => continue,
+            _ => return None,
+        };
+        if *flag {
+            return None;
+        }
+        *flag = true;
+    }
+    let protect = match (read, write, execute) {
+        (false, false, false) => PAGE_NOACCESS,
+        (true, false, false) => PAGE_READONLY,
+        (true, true, false) => PAGE_READWRITE,
+        (false, false, true) => PAGE_EXECUTE,
+        (true, false, true) => PAGE_EXECUTE_READ,
+        (true, true, true) => PAGE_EXECUTE_READWRITE,
+        _ => return None,
+    };
+    Some(protect.0)
+}
+
+/// 将页保护标志转换为 `"rwx"` 形式，忽略修饰位
+pub fn protection_to_flags(protect: u32) -> &'static str {
+    match PAGE_PROTECTION_FLAGS(protect & !PAGE_MODIFIERS) {
+        PAGE_READONLY => "r",
+        PAGE_READWRITE | PAGE_WRITECOPY => "rw",
+        PAGE_EXECUTE => "x",
+        PAGE_EXECUTE_READ => "rx",
+        PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY => "rwx",
+        _ => "",
+    }
+}
+
+/// 区域过滤条件，`None` 表示不限制
+#[derive(Debug, Default, Clone)]
+pub struct RegionFilter {
+    pub committed: Option<bool>,
+    pub readable: Option<bool>,
+    pub writable: Option<bool>,
+    pub executable: Option<bool>,
+    /// 只保留属于该模块的区域
+    pub module: Option<String>,
+}
+
+impl RegionFilter {
+    /// `module` 为区域所属的模块
+    pub fn matches(&self, region: &MemoryRegion, module: Option<&ModuleInfo>) -> bool {
+        let check = |expected: Option<bool>, actual: bool| expected.is_none_or(|e| e == actual);
+        check(self.committed, region.is_committed())
+            && check(self.readable, region.is_readable())
+            && check(self.writable, region.is_writable())
+            && check(self.executable, region.is_executable())
+            && self.module.as_deref().is_none_or(|name| {
+                module.is_some_and(|module| module.name.eq_ignore_ascii_case(name))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::System::Memory::MEM_FREE;
+
+    use super::*;
+
+    fn region(
+        base: usize,
+        protect: PAGE_PROTECTION_FLAGS,
+        state: VIRTUAL_ALLOCATION_TYPE,
+        kind: PAGE_TYPE,
+    ) -> MemoryRegion {
+        MemoryRegion {
+            base,
+            size: 0x1000,
+            allocation_base: base,
+            protect: protect.0,
+            state: state.0,
+            kind: kind.0,
+        }
+    }
+
+    fn modules() -> Vec<ModuleInfo> {
+        vec![
+            ModuleInfo {
+                name: "Game.exe".to_string(),
+                path: "C:\\Game\\Game.exe".to_string(),
+                base: 0x1_4000_0000,
+                size: 0x10_0000,
+            },
+            ModuleInfo {
+                name: "dinput8.dll".to_string(),
+                path: "C:\\Game\\dinput8.dll".to_string(),
+                base: 0x7FF0_0000_0000,
+                size: 0x2_0000,
+            },
+        ]
+    }
+
+    #[test]
+    fn decode_protection() {
+        let rx = region(0, PAGE_EXECUTE_READ, MEM_COMMIT, MEM_IMAGE);
+        assert!(rx.is_readable() && rx.is_executable() && !rx.is_writable());
+
+        let rw_nocache = region(0, PAGE_READWRITE | PAGE_NOCACHE, MEM_COMMIT, MEM_PRIVATE);
+        assert!(rw_nocache.is_readable() && rw_nocache.is_writable());
+
+        let guard = region(0, PAGE_READWRITE | PAGE_GUARD, MEM_COMMIT, MEM_PRIVATE);
+        assert!(!guard.is_readable() && !guard.is_writable());
+
+        // 保留但未提交的页没有访问权限
+        let reserved = region(0, PAGE_READWRITE, MEM_RESERVE, MEM_PRIVATE);
+        assert!(!reserved.is_readable());
+        assert_eq!(reserved.state_name(), "reserve");
+
+        let free = region(0, PAGE_NOACCESS, MEM_FREE, PAGE_TYPE(0));
+        assert_eq!(free.state_name(), "free");
+        assert_eq!(free.kind_name(), None);
+    }
+
+    #[test]
+    fn find_modules() {
+        let modules = modules();
+        assert_eq!(
+            find_module(&modules, "DINPUT8.DLL").map(|m| m.base),
+            Some(0x7FF0_0000_0000)
+        );
+        assert!(find_module(&modules, "dinput8").is_none());
+        assert_eq!(
+            module_containing(&modules, 0x1_4000_1234).map(|m| m.name.as_str()),
+            Some("Game.exe")
+        );
+        assert!(module_containing(&modules, 0x1_4010_0000).is_none());
+    }
+
+    #[test]
+    fn parse_protection_flags() {
+        assert_eq!(protection_from_flags("rwx"), Some(PAGE_EXECUTE_READWRITE.0));
+        assert_eq!(protection_from_flags("XR"), Some(PAGE_EXECUTE_READ.0));
+        assert_eq!(protection_from_flags("r-x"), Some(PAGE_EXECUTE_READ.0));
+        assert_eq!(protection_from_flags("rw"), Some(PAGE_READWRITE.0));
+        assert_eq!(protection_from_flags(""), Some(PAGE_NOACCESS.0));
+        assert_eq!(protection_from_flags("w"), None);
+        assert_eq!(protection_from_flags("wx"), None);
+        assert_eq!(protection_from_flags("rr"), None);
+        assert_eq!(protection_from_flags("rwz"), None);
+
+        assert_eq!(protection_to_flags(PAGE_EXECUTE_WRITECOPY.0), "rwx");
+        assert_eq!(protection_to_flags((PAGE_READONLY | PAGE_GUARD).0), "r");
+        assert_eq!(protection_to_flags(PAGE_NOACCESS.0), "");
+    }
+
+    #[test]
+    fn filter_regions() {
+        let modules = modules();
+        let game = find_module(&modules, "Game.exe");
+        let data = region(0x1_4000_2000, PAGE_READWRITE, MEM_COMMIT, MEM_IMAGE);
+        let code = region(0x1_4000_1000, PAGE_EXECUTE_READ, MEM_COMMIT, MEM_IMAGE);
+        let heap = region(0x2000_0000, PAGE_READWRITE, MEM_COMMIT, MEM_PRIVATE);
+
+        assert!(RegionFilter::default().matches(&heap, None));
+
+        let filter = RegionFilter {
+            committed: Some(true),
+            writable: Some(true),
+            ..Default::default()
+        };
+        assert!(filter.matches(&data, game));
+        assert!(!filter.matches(&code, game));
+        assert!(filter.matches(&heap, None));
+
+        let filter = RegionFilter {
+            module: Some("game.exe".to_string()),
+            ..filter
+        };
+        assert!(filter.matches(&data, game));
+        assert!(!filter.matches(&heap, None));
+    }
+}
@@ -0,0 +1,235 @@
+//! 持久化的特征码扫描缓存
+//!
+//! 缓存以主模块的 PE 时间戳、校验和与映像大小作为标识，
+//! 游戏程序更新后标识改变，旧缓存自动失效。
+//! 结果以相对模块基址的偏移保存，不受 ASLR 影响。
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+
+/// PE 映像标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageIdentity {
+    pub timestamp: u32,
+    pub checksum: u32,
+    pub size_of_image: u32,
+}
+
+impl ImageIdentity {
+    /// 从映像头部解析标识，`header` 需至少包含 NT 头
+    pub fn from_header(header: &[u8]) -> Option<Self> {
+        let read_u32 = |offset: usize| -> Option<u32> {
+            let bytes = header.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?))
+        };
+
+        if header.get(0..2)? != b"MZ" {
+            return None;
+        }
+        let nt = read_u32(0x3C)? as usize;
+        if header.get(nt..nt + 4)? != b"PE\0\0" {
+            return None;
+        }
+        // IMAGE_FILE_HEADER 位于签名之后，可选头紧随其后
+        let file_header = nt + 4;
+        let optional_header = file_header + 20;
+        Some(Self {
+            timestamp: read_u32(file_header + 4)?,
+            size_of_image: read_u32(optional_header + 56)?,
+            checksum: read_u32(optional_header + 64)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    image: ImageIdentity,
+    /// 特征码 -> 模块内偏移
+    entries: HashMap<String, usize>,
+}
+
+pub struct ScanCache {
+    path: PathBuf,
+    image: ImageIdentity,
+    entries: HashMap<String, usize>,
+}
+
+impl ScanCache {
+    /// 加载缓存文件，文件不存在、损坏或映像标识不一致时返回空缓存
+    pub fn load(path: impl AsRef<Path>, image: ImageIdentity) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CacheFile>(&content).ok())
+            .filter(|file| {
+                let valid = file.image == image;
+                if !valid {
+                    log::info!("Scan cache invalidated: game image changed");
+                }
+                valid
+            })
+            .map(|file| file.entries)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            image,
+            entries,
+        }
+    }
+
+    pub fn image(&self) -> ImageIdentity {
+        self.image
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 获取特征码的模块内偏移
+    pub fn get(&self, pattern: &str) -> Option<usize> {
+        self.entries.get(pattern).copied()
+    }
+
+    pub fn insert(&mut self, pattern: &str, offset: usize) {
+        self.entries.insert(pattern.to_string(), offset);
+    }
+
+    pub fn remove(&mut self, pattern: &str) -> Option<usize> {
+        self.entries.remove(pattern)
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        self.snapshot()?.write()
+    }
+
+    /// 序列化当前内容，返回的快照可在释放缓存锁之后写入文件
+    pub fn snapshot(&self) -> serde_json::Result<CacheSnapshot> {
+        let file = CacheFile {
+            image: self.image,
+            entries: self.entries.clone(),
+        };
+        Ok(CacheSnapshot {
+            path: self.path.clone(),
+            content: serde_json::to_string(&file)?,
+        })
+    }
+
+    /// 清空缓存并删除缓存文件
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.entries.clear();
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 待写入的缓存内容
+pub struct CacheSnapshot {
+    path: PathBuf,
+    content: String,
+}
+
+impl CacheSnapshot {
+    /// 原子地写入缓存文件，写入中途崩溃不会留下损坏的缓存
+    pub fn write(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        util::write_atomic(&self.path, self.content.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_header(timestamp: u32, size_of_image: u32, checksum: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 0x200];
+        header[0..2].copy_from_slice(b"MZ");
+        header[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        header[0x80..0x84].copy_from_slice(b"PE\0\0");
+        header[0x88..0x8C].copy_from_slice(&timestamp.to_le_bytes());
+        header[0xD0..0xD4].copy_from_slice(&size_of_image.to_le_bytes());
+        header[0xD8..0xDC].copy_from_slice(&checksum.to_le_bytes());
+        header
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("eglib-scan-cache-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn parse_image_identity() {
+        let header = fake_header(0x6500_0000, 0x0100_0000, 0x1234);
+        let image = ImageIdentity::from_header(&header).unwrap();
+        assert_eq!(image.timestamp, 0x6500_0000);
+        assert_eq!(image.size_of_image, 0x0100_0000);
+        assert_eq!(image.checksum, 0x1234);
+    }
+
+    #[test]
+    fn reject_invalid_header() {
+        let mut header = fake_header(1, 2, 3);
+        header[0x80] = b'X';
+        assert!(ImageIdentity::from_header(&header).is_none());
+        assert!(ImageIdentity::from_header(&header[..0x40]).is_none());
+        assert!(ImageIdentity::from_header(b"").is_none());
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let path = temp_path("reload.json");
+        let image = ImageIdentity::from_header(&fake_header(1, 2, 3)).unwrap();
+
+        let mut cache = ScanCache::load(&path, image);
+        cache.insert("48 8B ?? 05", 0x1234);
+        cache.save().unwrap();
+
+        let cache = ScanCache::load(&path, image);
+        assert_eq!(cache.get("48 8B ?? 05"), Some(0x1234));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalidate_on_image_change() {
+        let path = temp_path("invalidate.json");
+        let old = ImageIdentity::from_header(&fake_header(1, 2, 3)).unwrap();
+        let new = ImageIdentity::from_header(&fake_header(4, 2, 3)).unwrap();
+
+        let mut cache = ScanCache::load(&path, old);
+        cache.insert("90 90", 0x10);
+        cache.save().unwrap();
+
+        let cache = ScanCache::load(&path, new);
+        assert!(cache.is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn clear_removes_file() {
+        let path = temp_path("clear.json");
+        let image = ImageIdentity::from_header(&fake_header(1, 2, 3)).unwrap();
+
+        let mut cache = ScanCache::load(&path, image);
+        cache.insert("90 90", 0x10);
+        cache.save().unwrap();
+        cache.clear().unwrap();
+
+        assert!(!path.exists());
+        assert!(ScanCache::load(&path, image).is_empty());
+        // 文件不存在时再次清空不报错
+        cache.clear().unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+//! 特征码数据库
+//!
+//! 以 JSON 描述一组具名特征码，统一解析为地址：
+//!
+//! ```json
+//! {
+//!   "signatures": [
+//!     {
+//!       "name": "player_manager",
+//!       "patterns": ["48 8B 05 ?? ?? ?? ?? 48 85 C0", "48 8B 0D ?? ?? ?? ?? E8"],
+//!       "offset": 3,
+//!       "resolve": ["rip", "deref", { "add": 16 }],
+//!       "require_unique": true
+//!     }
+//!   ]
+//! }
+//! ```
+
+use serde::Deserialize;
+
+use super::{MemoryError, MemoryUtils};
+
+#[derive(Debug, Deserialize)]
+pub struct SignatureDb {
+    pub signatures: Vec<Signature>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Signature {
+    pub name: String,
+    /// 按顺序尝试的特征码，前者未命中时使用后者
+    #[serde(alias = "pattern", deserialize_with = "one_or_many")]
+    pub patterns: Vec<String>,
+    /// 匹配地址的偏移，在解析步骤之前应用
+    #[serde(default)]
+    pub offset: isize,
+    #[serde(default)]
+    pub resolve: Vec<ResolveStep>,
+    /// 特征码匹配多处时视为失败
+    #[serde(default)]
+    pub require_unique: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolveStep {
+    /// 将当前地址视为 rel32，计算 `addr + 4 + rel32`
+    Rip,
+    /// 读取当前地址处的指针
+    Deref,
+    /// 加上常量偏移
+    Add(isize),
+}
+
+/// 解析结果，顺序与文件一致
+#[derive(Debug, Default)]
+pub struct SignatureReport {
+    pub resolved: Vec<(String, usize)>,
+    pub failed: Vec<(String, MemoryError)>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureError {
+    #[error("invalid signature database: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("duplicate signature name '{0}'")]
+    DuplicateName(String),
+    #[error("signature '{0}' has no pattern")]
+    NoPattern(String),
+    #[error("no valid instruction at 0x{0:x}")]
+    Undecodable(usize),
+    #[error("no unique signature within {0} bytes")]
+    NotUnique(usize),
+    #[error("generated pattern does not match 0x{0:x}")]
+    TargetNotMatched(usize),
+    #[error("0x{0:x} is not in the module")]
+    OutOfModule(usize),
+    #[error("{0}")]
+    Memory(#[from] MemoryError),
+}
+
+impl SignatureDb {
+    pub fn from_json(source: &str) -> Result<Self, SignatureError> {
+        let db: SignatureDb = serde_json::from_str(source)?;
+        let mut names = std::collections::HashSet::new();
+        for sig in &db.signatures {
+            if !names.insert(sig.name.as_str()) {
+                return Err(SignatureError::DuplicateName(sig.name.clone()));
+            }
+            if sig.patterns.is_empty() {
+                return Err(SignatureError::NoPattern(sig.name.clone()));
+            }
+        }
+        Ok(db)
+    }
+
+    /// 在指定内存范围内解析所有特征码
+    pub fn resolve_all(&self, base: usize, size: usize) -> SignatureReport {
+        let mut report = SignatureReport::default();
+        for sig in &self.signatures {
+            match sig.resolve(base, size) {
+                Ok(address) => report.resolved.push((sig.name.clone(), address)),
+                Err(e) => report.failed.push((sig.name.clone(), e)),
+            }
+        }
+        report
+    }
+}
+
+impl Signature {
+    /// 依次尝试各特征码，返回第一个成功解析的地址
+    ///
+    /// 全部失败时返回最后一个特征码的错误。
+    pub fn resolve(&self, base: usize, size: usize) -> Result<usize, MemoryError> {
+        let mut last_error = MemoryError::NotFound(self.name.clone());
+        for pattern in &self.patterns {
+            let matched = if self.require_unique {
+                MemoryUtils::scan_all(base, size, pattern).and_then(|matches| {
+                    if matches.len() > 1 {
+                        Err(MemoryError::MultipleMatchesFound)
+                    } else {
+                        Ok(matches[0])
+                    }
+                })
+            } else {
+                MemoryUtils::scan_first(base, size, pattern)
+            };
+            match matched {
+                Ok(address) => {
+                    let address = (address as isize + self.offset) as usize;
+                    return apply_steps(address, &self.resolve, |addr| {
+                        MemoryUtils::quick_read(addr, 8, true)
+                    });
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+/// 依次应用解析步骤，`read` 读取指定地址处的 8 字节
+pub fn apply_steps(
+    mut address: usize,
+    steps: &[ResolveStep],
+    read: impl Fn(usize) -> Result<[u8; 8], MemoryError>,
+) -> Result<usize, MemoryError> {
+    for step in steps {
+        address = match step {
+            ResolveStep::Rip => {
+                let bytes = read(address)?;
+                let rel = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (address as isize + 4 + rel as isize) as usize
+            }
+            ResolveStep::Deref => u64::from_le_bytes(read(address)?) as usize,
+            ResolveStep::Add(n) => (address as isize + n) as usize,
+        };
+    }
+    Ok(address)
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(pattern) => vec![pattern],
+        OneOrMany::Many(patterns) => patterns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_database() {
+        let db = SignatureDb::from_json(
+            r#"{
+                "signatures": [
+                    {
+                        "name": "a",
+                        "patterns": ["48 8B 05", "48 8B 0D"],
+                        "offset": 3,
+                        "resolve": ["rip", "deref", { "add": -8 }],
+                        "require_unique": true
+                    },
+                    { "name": "b", "pattern": "E8 ?? ?? ?? ??" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let a = &db.signatures[0];
+        assert_eq!(a.patterns.len(), 2);
+        assert_eq!(a.offset, 3);
+        assert_eq!(
+            a.resolve,
+            vec![ResolveStep::Rip, ResolveStep::Deref, ResolveStep::Add(-8)]
+        );
+        assert!(a.require_unique);
+
+        let b = &db.signatures[1];
+        assert_eq!(b.patterns, vec!["E8 ?? ?? ?? ??"]);
+        assert_eq!(b.offset, 0);
+        assert!(b.resolve.is_empty());
+        assert!(!b.require_unique);
+    }
+
+    #[test]
+    fn reject_duplicate_names() {
+        let result = SignatureDb::from_json(
+            r#"{ "signatures": [ { "name": "a", "pattern": "90" }, { "name": "a", "pattern": "C3" } ] }"#,
+        );
+        assert!(matches!(result, Err(SignatureError::DuplicateName(name)) if name == "a"));
+    }
+
+    #[test]
+    fn reject_empty_patterns() {
+        let result =
+            SignatureDb::from_json(r#"{ "signatures": [ { "name": "a", "patterns": [] } ] }"#);
+        assert!(matches!(result, Err(SignatureError::NoPattern(_))));
+    }
+
+    #[test]
+    fn apply_rip_deref_add() {
+        // 0x1000: rel32 = 0x10 -> 0x1014，0x1014 处存放指针 0x2000
+        let read = |addr: usize| -> Result<[u8; 8], MemoryError> {
+            match addr {
+                0x1000 => Ok((0x10u64).to_le_bytes()),
+                0x1014 => Ok((0x2000u64).to_le_bytes()),
+                _ => Err(MemoryError::PagePermNoRead(addr)),
+            }
+        };
+        let steps = [ResolveStep::Rip, ResolveStep::Deref, ResolveStep::Add(8)];
+        assert_eq!(apply_steps(0x1000, &steps, read).unwrap(), 0x2008);
+
+        let steps = [ResolveStep::Add(4), ResolveStep::Deref];
+        assert!(apply_steps(0x1000, &steps, read).is_err());
+    }
+
+    #[test]
+    fn apply_negative_rip() {
+        let read = |_: usize| Ok((-0x20i32 as u32 as u64).to_le_bytes());
+        assert_eq!(
+            apply_steps(0x1000, &[ResolveStep::Rip], read).unwrap(),
+            0x0fe4
+        );
+    }
+}
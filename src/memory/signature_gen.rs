@@ -0,0 +1,221 @@
+//! 为指定地址生成唯一特征码
+
+use iced_x86::{Code, Decoder, DecoderOptions};
+
+use super::{MemoryError, signature::SignatureError};
+
+/// 解码从 `ip` 开始的指令，返回每条指令的字节，`None` 为通配符
+///
+/// `wildcard_operands` 为 true 时，RIP 相对位移与 4 字节及以上的立即数（含 rel32 跳转目标）
+/// 被替换为通配符。遇到无法解码的指令时停止。
+pub fn mask_instructions(code: &[u8], ip: u64, wildcard_operands: bool) -> Vec<Vec<Option<u8>>> {
+    let mut decoder = Decoder::with_ip(64, code, ip, DecoderOptions::NONE);
+    let mut instructions = vec![];
+    let mut position = 0;
+
+    while decoder.can_decode() {
+        let instr = decoder.decode();
+        if instr.code() == Code::INVALID {
+            break;
+        }
+        let len = instr.len();
+        let mut masked = code[position..position + len]
+            .iter()
+            .map(|&b| Some(b))
+            .collect::<Vec<_>>();
+
+        if wildcard_operands {
+            let offsets = decoder.get_constant_offsets(&instr);
+            let mut wildcard = |offset: usize, size: usize| {
+                masked[offset..offset + size].fill(None);
+            };
+            if offsets.has_displacement() && instr.is_ip_rel_memory_operand() {
+                wildcard(offsets.displacement_offset(), offsets.displacement_size());
+            }
+            if offsets.has_immediate() && offsets.immediate_size() >= 4 {
+                wildcard(offsets.immediate_offset(), offsets.immediate_size());
+            }
+            if offsets.has_immediate2() && offsets.immediate_size2() >= 4 {
+                wildcard(offsets.immediate_offset2(), offsets.immediate_size2());
+            }
+        }
+
+        instructions.push(masked);
+        position += len;
+    }
+    instructions
+}
+
+/// 将掩码字节格式化为特征码字符串
+pub fn format_pattern(bytes: &[Option<u8>]) -> String {
+    bytes
+        .iter()
+        .map(|b| match b {
+            Some(b) => format!("{:02X}", b),
+            None => "??".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 从 `address` 处逐条指令增长特征码，直到只匹配 `address` 自身
+///
+/// `code` 为 `address` 起的内存内容；`scan_all` 扫描整个模块，仅调用一次；
+/// 之后增长的特征码通过 `matches_at` 在已有候选地址上校验。
+pub fn generate(
+    code: &[u8],
+    address: usize,
+    max_len: usize,
+    wildcard_operands: bool,
+    scan_all: impl FnOnce(&str) -> Result<Vec<usize>, MemoryError>,
+    matches_at: impl Fn(usize, &str) -> bool,
+) -> Result<String, SignatureError> {
+    let instructions = mask_instructions(code, address as u64, wildcard_operands);
+    let mut instructions = instructions.into_iter();
+    let mut bytes = instructions
+        .next()
+        .ok_or(SignatureError::Undecodable(address))?;
+    if bytes.len() > max_len {
+        return Err(SignatureError::NotUnique(max_len));
+    }
+
+    let mut pattern = format_pattern(&bytes);
+    let mut candidates = scan_all(&pattern)?;
+    if !candidates.contains(&address) {
+        return Err(SignatureError::TargetNotMatched(address));
+    }
+
+    while candidates.len() > 1 {
+        let next = instructions
+            .next()
+            .ok_or(SignatureError::NotUnique(bytes.len()))?;
+        if bytes.len() + next.len() > max_len {
+            return Err(SignatureError::NotUnique(max_len));
+        }
+        bytes.extend(next);
+        pattern = format_pattern(&bytes);
+        candidates.retain(|&c| c == address || matches_at(c, &pattern));
+    }
+
+    // 末尾的通配符不影响匹配
+    while bytes.last() == Some(&None) {
+        bytes.pop();
+    }
+    Ok(format_pattern(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // call rel32; je rel8; mov rax,[rip+disp32]; mov rax,imm64; add ecx,imm32; ret
+    const CODE: [u8; 31] = [
+        0xE8, 0x01, 0x02, 0x03, 0x04, //
+        0x74, 0x05, //
+        0x48, 0x8B, 0x05, 0x01, 0x02, 0x03, 0x04, //
+        0x48, 0xB8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, //
+        0x81, 0xC1, 0x00, 0x10, 0x00, 0x00, //
+        0xC3,
+    ];
+
+    /// 在字节缓冲区中模拟扫描与校验
+    fn scanner(memory: &[u8]) -> (impl Fn(&str) -> Vec<usize>, impl Fn(usize, &str) -> bool) {
+        let memory = memory.to_vec();
+        let memory2 = memory.clone();
+        let matches = |bytes: &[u8], pattern: &str| {
+            let pattern = pattern.split_ascii_whitespace().collect::<Vec<_>>();
+            bytes.len() >= pattern.len()
+                && pattern
+                    .iter()
+                    .zip(bytes)
+                    .all(|(p, b)| *p == "??" || u8::from_str_radix(p, 16).unwrap() == *b)
+        };
+        let scan_all = move |pattern: &str| {
+            (0..memory.len())
+                .filter(|&i| matches(&memory[i..], pattern))
+                .collect::<Vec<_>>()
+        };
+        let matches_at = move |address: usize, pattern: &str| matches(&memory2[address..], pattern);
+        (scan_all, matches_at)
+    }
+
+    #[test]
+    fn mask_operands() {
+        let instructions = mask_instructions(&CODE, 0x1000, true);
+        let patterns = instructions
+            .iter()
+            .map(|i| format_pattern(i))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            patterns,
+            vec![
+                "E8 ?? ?? ?? ??",
+                "74 05",
+                "48 8B 05 ?? ?? ?? ??",
+                "48 B8 ?? ?? ?? ?? ?? ?? ?? ??",
+                "81 C1 ?? ?? ?? ??",
+                "C3",
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_operands() {
+        let instructions = mask_instructions(&CODE, 0x1000, false);
+        assert_eq!(format_pattern(&instructions[0]), "E8 01 02 03 04");
+        assert_eq!(
+            instructions.iter().map(|i| i.len()).sum::<usize>(),
+            CODE.len()
+        );
+    }
+
+    #[test]
+    fn stop_at_truncated_instruction() {
+        let instructions = mask_instructions(&CODE[..9], 0x1000, true);
+        assert_eq!(instructions.len(), 2);
+    }
+
+    #[test]
+    fn grow_until_unique() {
+        // 前两条指令在内存中出现两次，第三条指令不同
+        let mut memory = vec![0xCC; 16];
+        memory.extend_from_slice(&[0xE8, 9, 9, 9, 9, 0x74, 0x05, 0x90]);
+        let target = memory.len();
+        memory.extend_from_slice(&CODE);
+        let (scan_all, matches_at) = scanner(&memory);
+
+        let pattern = generate(
+            &memory[target..],
+            target,
+            64,
+            true,
+            |p| Ok(scan_all(p)),
+            matches_at,
+        )
+        .unwrap();
+        assert_eq!(pattern, "E8 ?? ?? ?? ?? 74 05 48 8B 05");
+    }
+
+    #[test]
+    fn unique_first_instruction() {
+        let (scan_all, matches_at) = scanner(&CODE);
+        let pattern = generate(&CODE, 0, 64, true, |p| Ok(scan_all(p)), matches_at).unwrap();
+        assert_eq!(pattern, "E8");
+    }
+
+    #[test]
+    fn exceed_max_len() {
+        let mut memory = CODE.to_vec();
+        memory.extend_from_slice(&CODE);
+        let (scan_all, matches_at) = scanner(&memory);
+
+        let result = generate(&CODE, 0, 12, true, |p| Ok(scan_all(p)), matches_at);
+        assert!(matches!(result, Err(SignatureError::NotUnique(12))));
+    }
+
+    #[test]
+    fn undecodable() {
+        let result = generate(&[0x0F, 0xFF], 0, 64, true, |_| Ok(vec![]), |_, _| false);
+        assert!(matches!(result, Err(SignatureError::Undecodable(0))));
+    }
+}
@@ -0,0 +1,286 @@
+//! 内存快照与差异比较
+//!
+//! 二进制格式（小端）：
+//!
+//! | 偏移 | 大小 | 内容                 |
+//! |------|------|----------------------|
+//! | 0    | 4    | 魔数 `EGSN`          |
+//! | 4    | 2    | 格式版本             |
+//! | 6    | 2    | 保留                 |
+//! | 8    | 8    | 起始地址             |
+//! | 16   | 8    | 数据大小             |
+//! | 24   | 8    | 时间戳（Unix 毫秒）  |
+//! | 32   | 8    | 主模块基址           |
+//! | 40   | -    | 数据                 |
+
+use std::io::Cursor;
+
+use super::{MemoryError, pattern_scan};
+
+const MAGIC: &[u8; 4] = b"EGSN";
+const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 40;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("not a snapshot file")]
+    InvalidMagic,
+    #[error("unsupported snapshot version {0}")]
+    UnsupportedVersion(u16),
+    #[error("snapshot data is truncated: expected {0} bytes, got {1}")]
+    Truncated(usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub address: usize,
+    pub timestamp: i64,
+    pub module_base: usize,
+    pub data: Vec<u8>,
+}
+
+/// 两个快照之间连续变化的字节
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRange {
+    pub address: usize,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// 按宽度解释的值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypedValue {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+impl Snapshot {
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(self.address as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&(self.module_base as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(SnapshotError::Truncated(HEADER_SIZE, bytes.len()));
+        }
+        if &bytes[0..4] != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let read_u64 =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let size = read_u64(16) as usize;
+        let data = &bytes[HEADER_SIZE..];
+        if data.len() < size {
+            return Err(SnapshotError::Truncated(HEADER_SIZE + size, bytes.len()));
+        }
+
+        Ok(Self {
+            address: read_u64(8) as usize,
+            timestamp: read_u64(24) as i64,
+            module_base: read_u64(32) as usize,
+            data: data[..size].to_vec(),
+        })
+    }
+
+    /// 比较两个快照地址重叠部分，返回变化的字节范围（按地址升序）
+    pub fn diff(&self, other: &Snapshot) -> Vec<ChangedRange> {
+        let start = self.address.max(other.address);
+        let end = (self.address + self.size()).min(other.address + other.size());
+        let mut ranges: Vec<ChangedRange> = vec![];
+
+        for address in start..end {
+            let before = self.data[address - self.address];
+            let after = other.data[address - other.address];
+            if before == after {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.address + last.before.len() == address => {
+                    last.before.push(before);
+                    last.after.push(after);
+                }
+                _ => ranges.push(ChangedRange {
+                    address,
+                    before: vec![before],
+                    after: vec![after],
+                }),
+            }
+        }
+        ranges
+    }
+
+    /// 读取快照内 `[address, address + len)` 的数据，超出范围时返回 None
+    pub fn get(&self, address: usize, len: usize) -> Option<&[u8]> {
+        let offset = address.checked_sub(self.address)?;
+        self.data.get(offset..offset + len)
+    }
+
+    /// 在快照数据中扫描特征码，返回匹配的地址
+    pub fn scan(&self, pattern: &str) -> Result<Vec<usize>, MemoryError> {
+        let matches = pattern_scan::scan(Cursor::new(&self.data), pattern)?;
+        Ok(matches.into_iter().map(|i| self.address + i).collect())
+    }
+}
+
+impl ChangedRange {
+    /// 包含该范围的最小自然对齐窗口 `(地址, 宽度)`，宽度为 1、2、4 或 8
+    ///
+    /// 范围跨越 8 字节边界时返回 None。
+    pub fn typed_window(&self) -> Option<(usize, usize)> {
+        let end = self.address + self.before.len();
+        [1, 2, 4, 8].into_iter().find_map(|width| {
+            let start = self.address / width * width;
+            (end <= start + width).then_some((start, width))
+        })
+    }
+}
+
+/// 按宽度解释小端字节
+pub fn typed_values(bytes: &[u8]) -> Vec<(&'static str, TypedValue)> {
+    match *bytes {
+        [b] => vec![
+            ("u8", TypedValue::UInt(b as u64)),
+            ("i8", TypedValue::Int(b as i8 as i64)),
+        ],
+        [a, b] => {
+            let v = u16::from_le_bytes([a, b]);
+            vec![
+                ("u16", TypedValue::UInt(v as u64)),
+                ("i16", TypedValue::Int(v as i16 as i64)),
+            ]
+        }
+        [a, b, c, d] => {
+            let v = u32::from_le_bytes([a, b, c, d]);
+            vec![
+                ("u32", TypedValue::UInt(v as u64)),
+                ("i32", TypedValue::Int(v as i32 as i64)),
+                ("f32", TypedValue::Float(f32::from_bits(v) as f64)),
+            ]
+        }
+        [a, b, c, d, e, f, g, h] => {
+            let v = u64::from_le_bytes([a, b, c, d, e, f, g, h]);
+            vec![
+                ("u64", TypedValue::UInt(v)),
+                ("i64", TypedValue::Int(v as i64)),
+                ("f64", TypedValue::Float(f64::from_bits(v))),
+            ]
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(address: usize, data: &[u8]) -> Snapshot {
+        Snapshot {
+            address,
+            timestamp: 1_700_000_000_000,
+            module_base: 0x1_4000_0000,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let snap = snapshot(0x1000, &[1, 2, 3, 4, 5]);
+        let bytes = snap.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE + 5);
+        assert_eq!(&bytes[0..4], b"EGSN");
+        assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snap);
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let mut bytes = snapshot(0x1000, &[1, 2, 3]).to_bytes();
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Truncated(43, 42))
+        ));
+        bytes[4] = 9;
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion(9))
+        ));
+        bytes[0] = b'X';
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::InvalidMagic)
+        ));
+        assert!(Snapshot::from_bytes(b"EGSN").is_err());
+    }
+
+    #[test]
+    fn diff_ranges() {
+        let before = snapshot(0x1000, &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let after = snapshot(0x1000, &[0, 1, 2, 0, 0, 0, 0, 0, 9, 0]);
+        let changes = before.diff(&after);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].address, 0x1001);
+        assert_eq!(changes[0].before, vec![0, 0]);
+        assert_eq!(changes[0].after, vec![1, 2]);
+        assert_eq!(changes[1].address, 0x1008);
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn diff_overlapping_only() {
+        let before = snapshot(0x1000, &[0, 0, 0, 0]);
+        let after = snapshot(0x1002, &[1, 0, 5, 5]);
+        let changes = before.diff(&after);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].address, 0x1002);
+        assert_eq!(changes[0].after, vec![1]);
+    }
+
+    #[test]
+    fn typed_window_alignment() {
+        let range = |address: usize, len: usize| ChangedRange {
+            address,
+            before: vec![0; len],
+            after: vec![0; len],
+        };
+        assert_eq!(range(0x1003, 1).typed_window(), Some((0x1003, 1)));
+        assert_eq!(range(0x1002, 2).typed_window(), Some((0x1002, 2)));
+        assert_eq!(range(0x1001, 2).typed_window(), Some((0x1000, 4)));
+        assert_eq!(range(0x1005, 3).typed_window(), Some((0x1004, 4)));
+        assert_eq!(range(0x1003, 2).typed_window(), Some((0x1000, 8)));
+        assert_eq!(range(0x1007, 2).typed_window(), None);
+    }
+
+    #[test]
+    fn interpret_values() {
+        let values = typed_values(&1.5f32.to_le_bytes());
+        assert_eq!(values[0], ("u32", TypedValue::UInt(0x3FC0_0000)));
+        assert_eq!(values[2], ("f32", TypedValue::Float(1.5)));
+        let values = typed_values(&[0xFF]);
+        assert_eq!(values[1], ("i8", TypedValue::Int(-1)));
+        assert!(typed_values(&[0, 0, 0]).is_empty());
+    }
+
+    #[test]
+    fn scan_data() {
+        let snap = snapshot(0x2000, &[0x90, 0x48, 0x8B, 0x05, 0x48, 0x8B]);
+        assert_eq!(snap.scan("48 8B").unwrap(), vec![0x2001, 0x2004]);
+    }
+}
@@ -0,0 +1,71 @@
+//! x64 回调跳板代码生成
+//!
+//! 跳板函数遵循 Windows x64 调用约定，将寄存器参数落地到栈上后，
+//! 以统一的签名调用分发函数：
+//!
+//! ```text
+//! extern "C" fn dispatch(ctx: *const c_void, int_args: *const u64, float_args: *const f64, ret: *mut u64)
+//! ```
+//!
+//! - `int_args`: 指向调用者的影子空间，与后续栈参数连续，第 i 个参数位于 `int_args[i]`
+//! - `float_args`: 前 4 个参数的 xmm0-xmm3 寄存器值
+//! - `ret`: 返回值槽位，返回时同时写入 rax 与 xmm0
+
+/// 跳板代码长度
+pub const THUNK_SIZE: usize = 100;
+
+/// 生成跳板代码
+pub fn build_thunk(context: u64, dispatch: u64) -> Vec<u8> {
+    let mut code = Vec::with_capacity(THUNK_SIZE);
+
+    // 寄存器参数落地到影子空间
+    code.extend_from_slice(&[0x48, 0x89, 0x4C, 0x24, 0x08]); // mov [rsp+08h], rcx
+    code.extend_from_slice(&[0x48, 0x89, 0x54, 0x24, 0x10]); // mov [rsp+10h], rdx
+    code.extend_from_slice(&[0x4C, 0x89, 0x44, 0x24, 0x18]); // mov [rsp+18h], r8
+    code.extend_from_slice(&[0x4C, 0x89, 0x4C, 0x24, 0x20]); // mov [rsp+20h], r9
+    // 入口处 rsp % 16 == 8，分配 0x48 字节后对齐
+    // [rsp+00h..20h] 被调用者影子空间
+    // [rsp+20h..40h] xmm0-xmm3
+    // [rsp+40h..48h] 返回值槽位
+    code.extend_from_slice(&[0x48, 0x83, 0xEC, 0x48]); // sub rsp, 48h
+    code.extend_from_slice(&[0xF2, 0x0F, 0x11, 0x44, 0x24, 0x20]); // movsd [rsp+20h], xmm0
+    code.extend_from_slice(&[0xF2, 0x0F, 0x11, 0x4C, 0x24, 0x28]); // movsd [rsp+28h], xmm1
+    code.extend_from_slice(&[0xF2, 0x0F, 0x11, 0x54, 0x24, 0x30]); // movsd [rsp+30h], xmm2
+    code.extend_from_slice(&[0xF2, 0x0F, 0x11, 0x5C, 0x24, 0x38]); // movsd [rsp+38h], xmm3
+    // 分发函数参数
+    code.extend_from_slice(&[0x48, 0xB9]); // mov rcx, imm64
+    code.extend_from_slice(&context.to_le_bytes());
+    code.extend_from_slice(&[0x48, 0x8D, 0x54, 0x24, 0x50]); // lea rdx, [rsp+50h]
+    code.extend_from_slice(&[0x4C, 0x8D, 0x44, 0x24, 0x20]); // lea r8, [rsp+20h]
+    code.extend_from_slice(&[0x4C, 0x8D, 0x4C, 0x24, 0x40]); // lea r9, [rsp+40h]
+    code.extend_from_slice(&[0x48, 0xB8]); // mov rax, imm64
+    code.extend_from_slice(&dispatch.to_le_bytes());
+    code.extend_from_slice(&[0xFF, 0xD0]); // call rax
+    // 返回值同时放入 rax 与 xmm0
+    code.extend_from_slice(&[0x48, 0x8B, 0x44, 0x24, 0x40]); // mov rax, [rsp+40h]
+    code.extend_from_slice(&[0x66, 0x48, 0x0F, 0x6E, 0xC0]); // movq xmm0, rax
+    code.extend_from_slice(&[0x48, 0x83, 0xC4, 0x48]); // add rsp, 48h
+    code.push(0xC3); // ret
+
+    debug_assert_eq!(code.len(), THUNK_SIZE);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_thunk_layout() {
+        let context = 0x1122_3344_5566_7788_u64;
+        let dispatch = 0x8877_6655_4433_2211_u64;
+        let code = build_thunk(context, dispatch);
+
+        assert_eq!(code.len(), THUNK_SIZE);
+        assert_eq!(&code[48..50], &[0x48, 0xB9]);
+        assert_eq!(&code[50..58], &context.to_le_bytes());
+        assert_eq!(&code[73..75], &[0x48, 0xB8]);
+        assert_eq!(&code[75..83], &dispatch.to_le_bytes());
+        assert_eq!(code.last(), Some(&0xC3));
+    }
+}
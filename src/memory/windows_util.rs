@@ -2,23 +2,32 @@ use std::ffi::c_void;
 
 use bitflags::bitflags;
 use windows::Win32::{
-    Foundation::HMODULE,
+    Foundation::{FreeLibrary, HMODULE},
     System::{
+        Diagnostics::Debug::FlushInstructionCache,
+        LibraryLoader::{GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS, GetModuleHandleExW},
         Memory::{
-            MEM_COMMIT, MEMORY_BASIC_INFORMATION, PAGE_PROTECTION_FLAGS, VirtualProtect,
-            VirtualQueryEx,
+            MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, MEMORY_BASIC_INFORMATION, PAGE_PROTECTION_FLAGS,
+            VirtualAlloc, VirtualFree, VirtualProtect, VirtualQueryEx,
+        },
+        ProcessStatus::{
+            EnumProcessModules, GetModuleFileNameExW, GetModuleInformation, MODULEINFO,
         },
-        ProcessStatus::{EnumProcessModules, GetModuleInformation, MODULEINFO},
         Threading::GetCurrentProcess,
     },
 };
 
+use windows::core::PCWSTR;
+
 use windows::Win32::System::Memory::{
     PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_NOACCESS,
     PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY,
 };
 
-use super::MemoryError;
+use super::{
+    MemoryError,
+    region::{MemoryRegion, ModuleInfo},
+};
 
 bitflags! {
     #[derive(Debug)]
@@ -69,6 +78,126 @@ pub unsafe fn get_base_module_space() -> Result<(usize, usize), windows::core::E
     }
 }
 
+/// 持有模块的引用计数，持有期间模块不会被卸载
+pub struct ModulePin(HMODULE);
+
+impl ModulePin {
+    /// 固定包含 `address` 的模块，地址不属于任何已加载模块时返回 `None`
+    pub fn new(address: usize) -> Option<Self> {
+        let mut module = HMODULE::default();
+        unsafe {
+            GetModuleHandleExW(
+                GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+                PCWSTR(address as *const u16),
+                &mut module,
+            )
+        }
+        .ok()?;
+        Some(Self(module))
+    }
+
+    /// 模块基址
+    pub fn base(&self) -> usize {
+        self.0.0 as usize
+    }
+}
+
+impl Drop for ModulePin {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { FreeLibrary(self.0) } {
+            log::error!("Failed to release module pin: {}", e);
+        }
+    }
+}
+
+/// 枚举进程中已加载的模块，第一个为主模块
+///
+/// # Safety
+///
+/// 调用 Windows API
+pub unsafe fn enumerate_modules() -> Result<Vec<ModuleInfo>, windows::core::Error> {
+    unsafe {
+        let hprocess = GetCurrentProcess();
+        let mut modules: [HMODULE; 1024] = [HMODULE::default(); 1024];
+        let mut cb_needed: u32 = 0;
+
+        EnumProcessModules(
+            hprocess,
+            modules.as_mut_ptr(),
+            (modules.len() * std::mem::size_of::<HMODULE>()) as u32,
+            &mut cb_needed,
+        )?;
+
+        let module_count = (cb_needed as usize / std::mem::size_of::<HMODULE>()).min(modules.len());
+        let mut result = Vec::with_capacity(module_count);
+        for &hmodule in &modules[..module_count] {
+            let mut module_info = MODULEINFO::default();
+            GetModuleInformation(
+                hprocess,
+                hmodule,
+                &mut module_info,
+                std::mem::size_of::<MODULEINFO>() as u32,
+            )?;
+
+            let mut buffer = [0u16; 1024];
+            let len = GetModuleFileNameExW(Some(hprocess), Some(hmodule), &mut buffer) as usize;
+            let path = String::from_utf16_lossy(&buffer[..len]);
+            let name = path
+                .rsplit(['\\', '/'])
+                .next()
+                .unwrap_or_default()
+                .to_string();
+
+            result.push(ModuleInfo {
+                name,
+                path,
+                base: module_info.lpBaseOfDll as usize,
+                size: module_info.SizeOfImage as usize,
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+/// 查询包含地址的内存区域，超出用户地址空间时返回 None
+pub fn query_region(address: usize) -> Option<MemoryRegion> {
+    let mut mbi = MEMORY_BASIC_INFORMATION::default();
+    let result = unsafe {
+        VirtualQueryEx(
+            GetCurrentProcess(),
+            Some(address as *const _),
+            &mut mbi,
+            size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+    if result == 0 {
+        return None;
+    }
+    Some(MemoryRegion {
+        base: mbi.BaseAddress as usize,
+        size: mbi.RegionSize,
+        allocation_base: mbi.AllocationBase as usize,
+        protect: mbi.Protect.0,
+        state: mbi.State.0,
+        kind: mbi.Type.0,
+    })
+}
+
+/// 从低地址到高地址遍历整个地址空间的内存区域
+pub fn query_regions() -> Vec<MemoryRegion> {
+    let mut regions = vec![];
+    let mut address = 0usize;
+    while let Some(region) = query_region(address) {
+        match region.base.checked_add(region.size) {
+            Some(next) if next > address => address = next,
+            _ => break,
+        }
+        regions.push(region);
+    }
+    regions
+}
+
 /// 获取内存的权限
 pub unsafe fn get_memory_state(address: usize) -> Result<MemoryState, windows::core::Error> {
     let mbi = unsafe {
@@ -122,6 +251,9 @@ pub struct VirtualProtectGuard {
     dwsize: usize,
 }
 
+unsafe impl Send for VirtualProtectGuard {}
+unsafe impl Sync for VirtualProtectGuard {}
+
 impl Drop for VirtualProtectGuard {
     fn drop(&mut self) {
         if let Err(e) = self.reset_protect() {
@@ -147,6 +279,24 @@ impl VirtualProtectGuard {
         Ok(this)
     }
 
+    pub fn address(&self) -> usize {
+        self.ptr as usize
+    }
+
+    pub fn size(&self) -> usize {
+        self.dwsize
+    }
+
+    /// 修改前的页保护标志
+    pub fn old_protect(&self) -> u32 {
+        self.old_protect.0
+    }
+
+    /// 放弃守卫，不恢复页保护
+    pub fn forget(self) {
+        std::mem::forget(self);
+    }
+
     fn set_protect(&mut self) -> Result<(), MemoryError> {
         unsafe {
             VirtualProtect(
@@ -171,3 +321,61 @@ impl VirtualProtectGuard {
         }
     }
 }
+
+/// 可执行内存 RAII 对象，释放时归还内存
+pub struct ExecutableMemory {
+    ptr: *mut c_void,
+    size: usize,
+}
+
+unsafe impl Send for ExecutableMemory {}
+unsafe impl Sync for ExecutableMemory {}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { VirtualFree(self.ptr, 0, MEM_RELEASE) } {
+            log::error!("Failed to free executable memory at {:p}: {}", self.ptr, e);
+        }
+    }
+}
+
+impl ExecutableMemory {
+    /// 分配新的可执行内存并写入代码，写入后内存为只读可执行
+    pub fn try_new(code: &[u8]) -> Result<Self, MemoryError> {
+        if code.is_empty() {
+            return Err(MemoryError::InvalidSize(0));
+        }
+
+        unsafe {
+            let ptr = VirtualAlloc(None, code.len(), MEM_COMMIT | MEM_RESERVE, PAGE_READWRITE);
+            if ptr.is_null() {
+                return Err(windows::core::Error::from_win32().into());
+            }
+            // 先构造对象，出错时由 Drop 释放内存
+            let this = Self {
+                ptr,
+                size: code.len(),
+            };
+
+            std::ptr::copy_nonoverlapping(code.as_ptr(), ptr as *mut u8, code.len());
+            VirtualProtect(
+                ptr,
+                code.len(),
+                PAGE_EXECUTE_READ,
+                &mut PAGE_PROTECTION_FLAGS::default(),
+            )
+            .map_err(MemoryError::VirtualProtect)?;
+            FlushInstructionCache(GetCurrentProcess(), Some(ptr), code.len())?;
+
+            Ok(this)
+        }
+    }
+
+    pub fn address(&self) -> usize {
+        self.ptr as usize
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
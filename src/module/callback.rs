@@ -0,0 +1,316 @@
+//! Lua 函数包装为原生回调
+
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use mlua::prelude::*;
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::memory::{ExecutableMemory, MemoryUtils};
+
+use super::{EgLib, luaptr::LuaPtr};
+
+/// 已创建的回调，以跳板地址为键
+static CALLBACKS: LazyLock<Mutex<HashMap<usize, NativeCallback>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 已释放但仍有调用在执行的回调，调用结束后才真正释放
+static RETIRED: LazyLock<Mutex<Vec<NativeCallback>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// 回调参数及返回值类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeType {
+    Void,
+    Bool,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+    Ptr,
+}
+
+impl FromStr for NativeType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let ty = match s {
+            "void" => NativeType::Void,
+            "bool" => NativeType::Bool,
+            "i8" => NativeType::I8,
+            "u8" => NativeType::U8,
+            "i16" => NativeType::I16,
+            "u16" => NativeType::U16,
+            "i32" => NativeType::I32,
+            "u32" => NativeType::U32,
+            "i64" => NativeType::I64,
+            "u64" => NativeType::U64,
+            "f32" => NativeType::F32,
+            "f64" => NativeType::F64,
+            "ptr" => NativeType::Ptr,
+            other => return Err(Error::InvalidValue("native type name", other.to_string())),
+        };
+        Ok(ty)
+    }
+}
+
+impl NativeType {
+    fn is_float(self) -> bool {
+        matches!(self, NativeType::F32 | NativeType::F64)
+    }
+
+    /// 将原生参数槽位的值转换为 Lua 值
+    fn raw_to_lua(self, lua: &Lua, raw: u64) -> LuaResult<LuaValue> {
+        let value = match self {
+            NativeType::Void => LuaNil,
+            NativeType::Bool => LuaValue::Boolean(raw as u8 != 0),
+            NativeType::I8 => LuaValue::Integer(raw as i8 as i64),
+            NativeType::U8 => LuaValue::Integer(raw as u8 as i64),
+            NativeType::I16 => LuaValue::Integer(raw as i16 as i64),
+            NativeType::U16 => LuaValue::Integer(raw as u16 as i64),
+            NativeType::I32 => LuaValue::Integer(raw as i32 as i64),
+            NativeType::U32 => LuaValue::Integer(raw as u32 as i64),
+            NativeType::I64 | NativeType::U64 => LuaValue::Integer(raw as i64),
+            NativeType::F32 => LuaValue::Number(f32::from_bits(raw as u32) as f64),
+            NativeType::F64 => LuaValue::Number(f64::from_bits(raw)),
+            NativeType::Ptr => LuaPtr::new(raw).into_lua(lua)?,
+        };
+        Ok(value)
+    }
+
+    /// 将 Lua 返回值转换为原生返回值
+    fn lua_to_raw(self, lua: &Lua, value: LuaValue) -> LuaResult<u64> {
+        let raw = match self {
+            NativeType::Void => 0,
+            NativeType::Bool => bool::from_lua(value, lua)? as u64,
+            NativeType::F32 => f32::from_lua(value, lua)?.to_bits() as u64,
+            NativeType::F64 => f64::from_lua(value, lua)?.to_bits(),
+            NativeType::Ptr => LuaPtr::from_lua(value, lua)?.to_u64(),
+            _ => i64::from_lua(value, lua)? as u64,
+        };
+        Ok(raw)
+    }
+}
+
+/// 回调签名，格式如 `void(ptr,i32)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackSignature {
+    ret: NativeType,
+    args: Vec<NativeType>,
+}
+
+impl FromStr for CallbackSignature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidValue("signature like 'void(ptr,i32)'", s.to_string());
+
+        let s = s.trim();
+        let (ret, rest) = s.split_once('(').ok_or_else(invalid)?;
+        let args = rest.strip_suffix(')').ok_or_else(invalid)?;
+
+        let ret = NativeType::from_str(ret.trim())?;
+        let args = if args.trim().is_empty() {
+            vec![]
+        } else {
+            args.split(',')
+                .map(|arg| NativeType::from_str(arg.trim()))
+                .collect::<Result<Vec<_>>>()?
+        };
+        if args.contains(&NativeType::Void) {
+            return Err(Error::InvalidValue("non-void argument type", s.to_string()));
+        }
+
+        Ok(Self { ret, args })
+    }
+}
+
+/// 跳板调用时的上下文
+struct CallbackContext {
+    lua: mlua::WeakLua,
+    function: LuaRegistryKey,
+    signature: CallbackSignature,
+    /// 正在执行的调用数
+    in_flight: AtomicUsize,
+}
+
+/// 调用期间持有，离开作用域时减少调用计数
+struct InFlightGuard<'a>(&'a AtomicUsize);
+
+impl<'a> InFlightGuard<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// 原生回调对象，持有上下文与跳板内存
+struct NativeCallback {
+    // 先释放跳板，再释放上下文
+    thunk: ExecutableMemory,
+    context: Box<CallbackContext>,
+}
+
+impl NativeCallback {
+    fn is_busy(&self) -> bool {
+        self.context.in_flight.load(Ordering::Acquire) > 0
+    }
+}
+
+/// 创建原生回调，返回跳板地址
+pub fn create_callback(lua: &Lua, function: LuaFunction, signature: &str) -> Result<usize> {
+    let signature = CallbackSignature::from_str(signature)?;
+    let context = Box::new(CallbackContext {
+        lua: lua.weak(),
+        function: lua.create_registry_value(function)?,
+        signature,
+        in_flight: AtomicUsize::new(0),
+    });
+
+    let thunk = MemoryUtils::alloc_thunk(
+        context.as_ref() as *const CallbackContext as usize,
+        callback_dispatch as usize,
+    )?;
+    let address = thunk.address();
+    sweep_retired(&mut RETIRED.lock());
+    CALLBACKS
+        .lock()
+        .insert(address, NativeCallback { thunk, context });
+
+    Ok(address)
+}
+
+/// 释放原生回调，释放后跳板地址不可再被调用
+///
+/// 仍有原生线程在执行该回调时延迟释放，直到调用返回。计数只覆盖分发函数的执行期间，
+/// 调用方仍需保证释放前已移除指向跳板的钩子，不会再有新的调用进入。
+pub fn release_callback(address: usize) -> bool {
+    let Some(callback) = CALLBACKS.lock().remove(&address) else {
+        return false;
+    };
+    let mut retired = RETIRED.lock();
+    retired.push(callback);
+    sweep_retired(&mut retired);
+    true
+}
+
+/// 释放所有原生回调
+///
+/// 仍在执行的回调无法安全释放，将被泄漏。Lua 状态卸载后它们不再调用 Lua 函数。
+pub fn release_all_callbacks() {
+    let mut callbacks = CALLBACKS.lock();
+    log::debug!("release_all_callbacks: count={}", callbacks.len());
+    let mut retired = RETIRED.lock();
+    retired.extend(callbacks.drain().map(|(_, callback)| callback));
+    sweep_retired(&mut retired);
+    if !retired.is_empty() {
+        log::warn!(
+            "release_all_callbacks: leaking {} callbacks still in use",
+            retired.len()
+        );
+        for callback in retired.drain(..) {
+            std::mem::forget(callback);
+        }
+    }
+}
+
+/// 释放调用已全部返回的回调
+fn sweep_retired(retired: &mut Vec<NativeCallback>) {
+    retired.retain(|callback| callback.is_busy());
+}
+
+/// 跳板统一调用的分发函数
+unsafe extern "C" fn callback_dispatch(
+    context: *const CallbackContext,
+    int_args: *const u64,
+    float_args: *const f64,
+    ret: *mut u64,
+) {
+    let context = unsafe { &*context };
+    let _guard = InFlightGuard::enter(&context.in_flight);
+    let signature = &context.signature;
+
+    // 读取原生参数
+    let raw_args = signature
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| unsafe {
+            if ty.is_float() && i < 4 {
+                (*float_args.add(i)).to_bits()
+            } else {
+                *int_args.add(i)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let Some(lua) = context.lua.try_upgrade() else {
+        log::error!("Native callback: Lua state not found via weak reference");
+        unsafe { *ret = 0 };
+        return;
+    };
+
+    let result = EgLib::run_with_global_lock(&lua, |lua| -> LuaResult<u64> {
+        let function = lua.registry_value::<LuaFunction>(&context.function)?;
+        let args = signature
+            .args
+            .iter()
+            .zip(raw_args)
+            .map(|(ty, raw)| ty.raw_to_lua(lua, raw))
+            .collect::<LuaResult<LuaMultiValue>>()?;
+        let value = function.call::<LuaValue>(args)?;
+        signature.ret.lua_to_raw(lua, value)
+    });
+
+    let value = result.unwrap_or_else(|e| {
+        log::error!("Native callback: calling Lua function error: {}", e);
+        0
+    });
+    unsafe { *ret = value };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signature() {
+        let sig = CallbackSignature::from_str("void(ptr,i32)").unwrap();
+        assert_eq!(sig.ret, NativeType::Void);
+        assert_eq!(sig.args, vec![NativeType::Ptr, NativeType::I32]);
+
+        let sig = CallbackSignature::from_str(" f32 ( f64 , u8 ) ").unwrap();
+        assert_eq!(sig.ret, NativeType::F32);
+        assert_eq!(sig.args, vec![NativeType::F64, NativeType::U8]);
+
+        let sig = CallbackSignature::from_str("bool()").unwrap();
+        assert!(sig.args.is_empty());
+    }
+
+    #[test]
+    fn test_parse_signature_invalid() {
+        assert!(CallbackSignature::from_str("void").is_err());
+        assert!(CallbackSignature::from_str("void(ptr").is_err());
+        assert!(CallbackSignature::from_str("int(ptr)").is_err());
+        assert!(CallbackSignature::from_str("void(void)").is_err());
+        assert!(CallbackSignature::from_str("void(ptr,)").is_err());
+    }
+}
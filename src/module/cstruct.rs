@@ -0,0 +1,206 @@
+//! 基于 C 结构体定义的类型化内存访问
+
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use parking_lot::RwLock;
+
+use crate::error::{Error, Result};
+use crate::memory::{CType, CTypeRegistry, Layout, MemoryUtils};
+
+use super::luaptr::LuaPtr;
+
+pub type SharedCTypeRegistry = Arc<RwLock<CTypeRegistry>>;
+
+/// 结构体视图，按字段名读写内存
+pub struct StructView {
+    registry: SharedCTypeRegistry,
+    layout: Arc<Layout>,
+    address: usize,
+}
+
+impl LuaUserData for StructView {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "StructView");
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("{}@0x{:016X}", this.layout.name, this.address))
+        });
+        methods.add_meta_method(LuaMetaMethod::Index, |lua, this, key: String| {
+            // 保留字段
+            match key.as_str() {
+                "_ptr" => return LuaPtr::new(this.address as u64).into_lua(lua),
+                "_type" => return this.layout.name.clone().into_lua(lua),
+                "_size" => return this.layout.size.into_lua(lua),
+                _ => {}
+            }
+            let field = this.field(&key).into_lua_err()?;
+            read_value(lua, &this.registry, &field.ty, this.address + field.offset).into_lua_err()
+        });
+        methods.add_meta_method(
+            LuaMetaMethod::NewIndex,
+            |lua, this, (key, value): (String, LuaValue)| {
+                let field = this.field(&key).into_lua_err()?;
+                write_value(lua, &field.ty, this.address + field.offset, value).into_lua_err()
+            },
+        );
+    }
+}
+
+impl StructView {
+    pub fn new(registry: SharedCTypeRegistry, layout: Arc<Layout>, address: usize) -> Self {
+        Self {
+            registry,
+            layout,
+            address,
+        }
+    }
+
+    fn field(&self, name: &str) -> Result<&crate::memory::Field> {
+        self.layout.field(name).ok_or_else(|| {
+            Error::InvalidValue("field name", format!("{}.{}", self.layout.name, name))
+        })
+    }
+}
+
+/// 数组视图，下标从 0 开始，与 C 一致
+pub struct ArrayView {
+    registry: SharedCTypeRegistry,
+    elem: CType,
+    len: usize,
+    address: usize,
+}
+
+impl LuaUserData for ArrayView {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ArrayView");
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.len));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Array[{}]@0x{:016X}", this.len, this.address))
+        });
+        methods.add_meta_method(LuaMetaMethod::Index, |lua, this, key: LuaValue| {
+            if let LuaValue::String(s) = &key
+                && s.to_string_lossy() == "_ptr"
+            {
+                return LuaPtr::new(this.address as u64).into_lua(lua);
+            }
+            let index = i64::from_lua(key, lua)?;
+            let address = this.element_address(index).into_lua_err()?;
+            read_value(lua, &this.registry, &this.elem, address).into_lua_err()
+        });
+        methods.add_meta_method(
+            LuaMetaMethod::NewIndex,
+            |lua, this, (index, value): (i64, LuaValue)| {
+                let address = this.element_address(index).into_lua_err()?;
+                write_value(lua, &this.elem, address, value).into_lua_err()
+            },
+        );
+    }
+}
+
+impl ArrayView {
+    fn element_address(&self, index: i64) -> Result<usize> {
+        if index < 0 || index as usize >= self.len {
+            return Err(Error::InvalidValue(
+                "0 <= index < len",
+                format!("{} (len {})", index, self.len),
+            ));
+        }
+        let elem_size = self.elem.size().unwrap_or(0);
+        Ok(self.address + index as usize * elem_size)
+    }
+}
+
+/// 按类型读取内存值
+fn read_value(
+    lua: &Lua,
+    registry: &SharedCTypeRegistry,
+    ty: &CType,
+    address: usize,
+) -> Result<LuaValue> {
+    let read_raw = |size: u32| MemoryUtils::quick_read(address, size, true);
+
+    let value = match ty {
+        CType::Bool => LuaValue::Boolean(read_raw(1)?[0] != 0),
+        CType::I8 => LuaValue::Integer(read_raw(1)?[0] as i8 as i64),
+        CType::U8 => LuaValue::Integer(read_raw(1)?[0] as i64),
+        CType::I16 => LuaValue::Integer(i64::from_le_bytes(read_raw(2)?) as i16 as i64),
+        CType::U16 => LuaValue::Integer(i64::from_le_bytes(read_raw(2)?) as u16 as i64),
+        CType::I32 => LuaValue::Integer(i64::from_le_bytes(read_raw(4)?) as i32 as i64),
+        CType::U32 => LuaValue::Integer(i64::from_le_bytes(read_raw(4)?) as u32 as i64),
+        CType::I64 | CType::U64 => LuaValue::Integer(i64::from_le_bytes(read_raw(8)?)),
+        CType::F32 => {
+            let bytes = read_raw(4)?;
+            let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            LuaValue::Number(value as f64)
+        }
+        CType::F64 => LuaValue::Number(f64::from_le_bytes(read_raw(8)?)),
+        CType::Pointer(pointee) => {
+            let target = u64::from_le_bytes(read_raw(8)?) as usize;
+            // 指向结构体的指针直接返回视图
+            let resolved = registry.read().resolve(pointee);
+            match resolved {
+                CType::Record(layout) => {
+                    if target == 0 {
+                        LuaNil
+                    } else {
+                        StructView::new(registry.clone(), layout, target).into_lua(lua)?
+                    }
+                }
+                _ => LuaPtr::new(target as u64).into_lua(lua)?,
+            }
+        }
+        CType::Array(elem, len) => ArrayView {
+            registry: registry.clone(),
+            elem: registry.read().resolve(elem),
+            len: *len,
+            address,
+        }
+        .into_lua(lua)?,
+        CType::Record(layout) => {
+            StructView::new(registry.clone(), layout.clone(), address).into_lua(lua)?
+        }
+        CType::Void | CType::Opaque(_) => {
+            return Err(Error::InvalidValue(
+                "complete type",
+                "void or incomplete struct".to_string(),
+            ));
+        }
+    };
+    Ok(value)
+}
+
+/// 按类型写入内存值，仅支持标量与指针
+fn write_value(lua: &Lua, ty: &CType, address: usize, value: LuaValue) -> Result<()> {
+    let bytes = match ty {
+        CType::Bool => vec![bool::from_lua(value, lua)? as u8],
+        CType::I8 | CType::U8 => i64::from_lua(value, lua)?.to_le_bytes()[..1].to_vec(),
+        CType::I16 | CType::U16 => i64::from_lua(value, lua)?.to_le_bytes()[..2].to_vec(),
+        CType::I32 | CType::U32 => i64::from_lua(value, lua)?.to_le_bytes()[..4].to_vec(),
+        CType::I64 | CType::U64 => i64::from_lua(value, lua)?.to_le_bytes().to_vec(),
+        CType::F32 => f32::from_lua(value, lua)?.to_le_bytes().to_vec(),
+        CType::F64 => f64::from_lua(value, lua)?.to_le_bytes().to_vec(),
+        CType::Pointer(_) => {
+            let target = match &value {
+                LuaValue::UserData(ud) if ud.is::<StructView>() => {
+                    ud.borrow::<StructView>()?.address as u64
+                }
+                _ => LuaPtr::from_lua(value, lua)?.to_u64(),
+            };
+            target.to_le_bytes().to_vec()
+        }
+        _ => {
+            return Err(Error::InvalidValue(
+                "scalar or pointer field",
+                "struct or array".to_string(),
+            ));
+        }
+    };
+    MemoryUtils::write(address, &bytes, true)?;
+    Ok(())
+}
@@ -0,0 +1,418 @@
+//! 流式文件句柄
+//!
+//! 所有打开的句柄登记于此，Lua 状态卸载时统一关闭。
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use mlua::prelude::*;
+use parking_lot::Mutex;
+
+use super::fs::BinaryData;
+
+static OPEN_FILES: LazyLock<Mutex<HashMap<u64, Arc<Mutex<FileStream>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 文件打开模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum OpenMode {
+    /// `"r"`，只读
+    Read,
+    /// `"w"`，创建或截断后写入
+    Write,
+    /// `"a"`，追加写入
+    Append,
+    /// `"r+"`，读写已存在的文件
+    ReadWrite,
+}
+
+impl OpenMode {
+    pub(super) fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "r" => Some(Self::Read),
+            "w" => Some(Self::Write),
+            "a" => Some(Self::Append),
+            "r+" => Some(Self::ReadWrite),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "r",
+            Self::Write => "w",
+            Self::Append => "a",
+            Self::ReadWrite => "r+",
+        }
+    }
+
+    fn readable(self) -> bool {
+        matches!(self, Self::Read | Self::ReadWrite)
+    }
+
+    fn writable(self) -> bool {
+        !matches!(self, Self::Read)
+    }
+}
+
+enum StreamState {
+    Read(BufReader<File>),
+    Write(BufWriter<File>),
+}
+
+/// 带缓冲的文件流，读写切换时同步缓冲区与文件位置
+struct FileStream {
+    /// 关闭后为 None
+    state: Option<StreamState>,
+    mode: OpenMode,
+}
+
+impl FileStream {
+    fn new(file: File, mode: OpenMode) -> Self {
+        let state = if mode.readable() {
+            StreamState::Read(BufReader::new(file))
+        } else {
+            StreamState::Write(BufWriter::new(file))
+        };
+        Self {
+            state: Some(state),
+            mode,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.is_none()
+    }
+
+    fn reader(&mut self) -> io::Result<&mut BufReader<File>> {
+        if !self.mode.readable() {
+            return Err(io::Error::other("file is not opened for reading"));
+        }
+        if let Some(StreamState::Write(writer)) = &mut self.state {
+            writer.flush()?;
+            let Some(StreamState::Write(writer)) = self.state.take() else {
+                unreachable!();
+            };
+            let (file, _) = writer.into_parts();
+            self.state = Some(StreamState::Read(BufReader::new(file)));
+        }
+        match &mut self.state {
+            Some(StreamState::Read(reader)) => Ok(reader),
+            _ => Err(closed_error()),
+        }
+    }
+
+    fn writer(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if !self.mode.writable() {
+            return Err(io::Error::other("file is not opened for writing"));
+        }
+        if let Some(StreamState::Read(reader)) = &mut self.state {
+            // 丢弃预读的数据，使文件位置回到逻辑位置
+            let position = reader.stream_position()?;
+            let Some(StreamState::Read(reader)) = self.state.take() else {
+                unreachable!();
+            };
+            let mut file = reader.into_inner();
+            let result = file.seek(SeekFrom::Start(position));
+            self.state = Some(StreamState::Write(BufWriter::new(file)));
+            result?;
+        }
+        match &mut self.state {
+            Some(StreamState::Write(writer)) => Ok(writer),
+            _ => Err(closed_error()),
+        }
+    }
+
+    /// 读取至多 `n` 字节，到达文件末尾时返回空
+    fn read(&mut self, n: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![];
+        self.reader()?.take(n as u64).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_to_end(&mut self) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![];
+        self.reader()?.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// 读取一行，不含换行符，到达文件末尾时返回 None
+    fn read_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut line = vec![];
+        if self.reader()?.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer()?.write_all(data)
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.state {
+            Some(StreamState::Read(reader)) => reader.seek(pos),
+            Some(StreamState::Write(writer)) => writer.seek(pos),
+            None => Err(closed_error()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            Some(StreamState::Write(writer)) => writer.flush(),
+            Some(StreamState::Read(_)) => Ok(()),
+            None => Err(closed_error()),
+        }
+    }
+
+    /// 文件大小，包含尚未写出的缓冲数据
+    fn size(&mut self) -> io::Result<u64> {
+        self.flush()?;
+        let file = match &self.state {
+            Some(StreamState::Read(reader)) => reader.get_ref(),
+            Some(StreamState::Write(writer)) => writer.get_ref(),
+            None => return Err(closed_error()),
+        };
+        Ok(file.metadata()?.len())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        let result = self.flush();
+        self.state = None;
+        result
+    }
+}
+
+fn closed_error() -> io::Error {
+    io::Error::other("file is closed")
+}
+
+/// 文件句柄对象，被回收或卸载时自动关闭
+pub(super) struct FileHandle {
+    id: u64,
+    path: String,
+    stream: Arc<Mutex<FileStream>>,
+}
+
+impl LuaUserData for FileHandle {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("path", |_, this| Ok(this.path.clone()));
+        fields.add_field_method_get("mode", |_, this| Ok(this.stream.lock().mode.as_str()));
+        fields.add_field_method_get("closed", |_, this| Ok(this.stream.lock().is_closed()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        // 省略 n 时读到文件末尾，到达末尾时返回 nil
+        methods.add_method("read", |lua, this, n: Option<usize>| {
+            let mut stream = this.stream.lock();
+            let data = match n {
+                Some(n) => stream.read(n),
+                None => stream.read_to_end(),
+            }
+            .map_err(|e| this.io_error("read", e))?;
+            if data.is_empty() && n != Some(0) {
+                return Ok(None);
+            }
+            lua.create_string(data).map(Some)
+        });
+        methods.add_method("read_line", |lua, this, ()| {
+            let line = this
+                .stream
+                .lock()
+                .read_line()
+                .map_err(|e| this.io_error("read", e))?;
+            line.map(|line| lua.create_string(line)).transpose()
+        });
+        // 逐行迭代，用于 `for line in file:lines() do`
+        methods.add_method("lines", |lua, this, ()| {
+            let stream = this.stream.clone();
+            let path = this.path.clone();
+            lua.create_function_mut(move |lua, ()| {
+                let line = stream.lock().read_line().map_err(|e| {
+                    LuaError::external(format!("Failed to read file {}: {}", path, e))
+                })?;
+                line.map(|line| lua.create_string(line)).transpose()
+            })
+        });
+        methods.add_method("write", |_, this, data: BinaryData| {
+            this.stream
+                .lock()
+                .write(&data.0)
+                .map_err(|e| this.io_error("write", e))
+        });
+        // 与 Lua 的 file:seek 相同，返回新的位置
+        methods.add_method(
+            "seek",
+            |_, this, (whence, offset): (Option<String>, Option<i64>)| {
+                let offset = offset.unwrap_or(0);
+                let pos = match whence.as_deref().unwrap_or("cur") {
+                    "set" => SeekFrom::Start(offset.max(0) as u64),
+                    "cur" => SeekFrom::Current(offset),
+                    "end" => SeekFrom::End(offset),
+                    whence => {
+                        return Err(LuaError::external(format!(
+                            "Invalid seek whence: {}. Use set/cur/end.",
+                            whence
+                        )));
+                    }
+                };
+                this.stream
+                    .lock()
+                    .seek(pos)
+                    .map_err(|e| this.io_error("seek", e))
+            },
+        );
+        methods.add_method("flush", |_, this, ()| {
+            this.stream
+                .lock()
+                .flush()
+                .map_err(|e| this.io_error("write", e))
+        });
+        methods.add_method("size", |_, this, ()| {
+            this.stream
+                .lock()
+                .size()
+                .map_err(|e| this.io_error("read", e))
+        });
+        methods.add_method("close", |_, this, ()| this.close());
+        // 支持 `local file <close> = service:open(...)`
+        methods.add_meta_method(LuaMetaMethod::Close, |_, this, ()| this.close());
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            let stream = this.stream.lock();
+            Ok(format!(
+                "FileHandle({}, \"{}\"{})",
+                this.path,
+                stream.mode.as_str(),
+                if stream.is_closed() { ", closed" } else { "" }
+            ))
+        });
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::error!("Failed to close file {}: {}", self.path, e);
+        }
+    }
+}
+
+impl FileHandle {
+    pub(super) fn new(path: String, file: File, mode: OpenMode) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let stream = Arc::new(Mutex::new(FileStream::new(file, mode)));
+        OPEN_FILES.lock().insert(id, stream.clone());
+        Self { id, path, stream }
+    }
+
+    fn close(&self) -> LuaResult<()> {
+        OPEN_FILES.lock().remove(&self.id);
+        let mut stream = self.stream.lock();
+        if stream.is_closed() {
+            return Ok(());
+        }
+        stream.close().map_err(|e| self.io_error("write", e))
+    }
+
+    fn io_error(&self, action: &str, e: io::Error) -> LuaError {
+        LuaError::external(format!("Failed to {} file {}: {}", action, self.path, e))
+    }
+}
+
+/// 关闭所有打开的文件句柄
+pub fn close_all_files() {
+    let mut files = OPEN_FILES.lock();
+    log::debug!("close_all_files: count={}", files.len());
+    for (_, stream) in files.drain() {
+        if let Err(e) = stream.lock().close() {
+            log::error!("Failed to close file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::test_util::TempDir;
+
+    use super::*;
+
+    fn open(path: &Path, mode: OpenMode) -> FileStream {
+        let file = match mode {
+            OpenMode::Read => File::open(path),
+            OpenMode::Write => File::create(path),
+            OpenMode::Append => File::options().append(true).open(path),
+            OpenMode::ReadWrite => File::options().read(true).write(true).open(path),
+        };
+        FileStream::new(file.unwrap(), mode)
+    }
+
+    #[test]
+    fn parse_modes() {
+        assert_eq!(OpenMode::parse("r+"), Some(OpenMode::ReadWrite));
+        assert_eq!(OpenMode::parse("rb"), None);
+        assert!(OpenMode::Append.writable() && !OpenMode::Append.readable());
+    }
+
+    #[test]
+    fn read_lines() {
+        let dir = TempDir::new("file_handle_lines");
+        dir.write("file", "first\r\nsecond\n\nlast");
+        let path = dir.path("file");
+        let mut stream = open(&path, OpenMode::Read);
+        assert_eq!(stream.read_line().unwrap().unwrap(), b"first");
+        assert_eq!(stream.read_line().unwrap().unwrap(), b"second");
+        assert_eq!(stream.read_line().unwrap().unwrap(), b"");
+        assert_eq!(stream.read_line().unwrap().unwrap(), b"last");
+        assert!(stream.read_line().unwrap().is_none());
+        assert!(stream.write(b"x").is_err());
+    }
+
+    #[test]
+    fn read_write_switch() {
+        let dir = TempDir::new("file_handle_switch");
+        dir.write("file", "0123456789");
+        let path = dir.path("file");
+        let mut stream = open(&path, OpenMode::ReadWrite);
+        assert_eq!(stream.read(3).unwrap(), b"012");
+        // 写入位置紧接已读取的数据，而不是预读缓冲区的末尾
+        stream.write(b"ab").unwrap();
+        assert_eq!(stream.read(2).unwrap(), b"56");
+        assert_eq!(stream.seek(SeekFrom::End(0)).unwrap(), 10);
+        stream.write(b"!").unwrap();
+        assert_eq!(stream.size().unwrap(), 11);
+        stream.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(stream.read_to_end().unwrap(), b"012ab56789!");
+        stream.close().unwrap();
+        assert!(stream.read(1).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"012ab56789!");
+    }
+
+    #[test]
+    fn append_and_flush() {
+        let dir = TempDir::new("file_handle_append");
+        dir.write("file", "log\n");
+        let path = dir.path("file");
+        let mut stream = open(&path, OpenMode::Append);
+        stream.write(b"line 1\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"log\n");
+        stream.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"log\nline 1\n");
+        assert!(stream.read(1).is_err());
+    }
+}
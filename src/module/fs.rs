@@ -1,19 +1,26 @@
 use std::{
     collections::HashMap,
     fs::File,
-    io::{Read, Write},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
     sync::LazyLock,
 };
 
-use bitflags::bitflags;
 use mlua::prelude::*;
 use parking_lot::Mutex;
 use serde::Deserialize;
 
 use crate::util;
 
-use super::LuaModule;
+use super::{
+    EgLib, LuaModule,
+    file_handle::{FileHandle, OpenMode},
+    fs_atomic,
+    fs_format::{self, Format, FormatOptions},
+    fs_glob,
+    fs_grants::{self, Access, GrantState, Permissions},
+    fs_meta::{self, EntryKind, FileStat},
+};
 
 static FS_MODULE: LazyLock<Mutex<FsModule>> = LazyLock::new(|| Mutex::new(FsModule::new()));
 
@@ -29,6 +36,45 @@ impl LuaModule for FsModule {
             "new",
             lua.create_function(|_, (_this, name): (LuaValue, String)| Ok(FsService::new(name)))?,
         )?;
+        fs.set(
+            "revoke",
+            lua.create_function(
+                |_, (_this, service_name, path): (LuaValue, String, String)| {
+                    Ok(FsModule::get_module().lock().revoke(&service_name, &path))
+                },
+            )?,
+        )?;
+        // 仅在 `eglib.__with_privileged` 中可用
+        fs.set(
+            "remove_rejection",
+            lua.create_function(
+                |_, (_this, service_name, path): (LuaValue, String, String)| {
+                    if !EgLib::is_privileged() {
+                        return Err(LuaError::external(
+                            "remove_rejection requires privileged access.",
+                        ));
+                    }
+                    Ok(FsModule::get_module()
+                        .lock()
+                        .remove_rejection(&service_name, &path))
+                },
+            )?,
+        )?;
+        // 仅在 `eglib.__with_privileged` 中可用
+        fs.set(
+            "clear_access",
+            lua.create_function(|_, (_this, service_name): (LuaValue, Option<String>)| {
+                if !EgLib::is_privileged() {
+                    return Err(LuaError::external(
+                        "clear_access requires privileged access.",
+                    ));
+                }
+                FsModule::get_module()
+                    .lock()
+                    .clear_access(service_name.as_deref());
+                Ok(())
+            })?,
+        )?;
         fs.set(
             "get_granted_access",
             lua.create_function(|lua, _: LuaValue| {
@@ -50,7 +96,10 @@ impl LuaModule for FsModule {
 impl FsModule {
     fn new() -> Self {
         Self {
-            granted: HashMap::new(),
+            granted: fs_grants::load_grants(
+                util::normalize_path(fs_grants::GRANTS_FILE),
+                now_millis(),
+            ),
         }
     }
 
@@ -58,17 +107,22 @@ impl FsModule {
         &FS_MODULE
     }
 
+    /// 保存授权到配置文件
+    fn save(&self) {
+        let path = util::normalize_path(fs_grants::GRANTS_FILE);
+        if let Err(e) = fs_grants::save_grants(path, &self.granted, now_millis()) {
+            log::warn!("Failed to save fs grants: {}", e);
+        }
+    }
+
     fn accept_access(&mut self, service_name: &str, access: Access) {
-        let acceptions = &mut self
-            .granted
+        let once = access.once;
+        self.granted
             .entry(service_name.to_string())
             .or_default()
-            .acceptions;
-        if !acceptions
-            .iter()
-            .any(|a| a.path == access.path && a.permissions == access.permissions)
-        {
-            acceptions.push(access);
+            .accept(access);
+        if !once {
+            self.save();
         }
     }
 
@@ -76,8 +130,8 @@ impl FsModule {
         self.granted
             .entry(service_name.to_string())
             .or_default()
-            .rejections
-            .push(access);
+            .reject(access);
+        self.save();
     }
 
     fn clear_access(&mut self, service_name: Option<&str>) {
@@ -86,127 +140,171 @@ impl FsModule {
         } else {
             self.granted.clear();
         }
+        self.save();
+    }
+
+    /// 撤销服务对 `path` 的授权，`path` 可以是授权时记录的模式，如 `C:/dir/**`
+    fn revoke(&mut self, service_name: &str, path: &str) -> bool {
+        let Some(state) = self.granted.get_mut(service_name) else {
+            return false;
+        };
+        let normalized = util::normalize_path(path).to_string_lossy().to_string();
+        let revoked = state.revoke(path)
+            | state.revoke(&normalized)
+            | state.revoke(&fs_glob::escape(&normalized));
+        if revoked {
+            self.save();
+        }
+        revoked
     }
 
+    /// 移除服务对 `path` 的拒绝记录，`path` 为记录中的路径模式
+    fn remove_rejection(&mut self, service_name: &str, path: &str) -> bool {
+        let removed = self
+            .granted
+            .get_mut(service_name)
+            .is_some_and(|state| state.remove_rejection(path));
+        if removed {
+            self.save();
+        }
+        removed
+    }
+
+    /// 用户是否拒绝了服务对 `path` 的访问
+    fn is_access_denied(&self, service_name: &str, path: &str, perm: Permissions) -> bool {
+        self.granted
+            .get(service_name)
+            .is_some_and(|state| state.is_denied(path, perm, now_millis()))
+    }
+
+    /// 检查权限，不消耗单次授权
     fn is_access_allowed(
         &self,
         service_name: &str,
         path: impl AsRef<Path>,
         perm: Permissions,
     ) -> (bool, String) {
-        let path = path.as_ref();
+        self.is_access_allowed_with(service_name, path, perm, true)
+    }
 
+    /// 同 `is_access_allowed`，`follow_link` 为 false 时不跟随最后一级链接
+    fn is_access_allowed_with(
+        &self,
+        service_name: &str,
+        path: impl AsRef<Path>,
+        perm: Permissions,
+        follow_link: bool,
+    ) -> (bool, String) {
+        let abs_path_str = match resolve_access_path(path, follow_link) {
+            Ok(abs_path_str) => abs_path_str,
+            Err(abs_path_str) => return (false, abs_path_str),
+        };
+        if is_in_data_dir(service_name, &abs_path_str) {
+            return (true, abs_path_str);
+        }
         let Some(state) = self.granted.get(service_name) else {
             log::warn!("Service not found: {}", service_name);
-            return (false, String::new());
+            return (false, abs_path_str);
         };
 
-        let abs_path = util::normalize_path(path);
-        let abs_path_str = abs_path.to_string_lossy().to_string();
-
-        // Helper function to check path matches
-        fn path_matches(pattern: &str, target: &str) -> bool {
-            if pattern.ends_with("/**") {
-                let base = pattern.trim_end_matches("**").trim_end_matches('/');
-                target.starts_with(base)
-                    && (target == base || target.starts_with(&format!("{}/", base)))
-            } else if pattern.ends_with("/*") {
-                let base = pattern.trim_end_matches('*').trim_end_matches('/');
-                target.starts_with(base) && !target[base.len()..].contains('/')
-            } else {
-                pattern == target
-            }
-        }
-
-        // Check rejections first
-        for reject in &state.rejections {
-            if reject.permissions.contains(perm) && path_matches(&reject.path, &abs_path_str) {
-                return (false, abs_path_str);
-            }
-        }
-
-        // Then check acceptions
-        for accept in &state.acceptions {
-            if accept.permissions.contains(perm) && path_matches(&accept.path, &abs_path_str) {
-                return (true, abs_path_str);
-            }
-        }
-
-        (false, abs_path_str)
+        let allowed = state.check(&abs_path_str, perm, now_millis()).is_some();
+        (allowed, abs_path_str)
     }
-}
 
-#[derive(Clone, Default)]
-struct GrantState {
-    acceptions: Vec<Access>,
-    rejections: Vec<Access>,
-}
-
-impl IntoLua for GrantState {
-    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
-        let table = lua.create_table()?;
-        table.set("acceptions", self.acceptions.into_lua(lua)?)?;
-        table.set("rejections", self.rejections.into_lua(lua)?)?;
-        Ok(LuaValue::Table(table))
+    /// 检查权限用于一次实际访问，匹配到单次授权时将其消耗
+    ///
+    /// 按跟随链接后的真实路径检查，返回的路径即应访问的路径。
+    fn use_access(
+        &mut self,
+        service_name: &str,
+        path: impl AsRef<Path>,
+        perm: Permissions,
+    ) -> (bool, String) {
+        self.use_access_with(service_name, path, perm, true)
     }
-}
 
-bitflags! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct Permissions: u8 {
-        const READ = 0b01;
-        const WRITE = 0b10;
+    /// 同 `use_access`，但不跟随最后一级链接，用于删除链接本身
+    fn use_link_access(
+        &mut self,
+        service_name: &str,
+        path: impl AsRef<Path>,
+        perm: Permissions,
+    ) -> (bool, String) {
+        self.use_access_with(service_name, path, perm, false)
     }
-}
 
-impl IntoLua for Permissions {
-    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
-        let mut string = String::new();
-        if self.contains(Permissions::READ) {
-            string.push('r');
-        }
-        if self.contains(Permissions::WRITE) {
-            string.push('w');
+    fn use_access_with(
+        &mut self,
+        service_name: &str,
+        path: impl AsRef<Path>,
+        perm: Permissions,
+        follow_link: bool,
+    ) -> (bool, String) {
+        let abs_path_str = match resolve_access_path(path, follow_link) {
+            Ok(abs_path_str) => abs_path_str,
+            Err(abs_path_str) => return (false, abs_path_str),
+        };
+        if is_in_data_dir(service_name, &abs_path_str) {
+            return (true, abs_path_str);
         }
+        let Some(state) = self.granted.get_mut(service_name) else {
+            log::warn!("Service not found: {}", service_name);
+            return (false, abs_path_str);
+        };
 
-        string.into_lua(lua)
+        let Some(index) = state.check(&abs_path_str, perm, now_millis()) else {
+            return (false, abs_path_str);
+        };
+        if state.acceptions[index].once {
+            state.acceptions.remove(index);
+        }
+        (true, abs_path_str)
     }
 }
 
-impl Permissions {
-    fn from_char(c: char) -> Option<Permissions> {
-        match c {
-            'r' => Some(Permissions::READ),
-            'w' => Some(Permissions::WRITE),
-            _ => None,
-        }
+/// 解析用于检查权限的真实路径
+///
+/// 无法解析时（如链接循环）返回 `Err`，其中为仅规范化的路径，用于错误信息。
+fn resolve_access_path(path: impl AsRef<Path>, follow_link: bool) -> Result<String, String> {
+    let resolved = if follow_link {
+        util::resolve_path(&path)
+    } else {
+        util::resolve_parent(&path)
+    };
+    match resolved {
+        Ok(abs_path) => Ok(abs_path.to_string_lossy().to_string()),
+        Err(_) => Err(util::normalize_path(path).to_string_lossy().to_string()),
     }
+}
 
-    fn from_str(s: &str) -> Option<Permissions> {
-        let mut perm = Permissions::empty();
-        for c in s.chars() {
-            if let Some(p) = Permissions::from_char(c) {
-                perm |= p;
-            } else {
-                return None;
-            }
-        }
-        Some(perm)
-    }
+/// 服务的私有数据目录
+fn service_data_dir(service_name: &str) -> PathBuf {
+    util::normalize_path(
+        Path::new(fs_grants::DATA_ROOT).join(fs_grants::data_dir_name(service_name)),
+    )
+}
+
+/// 真实路径 `path` 是否位于服务的数据目录中，数据目录无需授权即可读写
+fn is_in_data_dir(service_name: &str, path: &str) -> bool {
+    let Ok(data_dir) = util::resolve_path(service_data_dir(service_name)) else {
+        return false;
+    };
+    let pattern = format!("{}/**", fs_glob::escape(&data_dir.to_string_lossy()));
+    fs_glob::PathPattern::new(&pattern).is_ok_and(|pattern| pattern.matches(path))
 }
 
-#[derive(Debug, Clone)]
-struct Access {
-    path: String,
-    permissions: Permissions,
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
 }
 
-impl IntoLua for Access {
-    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
-        let table = lua.create_table()?;
-        table.set("path", self.path.into_lua(lua)?)?;
-        table.set("permissions", self.permissions.into_lua(lua)?)?;
-        Ok(LuaValue::Table(table))
+/// 用于对话框显示的权限名称
+fn permission_name(perm: Permissions) -> &'static str {
+    if perm.contains(Permissions::READ | Permissions::WRITE) {
+        "read/write"
+    } else if perm.contains(Permissions::WRITE) {
+        "write"
+    } else {
+        "read"
     }
 }
 
@@ -229,6 +327,11 @@ struct RequestAccessOptions {
     /// Save file mode, allows to select an unexisting file.
     #[serde(default)]
     save_file: bool,
+    /// Grant expires after this many seconds.
+    expires_in: Option<u64>,
+    /// Grant is valid for a single operation and not persisted.
+    #[serde(default)]
+    once: bool,
 }
 
 #[derive(Deserialize)]
@@ -243,7 +346,117 @@ impl FromLua for RequestAccessOptions {
     }
 }
 
-struct FsService {
+impl RequestAccessOptions {
+    /// 请求涉及的路径范围，用于记录与检查用户的拒绝，需在规范化 `directory` 后调用
+    ///
+    /// 未指定目录时用户可以选择任意路径，范围为任意路径。
+    fn scope(&self) -> RequestScope {
+        match (&self.directory, &self.file_name) {
+            (Some(dir), Some(file_name)) if !self.folder => {
+                let path = util::normalize_path(Path::new(dir).join(file_name))
+                    .to_string_lossy()
+                    .to_string();
+                RequestScope {
+                    pattern: fs_glob::escape(&path),
+                    target: path,
+                }
+            }
+            (Some(dir), _) => {
+                let suffix = if self.folder && !self.recursive {
+                    "/*"
+                } else {
+                    "/**"
+                };
+                RequestScope {
+                    pattern: format!("{}{}", fs_glob::escape(dir), suffix),
+                    target: dir.clone(),
+                }
+            }
+            (None, _) => RequestScope {
+                pattern: fs_grants::ANY_PATH.to_string(),
+                target: fs_grants::ANY_PATH.to_string(),
+            },
+        }
+    }
+}
+
+/// 请求涉及的路径范围
+struct RequestScope {
+    /// 用于检查拒绝的路径，未指定目录时为 `**`，只被拒绝任意路径的记录匹配
+    target: String,
+    /// 用户拒绝时记录的模式
+    pattern: String,
+}
+
+/// `load` 与 `save` 的选项
+#[derive(Deserialize, Default)]
+struct DataOptions {
+    /// 文件格式，默认按扩展名判断
+    format: Option<String>,
+    pretty: Option<bool>,
+    header: Option<bool>,
+    columns: Option<Vec<String>>,
+    atomic: Option<bool>,
+    backups: Option<u32>,
+}
+
+impl FromLua for DataOptions {
+    fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+        lua.from_value(value)
+    }
+}
+
+impl DataOptions {
+    fn format(&self, path_str: &str) -> LuaResult<Format> {
+        match self.format.as_deref() {
+            None | Some("auto") => Format::from_path(path_str).ok_or_else(|| {
+                LuaError::external(format!(
+                    "Cannot detect the format of {}. Specify `format`.",
+                    path_str
+                ))
+            }),
+            Some(name) => Format::parse(name).ok_or_else(|| {
+                LuaError::external(format!(
+                    "Unsupported format: {}. Use json/toml/yaml/ini/csv.",
+                    name
+                ))
+            }),
+        }
+    }
+
+    fn format_options(&self) -> FormatOptions {
+        let defaults = FormatOptions::default();
+        FormatOptions {
+            pretty: self.pretty.unwrap_or(defaults.pretty),
+            header: self.header.unwrap_or(defaults.header),
+            columns: self.columns.clone(),
+        }
+    }
+
+    fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            atomic: self.atomic,
+            backups: self.backups,
+        }
+    }
+}
+
+/// 整体写入文件的选项
+#[derive(Deserialize, Default)]
+struct WriteOptions {
+    /// 先写入临时文件再重命名覆盖，默认启用
+    atomic: Option<bool>,
+    /// 覆盖前保留的备份数量，默认不备份
+    backups: Option<u32>,
+}
+
+impl FromLua for WriteOptions {
+    fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+        lua.from_value(value)
+    }
+}
+
+pub(super) struct FsService {
     name: String,
 }
 
@@ -253,7 +466,7 @@ impl LuaUserData for FsService {
             "request_access",
             |_, this, mut options: RequestAccessOptions| {
                 // validate permission string
-                let Some(perm) = Permissions::from_str(&options.permission) else {
+                let Some(perm) = Permissions::parse(&options.permission) else {
                     return Err(LuaError::external(format!(
                         "Invalid permission string: {}. Use r/w/rw.",
                         options.permission
@@ -269,6 +482,15 @@ impl LuaUserData for FsService {
 
                 let mut module = FsModule::get_module().lock();
 
+                // 用户选择了不再询问时直接失败
+                let scope = options.scope();
+                if module.is_access_denied(&this.name, &scope.target, perm) {
+                    return Err(LuaError::external(format!(
+                        "Access to {} was denied by the user.",
+                        scope.target
+                    )));
+                }
+
                 // check if access already granted
                 if options.auto_grant && !options.multiple {
                     if options.folder && options.directory.is_some() {
@@ -332,18 +554,24 @@ impl LuaUserData for FsService {
                 match result {
                     Some(paths) => {
                         for path in &paths {
-                            let abs_path = util::normalize_path(path);
-                            let path_str = if options.folder {
-                                let mut path = abs_path.to_string_lossy().to_string();
-                                path.push_str(if options.recursive { "/**" } else { "/*" });
-                                path
-                            } else {
-                                abs_path.to_string_lossy().to_string()
-                            };
+                            // 记录真实路径，之后的访问同样按真实路径检查
+                            let abs_path = util::resolve_path(path)
+                                .unwrap_or_else(|_| util::normalize_path(path));
+                            // 路径中的 `*` `{` 等字符需要转义
+                            let mut path_str = fs_glob::escape(&abs_path.to_string_lossy());
+                            if options.folder {
+                                path_str.push_str(if options.recursive { "/**" } else { "/*" });
+                            }
 
                             let access = Access {
-                                path: path_str,
-                                permissions: perm,
+                                expires_at: options.expires_in.map(|secs| {
+                                    let millis = i64::try_from(secs)
+                                        .unwrap_or(i64::MAX)
+                                        .saturating_mul(1000);
+                                    now_millis().saturating_add(millis)
+                                }),
+                                once: options.once,
+                                ..Access::new(path_str, perm)
                             };
                             module.accept_access(&this.name, access);
                         }
@@ -352,42 +580,146 @@ impl LuaUserData for FsService {
                             .map(|p| p.to_string_lossy().to_string())
                             .collect())
                     }
-                    None => Ok(vec![]),
+                    None => {
+                        // 用户取消时询问是否记住拒绝
+                        let target = if scope.target == fs_grants::ANY_PATH {
+                            "any path"
+                        } else {
+                            scope.target.as_str()
+                        };
+                        let answer = rfd::MessageDialog::new()
+                            .set_level(rfd::MessageLevel::Warning)
+                            .set_title("File Access Denied")
+                            .set_description(format!(
+                                "\"{}\" requested {} access to {}.\n\nDon't ask again?",
+                                this.name,
+                                permission_name(perm),
+                                target
+                            ))
+                            .set_buttons(rfd::MessageButtons::YesNo)
+                            .show();
+                        if answer == rfd::MessageDialogResult::Yes {
+                            module.reject_access(&this.name, Access::new(scope.pattern, perm));
+                        }
+                        Ok(vec![])
+                    }
                 }
             },
         );
-        methods.add_method("read_text_file", |_, this, path_str: String| {
-            let mut file = OpenFileOptions::new(&path_str)
-                .with_service(&this.name)
-                .read()
-                .map_err(|e| e.into_lua_err())?;
-
-            let mut content = String::new();
-            file.read_to_string(&mut content).map_err(|e| {
-                LuaError::external(format!("Failed to read file {}: {}", path_str, e))
+        // 私有数据目录，不存在时创建
+        methods.add_method("data_dir", |_, this, ()| {
+            let data_dir = service_data_dir(&this.name);
+            std::fs::create_dir_all(&data_dir).map_err(|e| {
+                LuaError::external(format!(
+                    "Failed to create directory {}: {}",
+                    data_dir.display(),
+                    e
+                ))
             })?;
-
-            Ok(content)
+            Ok(data_dir.to_string_lossy().to_string())
+        });
+        methods.add_method("read_text_file", |_, this, path_str: String| {
+            this.read_text_file(&path_str)
         });
         methods.add_method(
             "write_text_file",
-            |_, this, (path_str, content): (String, String)| {
-                let mut file = OpenFileOptions::new(&path_str)
-                    .with_service(&this.name)
-                    .create()
+            |_, this, (path_str, content, options): (String, String, Option<WriteOptions>)| {
+                this.write_binary_file_with(
+                    &path_str,
+                    content.as_bytes(),
+                    &options.unwrap_or_default(),
+                )
+            },
+        );
+        // 读取结构化数据文件，转换为 Lua 值
+        methods.add_method(
+            "load",
+            |lua, this, (path_str, options): (String, Option<DataOptions>)| {
+                let options = options.unwrap_or_default();
+                let format = options.format(&path_str)?;
+                let text = this.read_text_file(&path_str)?;
+                let value =
+                    fs_format::parse(format, &text, &options.format_options()).map_err(|e| {
+                        LuaError::external(format!("Failed to load {}: {}", path_str, e))
+                    })?;
+                lua.to_value(&value)
+            },
+        );
+        methods.add_method(
+            "save",
+            |lua, this, (path_str, value, options): (String, LuaValue, Option<DataOptions>)| {
+                let options = options.unwrap_or_default();
+                let format = options.format(&path_str)?;
+                let value: serde_json::Value = lua.from_value(value)?;
+                let text = fs_format::serialize(format, &value, &options.format_options())
+                    .map_err(|e| {
+                        LuaError::external(format!("Failed to save {}: {}", path_str, e))
+                    })?;
+                this.write_binary_file_with(&path_str, text.as_bytes(), &options.write_options())
+            },
+        );
+        methods.add_method(
+            "read_file",
+            |lua, this, (path_str, offset, length): (String, Option<u64>, Option<u64>)| {
+                let content =
+                    this.read_binary_file_range(&path_str, offset.unwrap_or(0), length)?;
+                lua.create_string(content)
+            },
+        );
+        methods.add_method(
+            "write_file",
+            |_, this, (path_str, data, options): (String, BinaryData, Option<WriteOptions>)| {
+                this.write_binary_file_with(&path_str, &data.0, &options.unwrap_or_default())
+            },
+        );
+        // 用第 n 个备份替换文件，默认为最新的备份
+        methods.add_method(
+            "restore_backup",
+            |_, this, (path_str, n): (String, Option<u32>)| {
+                this.restore_backup(&path_str, n.unwrap_or(1))
+            },
+        );
+        methods.add_method(
+            "append_file",
+            |_, this, (path_str, data): (String, BinaryData)| {
+                let mut file = this
+                    .open_options(&path_str)
+                    .append()
                     .map_err(|e| e.into_lua_err())?;
 
-                file.write_all(content.as_bytes()).map_err(|e| {
+                file.write_all(&data.0).map_err(|e| {
                     LuaError::external(format!("Failed to write file {}: {}", path_str, e))
-                })?;
+                })
+            },
+        );
+        // 打开文件句柄，在打开时检查权限
+        methods.add_method(
+            "open",
+            |_, this, (path_str, mode_str): (String, Option<String>)| {
+                let mode_str = mode_str.as_deref().unwrap_or("r");
+                let Some(mode) = OpenMode::parse(mode_str) else {
+                    return Err(LuaError::external(format!(
+                        "Invalid open mode: {}. Use r/w/a/r+.",
+                        mode_str
+                    )));
+                };
 
-                Ok(())
+                let options = this.open_options(&path_str);
+                let file = match mode {
+                    OpenMode::Read => options.read(),
+                    OpenMode::Write => options.create(),
+                    OpenMode::Append => options.append(),
+                    OpenMode::ReadWrite => options.read_write(),
+                }
+                .map_err(|e| e.into_lua_err())?;
+
+                Ok(FileHandle::new(path_str, file, mode))
             },
         );
         methods.add_method("mkdir", |_, this, (path_str, recursive): (String, bool)| {
-            let module = FsModule::get_module().lock();
+            let mut module = FsModule::get_module().lock();
             let (ok, abs_path) =
-                module.is_access_allowed(&this.name, &path_str, Permissions::WRITE);
+                module.use_access(&this.name, this.full_path(&path_str), Permissions::WRITE);
             if !ok {
                 return Err(LuaError::external(format!(
                     "Access denied to path {}.",
@@ -403,9 +735,9 @@ impl LuaUserData for FsService {
             .map_err(|e| e.into_lua_err())
         });
         methods.add_method("remove", |_, this, path_str: String| {
-            let module = FsModule::get_module().lock();
+            let mut module = FsModule::get_module().lock();
             let (ok, abs_path) =
-                module.is_access_allowed(&this.name, &path_str, Permissions::WRITE);
+                module.use_link_access(&this.name, this.full_path(&path_str), Permissions::WRITE);
             if !ok {
                 return Err(LuaError::external(format!(
                     "Access denied to path {}.",
@@ -413,8 +745,11 @@ impl LuaUserData for FsService {
                 )));
             };
 
-            let path = Path::new(&abs_path);
-            if path.is_dir() {
+            // 删除链接本身，不删除其目标
+            let file_type = std::fs::symlink_metadata(&abs_path)?.file_type();
+            if file_type.is_symlink() {
+                std::fs::remove_file(&abs_path).or_else(|_| std::fs::remove_dir(&abs_path))
+            } else if file_type.is_dir() {
                 std::fs::remove_dir_all(&abs_path)
             } else {
                 std::fs::remove_file(&abs_path)
@@ -422,29 +757,89 @@ impl LuaUserData for FsService {
             .map_err(|e| e.into_lua_err())
         });
         methods.add_method("read_dir", |_, this, path_str: String| {
-            let module = FsModule::get_module().lock();
-            let (ok, abs_path) = module.is_access_allowed(&this.name, &path_str, Permissions::READ);
-            if !ok {
-                return Err(LuaError::external(format!(
-                    "Access denied to path {}.",
-                    abs_path
-                )));
-            };
+            let abs_path = this.queried_path(&path_str, Permissions::READ, true)?;
 
             let mut files = vec![];
             let mut dirs = vec![];
+            let mut others = vec![];
+            let module = FsModule::get_module().lock();
             for entry in std::fs::read_dir(&abs_path)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_file() {
-                    files.push(path.to_string_lossy().to_string());
-                } else {
-                    dirs.push(path.to_string_lossy().to_string());
-                }
+                let path = entry?.path();
+                // 链接按其目标分类，目标不可读时不访问目标
+                let follow_link = |link: &Path| {
+                    module
+                        .is_access_allowed(&this.name, link, Permissions::READ)
+                        .0
+                };
+                let list = match fs_meta::entry_kind(&path, follow_link) {
+                    EntryKind::Dir => &mut dirs,
+                    EntryKind::File => &mut files,
+                    EntryKind::Other => &mut others,
+                };
+                list.push(path.to_string_lossy().to_string());
+            }
+
+            Ok((dirs, files, others))
+        });
+        methods.add_method("exists", |_, this, path_str: String| this.exists(&path_str));
+        // 链接本身的信息，目标可访问时其余字段取自目标
+        methods.add_method("stat", |_, this, path_str: String| {
+            let link_path = this.queried_path(&path_str, Permissions::READ, false)?;
+            let stat_err = |e: std::io::Error| {
+                LuaError::external(format!("Failed to stat {}: {}", path_str, e))
+            };
+            let link_metadata = std::fs::symlink_metadata(&link_path).map_err(stat_err)?;
+            if !link_metadata.is_symlink() {
+                return Ok(FileStat::new(&link_metadata, false));
             }
 
-            Ok((dirs, files))
+            let module = FsModule::get_module().lock();
+            let (ok, target) = module.is_access_allowed(&this.name, &link_path, Permissions::READ);
+            let metadata = ok
+                .then(|| std::fs::metadata(target).ok())
+                .flatten()
+                .unwrap_or(link_metadata);
+            Ok(FileStat::new(&metadata, true))
         });
+        methods.add_method("copy", |_, this, (from, to): (String, String)| {
+            let from_path = this.checked_path(&from, Permissions::READ, true)?;
+            if !from_path.is_file() {
+                return Err(LuaError::external(format!("File {} not found.", from)));
+            }
+            let to_path = this
+                .open_options(&to)
+                .prepare_write()
+                .map_err(|e| e.into_lua_err())?;
+
+            std::fs::copy(&from_path, &to_path).map_err(|e| {
+                LuaError::external(format!("Failed to copy {} to {}: {}", from, to, e))
+            })?;
+            Ok(())
+        });
+        methods.add_method("rename", |_, this, (from, to): (String, String)| {
+            this.rename(&from, &to)
+        });
+        methods.add_method("move", |_, this, (from, to): (String, String)| {
+            this.rename(&from, &to)
+        });
+        methods.add_method("touch", |_, this, path_str: String| {
+            let abs_path = this
+                .open_options(&path_str)
+                .prepare_write()
+                .map_err(|e| e.into_lua_err())?;
+
+            fs_meta::touch(&abs_path)
+                .map_err(|e| LuaError::external(format!("Failed to touch {}: {}", path_str, e)))
+        });
+        methods.add_method(
+            "set_readonly",
+            |_, this, (path_str, readonly): (String, Option<bool>)| {
+                let abs_path = this.checked_path(&path_str, Permissions::WRITE, true)?;
+                fs_meta::set_readonly(&abs_path, readonly.unwrap_or(true)).map_err(|e| {
+                    LuaError::external(format!("Failed to set permissions of {}: {}", path_str, e))
+                })
+            },
+        );
     }
 }
 
@@ -452,6 +847,206 @@ impl FsService {
     fn new(name: String) -> Self {
         Self { name }
     }
+
+    /// 相对路径解析到服务的数据目录中
+    fn full_path(&self, path_str: &str) -> PathBuf {
+        let path = Path::new(path_str);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            service_data_dir(&self.name).join(path)
+        }
+    }
+
+    fn open_options(&self, path_str: &str) -> OpenFileOptions {
+        OpenFileOptions::new(self.full_path(path_str)).with_service(&self.name)
+    }
+
+    /// 检查权限，返回应访问的真实路径。`follow_link` 为 false 时不跟随最后一级链接
+    fn checked_path(
+        &self,
+        path_str: &str,
+        perm: Permissions,
+        follow_link: bool,
+    ) -> LuaResult<PathBuf> {
+        let mut module = FsModule::get_module().lock();
+        let (ok, abs_path) =
+            module.use_access_with(&self.name, self.full_path(path_str), perm, follow_link);
+        if !ok {
+            return Err(LuaError::external(format!(
+                "Access denied to path {}.",
+                abs_path
+            )));
+        }
+        Ok(PathBuf::from(abs_path))
+    }
+
+    /// 同 `checked_path`，但不消耗单次授权，用于只查询元数据的操作
+    fn queried_path(
+        &self,
+        path_str: &str,
+        perm: Permissions,
+        follow_link: bool,
+    ) -> LuaResult<PathBuf> {
+        let module = FsModule::get_module().lock();
+        let (ok, abs_path) =
+            module.is_access_allowed_with(&self.name, self.full_path(path_str), perm, follow_link);
+        if !ok {
+            return Err(LuaError::external(format!(
+                "Access denied to path {}.",
+                abs_path
+            )));
+        }
+        Ok(PathBuf::from(abs_path))
+    }
+
+    fn exists(&self, path_str: &str) -> LuaResult<bool> {
+        let abs_path = self.queried_path(path_str, Permissions::READ, true)?;
+        Ok(abs_path.exists())
+    }
+
+    /// 移动文件或目录。移走源路径同样是修改，源路径需要读写权限
+    fn rename(&self, from: &str, to: &str) -> LuaResult<()> {
+        let from_path = self.checked_path(from, Permissions::READ | Permissions::WRITE, false)?;
+        if std::fs::symlink_metadata(&from_path).is_err() {
+            return Err(LuaError::external(format!("File {} not found.", from)));
+        }
+        let to_path = self.checked_path(to, Permissions::WRITE, false)?;
+        if let Some(parent) = to_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                LuaError::external(format!(
+                    "Failed to create directory {}: {}",
+                    parent.display(),
+                    e
+                ))
+            })?;
+        }
+
+        fs_meta::move_path(&from_path, &to_path)
+            .map_err(|e| LuaError::external(format!("Failed to move {} to {}: {}", from, to, e)))
+    }
+
+    /// 以该服务的权限读取文本文件
+    pub(super) fn read_text_file(&self, path_str: &str) -> LuaResult<String> {
+        let mut file = self
+            .open_options(path_str)
+            .read()
+            .map_err(|e| e.into_lua_err())?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .map_err(|e| LuaError::external(format!("Failed to read file {}: {}", path_str, e)))?;
+
+        Ok(content)
+    }
+
+    /// 以该服务的权限读取二进制文件
+    pub(super) fn read_binary_file(&self, path_str: &str) -> LuaResult<Vec<u8>> {
+        self.read_binary_file_range(path_str, 0, None)
+    }
+
+    /// 从 `offset` 开始读取至多 `length` 字节，未指定长度时读到文件末尾
+    fn read_binary_file_range(
+        &self,
+        path_str: &str,
+        offset: u64,
+        length: Option<u64>,
+    ) -> LuaResult<Vec<u8>> {
+        let mut file = self
+            .open_options(path_str)
+            .read()
+            .map_err(|e| e.into_lua_err())?;
+
+        let read_err = |e: std::io::Error| {
+            LuaError::external(format!("Failed to read file {}: {}", path_str, e))
+        };
+        if offset > 0 {
+            file.seek(SeekFrom::Start(offset)).map_err(read_err)?;
+        }
+        let mut content = vec![];
+        match length {
+            Some(length) => file.take(length).read_to_end(&mut content),
+            None => file.read_to_end(&mut content),
+        }
+        .map_err(read_err)?;
+
+        Ok(content)
+    }
+
+    /// 以该服务的权限原子地写入二进制文件，覆盖原有内容
+    pub(super) fn write_binary_file(&self, path_str: &str, data: &[u8]) -> LuaResult<()> {
+        self.write_binary_file_with(path_str, data, &WriteOptions::default())
+    }
+
+    fn write_binary_file_with(
+        &self,
+        path_str: &str,
+        data: &[u8],
+        options: &WriteOptions,
+    ) -> LuaResult<()> {
+        let backups = options.backups.unwrap_or(0);
+        if backups > fs_atomic::MAX_BACKUPS {
+            return Err(LuaError::external(format!(
+                "Too many backups: {}. At most {} are kept.",
+                backups,
+                fs_atomic::MAX_BACKUPS
+            )));
+        }
+        let abs_path = self
+            .open_options(path_str)
+            .prepare_write()
+            .map_err(|e| e.into_lua_err())?;
+
+        // 备份与临时文件是目标文件的同级文件，由目标的授权覆盖。
+        // 它们只被重命名、删除或独占创建，不会经由链接写到其他位置。
+        fs_atomic::rotate_backups(&abs_path, backups).map_err(|e| {
+            LuaError::external(format!("Failed to back up file {}: {}", path_str, e))
+        })?;
+        self.replace_file(&abs_path, path_str, data, options.atomic.unwrap_or(true))
+    }
+
+    /// 用第 `n` 个备份替换文件内容
+    ///
+    /// 备份由文件的授权覆盖；备份是指向其他位置的链接时，需要其目标的读权限。
+    fn restore_backup(&self, path_str: &str, n: u32) -> LuaResult<()> {
+        let abs_path = self.checked_path(path_str, Permissions::WRITE, true)?;
+        let backup = fs_atomic::backup_path(&abs_path, n);
+        let backup = match util::resolve_path(&backup) {
+            Ok(resolved) if resolved == backup => backup,
+            _ => self.checked_path(&backup.to_string_lossy(), Permissions::READ, true)?,
+        };
+
+        let data = std::fs::read(&backup).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                LuaError::external(format!("Backup {} of {} not found.", n, path_str))
+            } else {
+                LuaError::external(format!(
+                    "Failed to restore backup {} of {}: {}",
+                    n, path_str, e
+                ))
+            }
+        })?;
+        self.replace_file(&abs_path, path_str, &data, true)
+    }
+
+    /// 替换已检查写权限的文件 `abs_path` 的内容，原子写入的临时文件由目标的授权覆盖
+    fn replace_file(
+        &self,
+        abs_path: &Path,
+        path_str: &str,
+        data: &[u8],
+        atomic: bool,
+    ) -> LuaResult<()> {
+        let write_err = |e: std::io::Error| {
+            LuaError::external(format!("Failed to write file {}: {}", path_str, e))
+        };
+        if atomic {
+            return util::write_atomic(abs_path, data).map_err(write_err);
+        }
+        File::create(abs_path)
+            .and_then(|mut file| file.write_all(data))
+            .map_err(write_err)
+    }
 }
 
 struct OpenFileOptions {
@@ -473,29 +1068,59 @@ impl OpenFileOptions {
     }
 
     fn read(self) -> anyhow::Result<File> {
-        let module = FsModule::get_module().lock();
-        let (ok, abs_path) =
-            module.is_access_allowed(&self.service_name, &self.path, Permissions::READ);
+        let mut module = FsModule::get_module().lock();
+        let (ok, abs_path) = module.use_access(&self.service_name, &self.path, Permissions::READ);
         if !ok {
             anyhow::bail!("Access denied to file {}.", abs_path);
         };
 
-        if !self.path.is_file() {
+        if !Path::new(&abs_path).is_file() {
             anyhow::bail!("File {} not found.", self.path.display());
         }
 
-        Ok(File::open(&self.path)?)
+        Ok(File::open(&abs_path)?)
     }
 
     fn create(self) -> anyhow::Result<File> {
-        let module = FsModule::get_module().lock();
-        let (ok, abs_path) =
-            module.is_access_allowed(&self.service_name, &self.path, Permissions::WRITE);
+        let abs_path = self.prepare_write()?;
+        Ok(File::create(abs_path)?)
+    }
+
+    /// 以追加模式打开，文件不存在时创建
+    fn append(self) -> anyhow::Result<File> {
+        let abs_path = self.prepare_write()?;
+        Ok(File::options().create(true).append(true).open(abs_path)?)
+    }
+
+    /// 以读写模式打开已存在的文件
+    fn read_write(self) -> anyhow::Result<File> {
+        let mut module = FsModule::get_module().lock();
+        let (ok, abs_path) = module.use_access(
+            &self.service_name,
+            &self.path,
+            Permissions::READ | Permissions::WRITE,
+        );
         if !ok {
             anyhow::bail!("Access denied to file {}.", abs_path);
         };
 
-        let parent = Path::new(&abs_path).parent().unwrap();
+        if !Path::new(&abs_path).is_file() {
+            anyhow::bail!("File {} not found.", self.path.display());
+        }
+
+        Ok(File::options().read(true).write(true).open(&abs_path)?)
+    }
+
+    /// 检查写入权限，并创建缺失的父目录，返回应写入的真实路径
+    fn prepare_write(&self) -> anyhow::Result<PathBuf> {
+        let mut module = FsModule::get_module().lock();
+        let (ok, abs_path) = module.use_access(&self.service_name, &self.path, Permissions::WRITE);
+        if !ok {
+            anyhow::bail!("Access denied to file {}.", abs_path);
+        };
+
+        let abs_path = PathBuf::from(abs_path);
+        let parent = abs_path.parent().unwrap();
         if !parent.exists() {
             std::fs::create_dir_all(parent).map_err(|e| {
                 LuaError::external(format!(
@@ -506,6 +1131,102 @@ impl OpenFileOptions {
             })?;
         }
 
-        Ok(File::create(&self.path)?)
+        Ok(abs_path)
+    }
+}
+
+/// 二进制数据参数，接受 Lua 字符串或字节列表
+pub(super) struct BinaryData(pub Vec<u8>);
+
+impl FromLua for BinaryData {
+    fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => Ok(Self(s.as_bytes().to_vec())),
+            LuaValue::Table(_) => Ok(Self(Vec::<u8>::from_lua(value, lua)?)),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "BinaryData".to_string(),
+                message: Some("expected string or List<u8>".to_string()),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+
+    use super::*;
+
+    /// 授予服务对单个文件的权限，不写入授权文件
+    fn grant(service: &FsService, path: &Path, perm: Permissions, once: bool) {
+        let pattern = fs_glob::escape(&path.to_string_lossy());
+        FsModule::get_module()
+            .lock()
+            .granted
+            .entry(service.name.clone())
+            .or_default()
+            .accept(Access {
+                once,
+                ..Access::new(pattern, perm)
+            });
+    }
+
+    #[test]
+    fn single_file_grant_covers_backups() {
+        let dir = TempDir::new("single_file_grant");
+        let service = FsService::new("test_single_file_grant".to_string());
+        let path = dir.path("config.json");
+        grant(
+            &service,
+            &path,
+            Permissions::READ | Permissions::WRITE,
+            false,
+        );
+
+        let path_str = path.to_string_lossy();
+        let options = WriteOptions {
+            atomic: None,
+            backups: Some(2),
+        };
+        for content in ["v1", "v2", "v3"] {
+            service
+                .write_binary_file_with(&path_str, content.as_bytes(), &options)
+                .unwrap();
+        }
+        // 原子写入的临时文件已被重命名
+        assert_eq!(
+            dir.entries(),
+            vec!["config.json", "config.json.bak1", "config.json.bak2"]
+        );
+        assert_eq!(dir.read("config.json.bak2"), "v1");
+
+        service.restore_backup(&path_str, 2).unwrap();
+        assert_eq!(dir.read("config.json"), "v1");
+
+        // 同目录的其他文件仍需授权
+        dir.write("other.txt", "other");
+        assert!(
+            service
+                .read_binary_file(&dir.path("other.txt").to_string_lossy())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn queries_keep_once_grant() {
+        let dir = TempDir::new("queries_keep_once_grant");
+        let service = FsService::new("test_queries_keep_once_grant".to_string());
+        dir.write("a.txt", "data");
+        let path = dir.path("a.txt");
+        grant(&service, &path, Permissions::READ, true);
+
+        let path_str = path.to_string_lossy();
+        assert!(service.exists(&path_str).unwrap());
+        assert!(service.exists(&path_str).unwrap());
+        assert_eq!(service.read_binary_file(&path_str).unwrap(), b"data");
+        // 实际读取消耗了单次授权
+        assert!(service.exists(&path_str).is_err());
+        assert!(service.read_binary_file(&path_str).is_err());
     }
 }
@@ -0,0 +1,155 @@
+//! 原子写入与备份轮换
+//!
+//! 先写入同目录下的临时文件并同步到磁盘，再重命名覆盖目标文件。
+//! 写入中途崩溃时目标文件保持原有内容，最多残留一个临时文件。
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// 保留的备份数量上限
+pub(super) const MAX_BACKUPS: u32 = 32;
+
+/// 第 `n` 个备份的路径，如 `config.json.bak1`
+pub(super) fn backup_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak{}", n));
+    path.with_file_name(name)
+}
+
+/// 将现有文件轮换为备份，`bak1` 为最新的备份，超出 `count` 的备份被删除
+///
+/// 目标文件不存在时不做任何事。
+pub(super) fn rotate_backups(path: &Path, count: u32) -> io::Result<()> {
+    if count == 0 || !path.is_file() {
+        return Ok(());
+    }
+    let count = count.min(MAX_BACKUPS);
+
+    // 按链接本身判断是否存在，悬空链接同样被移走，复制时不会经由链接写出
+    let oldest = backup_path(path, count);
+    if oldest.symlink_metadata().is_ok() {
+        std::fs::remove_file(&oldest)?;
+    }
+    for n in (1..count).rev() {
+        let from = backup_path(path, n);
+        if from.symlink_metadata().is_ok() {
+            std::fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+
+    // 复制而不是重命名，目标文件在写入完成前保持可用
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::TempDir, util::write_atomic};
+
+    use super::*;
+
+    #[test]
+    fn backup_paths() {
+        assert_eq!(
+            backup_path(Path::new("dir/config.json"), 1),
+            PathBuf::from("dir/config.json.bak1")
+        );
+        assert_eq!(
+            backup_path(Path::new("save"), 12),
+            PathBuf::from("save.bak12")
+        );
+    }
+
+    #[test]
+    fn atomic_write_replaces_file() {
+        let dir = TempDir::new("atomic_write_replaces_file");
+        let path = dir.path("config.json");
+
+        write_atomic(&path, b"first").unwrap();
+        assert_eq!(dir.read("config.json"), "first");
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(dir.read("config.json"), "second");
+
+        // 不残留临时文件
+        assert_eq!(dir.entries(), vec!["config.json"]);
+    }
+
+    #[test]
+    fn atomic_write_failure_keeps_target() {
+        let dir = TempDir::new("atomic_write_failure_keeps_target");
+        let path = dir.path("config.json");
+        write_atomic(&path, b"original").unwrap();
+
+        // 目标为目录时重命名失败，临时文件被清理
+        std::fs::create_dir(dir.path("folder")).unwrap();
+        assert!(write_atomic(&dir.path("folder"), b"data").is_err());
+        assert_eq!(dir.read("config.json"), "original");
+        assert_eq!(dir.entries(), vec!["config.json", "folder"]);
+    }
+
+    #[test]
+    fn rotate_keeps_newest() {
+        let dir = TempDir::new("rotate_keeps_newest");
+        let path = dir.path("config.json");
+
+        // 文件不存在时没有可备份的内容
+        rotate_backups(&path, 2).unwrap();
+        assert!(dir.entries().is_empty());
+
+        for content in ["v1", "v2", "v3", "v4"] {
+            rotate_backups(&path, 2).unwrap();
+            write_atomic(&path, content.as_bytes()).unwrap();
+        }
+        assert_eq!(
+            dir.entries(),
+            vec!["config.json", "config.json.bak1", "config.json.bak2"]
+        );
+        assert_eq!(dir.read("config.json"), "v4");
+        assert_eq!(dir.read("config.json.bak1"), "v3");
+        assert_eq!(dir.read("config.json.bak2"), "v2");
+    }
+
+    #[test]
+    fn shrinking_backup_count() {
+        let dir = TempDir::new("shrinking_backup_count");
+        let path = dir.path("save");
+        for content in ["v1", "v2", "v3", "v4"] {
+            rotate_backups(&path, 3).unwrap();
+            write_atomic(&path, content.as_bytes()).unwrap();
+        }
+
+        // 数量减少时只轮换保留范围内的备份，超出的旧备份保持不变
+        rotate_backups(&path, 1).unwrap();
+        write_atomic(&path, b"v5").unwrap();
+        assert_eq!(dir.read("save.bak1"), "v4");
+        assert_eq!(dir.read("save.bak2"), "v2");
+        assert_eq!(dir.read("save.bak3"), "v1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rotate_moves_dangling_links() {
+        let dir = TempDir::new("rotate_moves_dangling_links");
+        dir.create_dir("outside");
+        let path = dir.path("save");
+        write_atomic(&path, b"v1").unwrap();
+        dir.symlink("save.bak1", "outside/escaped");
+
+        // 链接本身被轮换，新备份不会经由链接写到目录外
+        rotate_backups(&path, 2).unwrap();
+        assert_eq!(dir.read("save.bak1"), "v1");
+        assert!(
+            dir.path("save.bak2")
+                .symlink_metadata()
+                .unwrap()
+                .is_symlink()
+        );
+        assert!(!dir.path("outside/escaped").exists());
+
+        rotate_backups(&path, 1).unwrap();
+        assert_eq!(dir.read("save.bak1"), "v1");
+        assert!(!dir.path("outside/escaped").exists());
+    }
+}
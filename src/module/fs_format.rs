@@ -0,0 +1,809 @@
+//! 结构化数据文件的解析与序列化
+//!
+//! 各格式统一转换为 `serde_json::Value`，再通过 mlua 的 serde 支持与 Lua 值互相转换。
+//! 整数与浮点数分别保持为 i64 与 f64。
+
+use serde_json::{Map, Number, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Format {
+    Json,
+    Toml,
+    Yaml,
+    Ini,
+    Csv,
+}
+
+impl Format {
+    pub(super) fn parse(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "ini" => Some(Format::Ini),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    /// 按扩展名判断格式
+    pub(super) fn from_path(path: &str) -> Option<Format> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        Format::parse(extension)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Json => "JSON",
+            Format::Toml => "TOML",
+            Format::Yaml => "YAML",
+            Format::Ini => "INI",
+            Format::Csv => "CSV",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub(super) enum FormatError {
+    #[error("{format} syntax error at line {line}, column {column}: {message}")]
+    Syntax {
+        format: &'static str,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("{format} error: {message}")]
+    Unsupported {
+        format: &'static str,
+        message: String,
+    },
+}
+
+impl FormatError {
+    fn unsupported(format: Format, message: impl Into<String>) -> Self {
+        FormatError::Unsupported {
+            format: format.name(),
+            message: message.into(),
+        }
+    }
+}
+
+/// 读写选项
+#[derive(Debug, Clone)]
+pub(super) struct FormatOptions {
+    /// 带缩进的输出，仅 JSON 与 TOML 有效
+    pub pretty: bool,
+    /// CSV 首行为表头，每行读取为以表头为键的表
+    pub header: bool,
+    /// CSV 写入的列及其顺序，默认为所有行的键按字母排序
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            pretty: true,
+            header: true,
+            columns: None,
+        }
+    }
+}
+
+pub(super) fn parse(
+    format: Format,
+    text: &str,
+    options: &FormatOptions,
+) -> Result<Value, FormatError> {
+    match format {
+        Format::Json => serde_json::from_str(text).map_err(|e| {
+            let message = strip_location(&e.to_string(), e.line(), e.column());
+            syntax_error(format, e.line(), e.column(), message)
+        }),
+        Format::Toml => match toml::from_str::<toml::Table>(text) {
+            Ok(table) => Ok(toml_to_json(toml::Value::Table(table))),
+            Err(e) => {
+                let (line, column) = e
+                    .span()
+                    .map_or((1, 1), |span| line_column(text, span.start));
+                Err(syntax_error(
+                    format,
+                    line,
+                    column,
+                    e.message().trim().to_string(),
+                ))
+            }
+        },
+        Format::Yaml => match serde_yaml_ng::from_str::<serde_yaml_ng::Value>(text) {
+            Ok(value) => yaml_to_json(value),
+            Err(e) => {
+                let (line, column) = e
+                    .location()
+                    .map_or((1, 1), |location| (location.line(), location.column()));
+                let message = strip_location(&e.to_string(), line, column);
+                Err(syntax_error(format, line, column, message))
+            }
+        },
+        Format::Ini => parse_ini(text),
+        Format::Csv => parse_csv(text, options.header),
+    }
+}
+
+pub(super) fn serialize(
+    format: Format,
+    value: &Value,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    match format {
+        Format::Json => if options.pretty {
+            serde_json::to_string_pretty(value)
+        } else {
+            serde_json::to_string(value)
+        }
+        .map_err(|e| FormatError::unsupported(format, e.to_string())),
+        Format::Toml => {
+            let toml::Value::Table(table) = json_to_toml(value)? else {
+                return Err(FormatError::unsupported(
+                    format,
+                    "top level value must be a table",
+                ));
+            };
+            if options.pretty {
+                toml::to_string_pretty(&table)
+            } else {
+                toml::to_string(&table)
+            }
+            .map_err(|e| FormatError::unsupported(format, e.to_string()))
+        }
+        Format::Yaml => serde_yaml_ng::to_string(value)
+            .map_err(|e| FormatError::unsupported(format, e.to_string())),
+        Format::Ini => serialize_ini(value),
+        Format::Csv => serialize_csv(value, options),
+    }
+}
+
+fn syntax_error(format: Format, line: usize, column: usize, message: String) -> FormatError {
+    FormatError::Syntax {
+        format: format.name(),
+        line,
+        column,
+        message,
+    }
+}
+
+/// 去除错误信息末尾的位置，位置已单独记录
+fn strip_location(message: &str, line: usize, column: usize) -> String {
+    let suffix = format!(" at line {} column {}", line, column);
+    message.strip_suffix(&suffix).unwrap_or(message).to_string()
+}
+
+/// 字节偏移对应的行列，从 1 开始
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = text.get(..offset).unwrap_or(text);
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+/// 无法表示的浮点数（NaN 与无穷）转为 null
+fn float_value(f: f64) -> Value {
+    Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => float_value(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(array) => Value::Array(array.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn json_to_toml(value: &Value) -> Result<toml::Value, FormatError> {
+    Ok(match value {
+        Value::Null => {
+            return Err(FormatError::unsupported(
+                Format::Toml,
+                "null values are not supported",
+            ));
+        }
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None if n.is_u64() => {
+                return Err(FormatError::unsupported(
+                    Format::Toml,
+                    format!("integer {} is out of range", n),
+                ));
+            }
+            None => toml::Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(array) => {
+            toml::Value::Array(array.iter().map(json_to_toml).collect::<Result<_, _>>()?)
+        }
+        Value::Object(object) => toml::Value::Table(
+            object
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), json_to_toml(v)?)))
+                .collect::<Result<_, FormatError>>()?,
+        ),
+    })
+}
+
+fn yaml_to_json(value: serde_yaml_ng::Value) -> Result<Value, FormatError> {
+    Ok(match value {
+        serde_yaml_ng::Value::Null => Value::Null,
+        serde_yaml_ng::Value::Bool(b) => Value::Bool(b),
+        serde_yaml_ng::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                float_value(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        serde_yaml_ng::Value::String(s) => Value::String(s),
+        serde_yaml_ng::Value::Sequence(seq) => Value::Array(
+            seq.into_iter()
+                .map(yaml_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        serde_yaml_ng::Value::Mapping(mapping) => {
+            let mut object = Map::new();
+            for (k, v) in mapping {
+                // 表的键只能是字符串，数字与布尔值的键转为字符串
+                let key = match k {
+                    serde_yaml_ng::Value::String(s) => s,
+                    serde_yaml_ng::Value::Number(n) => n.to_string(),
+                    serde_yaml_ng::Value::Bool(b) => b.to_string(),
+                    serde_yaml_ng::Value::Null => "null".to_string(),
+                    _ => {
+                        return Err(FormatError::unsupported(
+                            Format::Yaml,
+                            "mapping keys must be scalars",
+                        ));
+                    }
+                };
+                object.insert(key, yaml_to_json(v)?);
+            }
+            Value::Object(object)
+        }
+        serde_yaml_ng::Value::Tagged(tagged) => yaml_to_json(tagged.value)?,
+    })
+}
+
+/// INI 与 CSV 中的值为字符串
+fn scalar_to_string(format: Format, key: &str, value: &Value) -> Result<String, FormatError> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::String(s) => Ok(s.clone()),
+        Value::Array(_) | Value::Object(_) => Err(FormatError::unsupported(
+            format,
+            format!("value of {} must be a string, number or boolean", key),
+        )),
+    }
+}
+
+/// 全局的键位于顶层，各节读取为子表，值均为字符串
+fn parse_ini(text: &str) -> Result<Value, FormatError> {
+    let ini = ini::Ini::load_from_str(text)
+        .map_err(|e| syntax_error(Format::Ini, e.line, e.col, e.msg.to_string()))?;
+
+    let mut root = Map::new();
+    for (section, properties) in &ini {
+        let target = match section {
+            None => &mut root,
+            Some(name) => {
+                let entry = root
+                    .entry(name.to_string())
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if !entry.is_object() {
+                    *entry = Value::Object(Map::new());
+                }
+                entry.as_object_mut().unwrap()
+            }
+        };
+        for (key, value) in properties.iter() {
+            target.insert(key.to_string(), Value::String(value.to_string()));
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+fn serialize_ini(value: &Value) -> Result<String, FormatError> {
+    let Value::Object(root) = value else {
+        return Err(FormatError::unsupported(
+            Format::Ini,
+            "top level value must be a table",
+        ));
+    };
+
+    let mut ini = ini::Ini::new();
+    for (key, value) in root {
+        if !value.is_object() {
+            let value = scalar_to_string(Format::Ini, key, value)?;
+            ini.with_general_section().set(key.as_str(), value);
+        }
+    }
+    for (name, section) in root {
+        let Value::Object(section) = section else {
+            continue;
+        };
+        // 空的节同样写入
+        ini.entry(Some(name.clone()))
+            .or_insert_with(Default::default);
+        for (key, value) in section {
+            let value = scalar_to_string(Format::Ini, &format!("{}.{}", name, key), value)?;
+            ini.with_section(Some(name.as_str()))
+                .set(key.as_str(), value);
+        }
+    }
+
+    let mut buffer = vec![];
+    let option = ini::WriteOption {
+        line_separator: ini::LineSeparator::CR,
+        ..Default::default()
+    };
+    ini.write_to_opt(&mut buffer, option)
+        .map_err(|e| FormatError::unsupported(Format::Ini, e.to_string()))?;
+    String::from_utf8(buffer).map_err(|e| FormatError::unsupported(Format::Ini, e.to_string()))
+}
+
+/// 按 RFC 4180 解析 CSV，返回各行及其起始行号
+///
+/// 字段以 `,` 分隔，含分隔符、引号或换行的字段以 `"` 包裹，`""` 表示引号。跳过空行。
+fn parse_csv_rows(text: &str) -> Result<Vec<(usize, Vec<String>)>, FormatError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let error =
+        |line, column, message: &str| syntax_error(Format::Csv, line, column, message.to_string());
+
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut row_line = 1;
+    // 当前字符的位置
+    let (mut line, mut column) = (1, 0);
+    // 正在读取的引号字段的起始位置
+    let mut quote_start = None;
+    // 引号字段已结束，之后只能是分隔符或换行
+    let mut quote_closed = false;
+    let mut field_started = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        column += 1;
+        if quote_start.is_some() {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    column += 1;
+                    field.push('"');
+                }
+                '"' => {
+                    quote_start = None;
+                    quote_closed = true;
+                }
+                '\n' => {
+                    field.push(c);
+                    line += 1;
+                    column = 0;
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            ',' => {
+                row.push(std::mem::take(&mut field));
+                quote_closed = false;
+                field_started = true;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if field_started || quote_closed || !field.is_empty() {
+                    row.push(std::mem::take(&mut field));
+                    rows.push((row_line, std::mem::take(&mut row)));
+                }
+                quote_closed = false;
+                field_started = false;
+                line += 1;
+                column = 0;
+                row_line = line;
+            }
+            _ if quote_closed => {
+                return Err(error(
+                    line,
+                    column,
+                    "unexpected character after closing quote",
+                ));
+            }
+            '"' if field.is_empty() => {
+                quote_start = Some((line, column));
+                field_started = true;
+            }
+            '"' => return Err(error(line, column, "unexpected quote in unquoted field")),
+            _ => {
+                field.push(c);
+                field_started = true;
+            }
+        }
+    }
+
+    if let Some((start_line, start_column)) = quote_start {
+        return Err(error(start_line, start_column, "unterminated quoted field"));
+    }
+    if field_started || quote_closed || !field.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+fn parse_csv(text: &str, header: bool) -> Result<Value, FormatError> {
+    let rows = parse_csv_rows(text)?;
+    if !header {
+        return Ok(Value::Array(
+            rows.into_iter()
+                .map(|(_, row)| Value::Array(row.into_iter().map(Value::String).collect()))
+                .collect(),
+        ));
+    }
+
+    let mut rows = rows.into_iter();
+    let Some((_, names)) = rows.next() else {
+        return Ok(Value::Array(vec![]));
+    };
+    rows.map(|(line, row)| {
+        if row.len() != names.len() {
+            return Err(syntax_error(
+                Format::Csv,
+                line,
+                1,
+                format!("expected {} fields, found {}", names.len(), row.len()),
+            ));
+        }
+        Ok(Value::Object(
+            names
+                .iter()
+                .cloned()
+                .zip(row.into_iter().map(Value::String))
+                .collect(),
+        ))
+    })
+    .collect::<Result<_, _>>()
+    .map(Value::Array)
+}
+
+fn csv_field(format: Format, key: &str, value: &Value) -> Result<String, FormatError> {
+    let field = scalar_to_string(format, key, value)?;
+    let needs_quote =
+        field.contains([',', '"', '\n', '\r']) || field.starts_with(' ') || field.ends_with(' ');
+    if needs_quote {
+        Ok(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Ok(field)
+    }
+}
+
+/// 以表为元素的列表写入表头与各行，以列表为元素时直接写入各行
+fn serialize_csv(value: &Value, options: &FormatOptions) -> Result<String, FormatError> {
+    let Value::Array(rows) = value else {
+        return Err(FormatError::unsupported(
+            Format::Csv,
+            "top level value must be a list of rows",
+        ));
+    };
+
+    let mut output = String::new();
+    let mut write_row = |fields: Vec<String>| {
+        output.push_str(&fields.join(","));
+        output.push('\n');
+    };
+
+    if rows.iter().all(Value::is_array) {
+        for (i, row) in rows.iter().enumerate() {
+            let fields = row.as_array().unwrap();
+            write_row(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(j, v)| {
+                        csv_field(Format::Csv, &format!("row {} field {}", i + 1, j + 1), v)
+                    })
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        return Ok(output);
+    }
+    if !rows.iter().all(Value::is_object) {
+        return Err(FormatError::unsupported(
+            Format::Csv,
+            "rows must be all lists or all tables",
+        ));
+    }
+
+    let columns = match &options.columns {
+        Some(columns) => columns.clone(),
+        None => {
+            let mut columns = rows
+                .iter()
+                .flat_map(|row| row.as_object().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            columns.sort();
+            columns.dedup();
+            columns
+        }
+    };
+    if options.header {
+        write_row(
+            columns
+                .iter()
+                .map(|c| csv_field(Format::Csv, "header", &Value::String(c.clone())))
+                .collect::<Result<_, _>>()?,
+        );
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let row = row.as_object().unwrap();
+        write_row(
+            columns
+                .iter()
+                .map(|c| {
+                    let value = row.get(c).unwrap_or(&Value::Null);
+                    csv_field(Format::Csv, &format!("row {} column {}", i + 1, c), value)
+                })
+                .collect::<Result<_, _>>()?,
+        );
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn load(format: Format, text: &str) -> Result<Value, FormatError> {
+        parse(format, text, &FormatOptions::default())
+    }
+
+    fn save(format: Format, value: &Value) -> String {
+        serialize(format, value, &FormatOptions::default()).unwrap()
+    }
+
+    fn syntax_position(error: FormatError) -> (usize, usize) {
+        match error {
+            FormatError::Syntax { line, column, .. } => (line, column),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(Format::from_path("cfg/a.JSON"), Some(Format::Json));
+        assert_eq!(Format::from_path("a.yml"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("a.txt"), None);
+        assert_eq!(Format::from_path("json"), None);
+        assert_eq!(Format::parse("TOML"), Some(Format::Toml));
+    }
+
+    #[test]
+    fn json_keeps_number_types() {
+        let value = load(
+            Format::Json,
+            r#"{"i": 1, "f": 1.0, "big": 1e300, "neg": -3}"#,
+        )
+        .unwrap();
+        assert!(value["i"].is_i64());
+        assert!(value["f"].is_f64());
+        assert!(value["neg"].is_i64());
+
+        let compact = serialize(
+            Format::Json,
+            &value,
+            &FormatOptions {
+                pretty: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(compact, r#"{"big":1e+300,"f":1.0,"i":1,"neg":-3}"#);
+        assert!(save(Format::Json, &value).contains("\n  \"f\": 1.0"));
+    }
+
+    #[test]
+    fn json_error_position() {
+        let error = load(Format::Json, "{\n  \"a\": 1,\n  \"b\": ?\n}").unwrap_err();
+        assert_eq!(syntax_position(error.clone()), (3, 8));
+        assert_eq!(
+            error.to_string(),
+            "JSON syntax error at line 3, column 8: expected value"
+        );
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let text = "title = \"x\"\ncount = 3\nratio = 2.0\nwhen = 1979-05-27T07:32:00Z\n\n[server]\nports = [80, 443]\n";
+        let value = load(Format::Toml, text).unwrap();
+        assert_eq!(value["count"], json!(3));
+        assert!(value["ratio"].is_f64());
+        assert_eq!(value["when"], json!("1979-05-27T07:32:00Z"));
+        assert_eq!(value["server"]["ports"], json!([80, 443]));
+
+        let saved = save(Format::Toml, &value);
+        assert!(saved.contains("ratio = 2.0"));
+        assert!(saved.contains("count = 3\n"));
+        assert_eq!(
+            load(Format::Toml, &saved).unwrap()["server"],
+            value["server"]
+        );
+
+        assert!(serialize(Format::Toml, &json!([1]), &FormatOptions::default()).is_err());
+        assert!(serialize(Format::Toml, &json!({"a": null}), &FormatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn toml_error_position() {
+        let error = load(Format::Toml, "a = 1\nb = = 2\n").unwrap_err();
+        assert_eq!(syntax_position(error), (2, 5));
+        let error = load(Format::Toml, "a = 1\na = 2\n").unwrap_err();
+        assert_eq!(syntax_position(error).0, 2);
+    }
+
+    #[test]
+    fn yaml_round_trip() {
+        let text = "name: test\ncount: 3\nratio: 0.5\nwhole: 2.0\n1: one\ntrue: yes\nlist:\n  - a\n  - !tag b\n";
+        let value = load(Format::Yaml, text).unwrap();
+        assert_eq!(value["count"], json!(3));
+        assert!(value["ratio"].is_f64());
+        assert!(value["whole"].is_f64());
+        assert_eq!(value["1"], json!("one"));
+        assert_eq!(value["true"], json!("yes"));
+        assert_eq!(value["list"], json!(["a", "b"]));
+
+        let saved = save(Format::Yaml, &value);
+        assert!(saved.contains("whole: 2.0"));
+        assert_eq!(load(Format::Yaml, &saved).unwrap(), value);
+
+        assert_eq!(load(Format::Yaml, "").unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn yaml_error_position() {
+        let error = load(Format::Yaml, "a: 1\nb: [1, 2\n").unwrap_err();
+        assert_eq!(syntax_position(error).0, 3);
+        let error = load(Format::Yaml, "a: 1\n  b: 2\n").unwrap_err();
+        assert_eq!(syntax_position(error), (2, 4));
+    }
+
+    #[test]
+    fn ini_sections() {
+        let text = "debug = true\n\n[window]\nwidth = 800\ntitle = My Mod\n\n[empty]\n";
+        let value = load(Format::Ini, text).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "debug": "true",
+                "window": {"width": "800", "title": "My Mod"},
+                "empty": {},
+            })
+        );
+
+        let saved = save(
+            Format::Ini,
+            &json!({"debug": true, "scale": 1.5, "window": {"width": 800}, "empty": {}}),
+        );
+        assert!(saved.starts_with("debug=true\nscale=1.5\n"));
+        assert!(saved.contains("[window]\nwidth=800\n"));
+        assert!(saved.contains("[empty]\n"));
+
+        assert!(
+            serialize(
+                Format::Ini,
+                &json!({"a": {"b": {"c": 1}}}),
+                &FormatOptions::default()
+            )
+            .is_err()
+        );
+        assert!(serialize(Format::Ini, &json!({"a": [1]}), &FormatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn ini_errors() {
+        // 未闭合的节名一直读到文件末尾
+        let error = load(Format::Ini, "a = 1\n[section\nb = 2\n").unwrap_err();
+        assert!(matches!(error, FormatError::Syntax { format: "INI", .. }));
+    }
+
+    #[test]
+    fn csv_rows() {
+        let text =
+            "\u{feff}name,note\r\nalice,\"hello, \"\"world\"\"\"\nbob,\"multi\nline\"\n\ncarol,\n";
+        let value = load(Format::Csv, text).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"name": "alice", "note": "hello, \"world\""},
+                {"name": "bob", "note": "multi\nline"},
+                {"name": "carol", "note": ""},
+            ])
+        );
+
+        let options = FormatOptions {
+            header: false,
+            ..Default::default()
+        };
+        let value = parse(Format::Csv, "a,b\n1,\"\"\n,", &options).unwrap();
+        assert_eq!(value, json!([["a", "b"], ["1", ""], ["", ""]]));
+        assert_eq!(load(Format::Csv, "").unwrap(), json!([]));
+        assert_eq!(load(Format::Csv, "a,b\n").unwrap(), json!([]));
+    }
+
+    #[test]
+    fn csv_errors() {
+        let position = |text| syntax_position(load(Format::Csv, text).unwrap_err());
+        assert_eq!(position("a,b\n1,\"2\n3,4\n"), (2, 3));
+        assert_eq!(position("a,b\n1,2\"x\n"), (2, 4));
+        assert_eq!(position("a,b\n\"1\"x,2\n"), (2, 4));
+        assert_eq!(position("a,b\n1,2\n3\n"), (3, 1));
+        // 引号字段中的换行计入行号
+        assert_eq!(position("a,b\n\"x\ny\",2,3\n"), (2, 1));
+        assert_eq!(position("a\n\"x\ny\"z\n"), (3, 3));
+    }
+
+    #[test]
+    fn csv_write() {
+        let rows = json!([
+            {"name": "alice", "score": 1.5, "tags": "a,b"},
+            {"name": " bob", "score": 2, "extra": true},
+        ]);
+        assert_eq!(
+            save(Format::Csv, &rows),
+            "extra,name,score,tags\n,alice,1.5,\"a,b\"\ntrue,\" bob\",2,\n"
+        );
+
+        let options = FormatOptions {
+            columns: Some(vec!["score".to_string(), "name".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            serialize(Format::Csv, &rows, &options).unwrap(),
+            "score,name\n1.5,alice\n2,\" bob\"\n"
+        );
+
+        let lists = json!([["a", "say \"hi\""], [1, null]]);
+        let saved = save(Format::Csv, &lists);
+        assert_eq!(saved, "a,\"say \"\"hi\"\"\"\n1,\n");
+        let options = FormatOptions {
+            header: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse(Format::Csv, &saved, &options).unwrap(),
+            json!([["a", "say \"hi\""], ["1", ""]])
+        );
+
+        assert!(
+            serialize(
+                Format::Csv,
+                &json!([[1], {"a": 1}]),
+                &FormatOptions::default()
+            )
+            .is_err()
+        );
+        assert!(serialize(Format::Csv, &json!([[[1]]]), &FormatOptions::default()).is_err());
+        assert!(serialize(Format::Csv, &json!({"a": 1}), &FormatOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,573 @@
+//! 授权路径的模式匹配
+//!
+//! 支持 `**`（任意层级目录）、`*`、`?`、字符类 `[abc]` `[a-z]` `[!a]` 与 `{a,b}` 选择，
+//! 扩展名过滤可写作 `*.{json,toml}`。Windows 下 `\` 视为分隔符，且不区分大小写。
+
+/// 展开 `{a,b}` 后的最大模式数量
+const MAX_ALTERNATIVES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub(super) enum PatternError {
+    #[error("unclosed '{{' in pattern")]
+    UnclosedBrace,
+    #[error("unclosed '[' in pattern")]
+    UnclosedBracket,
+    #[error("pattern expands to more than {MAX_ALTERNATIVES} alternatives")]
+    TooManyAlternatives,
+}
+
+/// 路径比较规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct MatchOptions {
+    /// 忽略大小写
+    pub case_insensitive: bool,
+    /// `\` 视为路径分隔符
+    pub backslash_separator: bool,
+}
+
+impl MatchOptions {
+    /// 当前平台文件系统的规则
+    pub(super) const fn native() -> Self {
+        Self {
+            case_insensitive: cfg!(windows),
+            backslash_separator: cfg!(windows),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `*`
+    Any,
+    /// `?`
+    One,
+    /// `[...]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(expected) => *expected == c,
+            Token::Any | Token::One => true,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// `**`，匹配零或多级目录
+    Globstar,
+    Tokens(Vec<Token>),
+}
+
+/// 编译后的路径模式
+#[derive(Debug, Clone)]
+pub(super) struct PathPattern {
+    alternatives: Vec<Vec<Segment>>,
+    options: MatchOptions,
+}
+
+impl PathPattern {
+    pub(super) fn new(pattern: &str) -> Result<Self, PatternError> {
+        Self::with_options(pattern, MatchOptions::native())
+    }
+
+    pub(super) fn with_options(pattern: &str, options: MatchOptions) -> Result<Self, PatternError> {
+        let pattern = if options.backslash_separator {
+            pattern.replace('\\', "/")
+        } else {
+            pattern.to_string()
+        };
+
+        let mut expanded = vec![];
+        expand_braces(&pattern.chars().collect::<Vec<_>>(), &mut expanded)?;
+        let alternatives = expanded
+            .iter()
+            .map(|alt| parse_segments(&normalize(alt, options)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            alternatives,
+            options,
+        })
+    }
+
+    pub(super) fn matches(&self, path: &str) -> bool {
+        let path = normalize(path, self.options);
+        let segments = path
+            .split('/')
+            .map(|s| s.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        self.alternatives
+            .iter()
+            .any(|alt| match_segments(alt, &segments))
+    }
+}
+
+/// 转义路径中的模式字符，使其只匹配自身
+pub(super) fn escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if matches!(c, '*' | '?' | '[' | '{' | '}') {
+            escaped.push('[');
+            escaped.push(c);
+            escaped.push(']');
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// 统一分隔符，去除 Windows 扩展路径前缀、重复与末尾的分隔符，按需转为小写
+fn normalize(path: &str, options: MatchOptions) -> String {
+    let mut path = if options.backslash_separator {
+        let path = path.replace('\\', "/");
+        if let Some(rest) = path.strip_prefix("//?/UNC/") {
+            format!("//{}", rest)
+        } else if let Some(rest) = path.strip_prefix("//?/") {
+            rest.to_string()
+        } else {
+            path
+        }
+    } else {
+        path.to_string()
+    };
+
+    // UNC 路径保留开头的 `//`
+    let unc = options.backslash_separator && path.starts_with("//");
+    let mut normalized = String::with_capacity(path.len());
+    if unc {
+        normalized.push('/');
+    }
+    for c in path.chars() {
+        if c == '/' && normalized.ends_with('/') && normalized.len() > usize::from(unc) {
+            continue;
+        }
+        normalized.push(c);
+    }
+    if normalized.len() > 1 && normalized.ends_with('/') {
+        normalized.pop();
+    }
+    path = normalized;
+
+    if options.case_insensitive {
+        path.to_lowercase()
+    } else {
+        path
+    }
+}
+
+/// 从 `[` 的下一个字符开始查找字符类的结尾，返回 `]` 的位置
+///
+/// 紧跟 `[` 或 `[!` 的 `]` 视为普通字符。
+fn class_end(chars: &[char], start: usize) -> Result<usize, PatternError> {
+    let mut i = start;
+    if matches!(chars.get(i), Some('!' | '^')) {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    while i < chars.len() {
+        if chars[i] == ']' {
+            return Ok(i);
+        }
+        i += 1;
+    }
+    Err(PatternError::UnclosedBracket)
+}
+
+/// 展开 `{a,b}`，支持嵌套
+fn expand_braces(chars: &[char], out: &mut Vec<String>) -> Result<(), PatternError> {
+    // 查找第一个不在字符类中的 `{`
+    let mut open = None;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '[' => i = class_end(chars, i + 1)?,
+            '{' => {
+                open = Some(i);
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let Some(open) = open else {
+        if out.len() >= MAX_ALTERNATIVES {
+            return Err(PatternError::TooManyAlternatives);
+        }
+        out.push(chars.iter().collect());
+        return Ok(());
+    };
+
+    // 查找匹配的 `}` 与顶层的 `,`
+    let mut depth = 0;
+    let mut commas = vec![];
+    let mut close = None;
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '[' => i = class_end(chars, i + 1)?,
+            '{' => depth += 1,
+            '}' if depth == 0 => {
+                close = Some(i);
+                break;
+            }
+            '}' => depth -= 1,
+            ',' if depth == 0 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    let close = close.ok_or(PatternError::UnclosedBrace)?;
+
+    let prefix = &chars[..open];
+    let suffix = &chars[close + 1..];
+    let mut start = open + 1;
+    for end in commas.into_iter().chain([close]) {
+        let mut alternative = prefix.to_vec();
+        alternative.extend_from_slice(&chars[start..end]);
+        alternative.extend_from_slice(suffix);
+        expand_braces(&alternative, out)?;
+        start = end + 1;
+    }
+    Ok(())
+}
+
+fn parse_segments(pattern: &str) -> Result<Vec<Segment>, PatternError> {
+    let mut segments: Vec<Segment> = vec![];
+    for segment in pattern.split('/') {
+        if segment == "**" {
+            // 连续的 `**` 等价于一个
+            if segments.last() != Some(&Segment::Globstar) {
+                segments.push(Segment::Globstar);
+            }
+        } else {
+            segments.push(Segment::Tokens(parse_tokens(segment)?));
+        }
+    }
+    Ok(segments)
+}
+
+fn parse_tokens(segment: &str) -> Result<Vec<Token>, PatternError> {
+    let chars = segment.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                // 段内的 `**` 等价于 `*`
+                if tokens.last() != Some(&Token::Any) {
+                    tokens.push(Token::Any);
+                }
+            }
+            '?' => tokens.push(Token::One),
+            '[' => {
+                let end = class_end(&chars, i + 1)?;
+                tokens.push(parse_class(&chars[i + 1..end]));
+                i = end;
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// 解析字符类 `[...]` 的内容
+fn parse_class(body: &[char]) -> Token {
+    let (negated, body) = match body.first() {
+        Some('!' | '^') => (true, &body[1..]),
+        _ => (false, body),
+    };
+    let mut ranges = vec![];
+    let mut i = 0;
+    while i < body.len() {
+        if i + 2 < body.len() && body[i + 1] == '-' {
+            ranges.push((body[i], body[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((body[i], body[i]));
+            i += 1;
+        }
+    }
+    Token::Class { negated, ranges }
+}
+
+fn match_segments(pattern: &[Segment], path: &[Vec<char>]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((Segment::Globstar, rest)) => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((Segment::Tokens(tokens), rest)) => match path.split_first() {
+            Some((segment, path)) => match_tokens(tokens, segment) && match_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+/// 匹配单个路径段，`*` 失配时回溯
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let (mut t, mut s) = (0, 0);
+    // 最近的 `*` 位置及其已匹配到的文本位置
+    let mut star = None;
+    while s < text.len() {
+        match tokens.get(t) {
+            Some(Token::Any) => {
+                star = Some((t, s));
+                t += 1;
+                continue;
+            }
+            Some(token) if token.matches(text[s]) => {
+                t += 1;
+                s += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let Some((star_t, star_s)) = star else {
+            return false;
+        };
+        t = star_t + 1;
+        s = star_s + 1;
+        star = Some((star_t, star_s + 1));
+    }
+    tokens[t..].iter().all(|token| *token == Token::Any)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIX: MatchOptions = MatchOptions {
+        case_insensitive: false,
+        backslash_separator: false,
+    };
+    const WINDOWS: MatchOptions = MatchOptions {
+        case_insensitive: true,
+        backslash_separator: true,
+    };
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        PathPattern::with_options(pattern, UNIX)
+            .unwrap()
+            .matches(path)
+    }
+
+    fn matches_windows(pattern: &str, path: &str) -> bool {
+        PathPattern::with_options(pattern, WINDOWS)
+            .unwrap()
+            .matches(path)
+    }
+
+    #[test]
+    fn literal_paths() {
+        assert!(matches("/home/user/a.txt", "/home/user/a.txt"));
+        assert!(!matches("/home/user/a.txt", "/home/user/b.txt"));
+        assert!(!matches("/home/user/a.txt", "/home/user/a.txt/b"));
+        assert!(!matches("/home/user", "/home/user2"));
+        assert!(matches("C:/game/save.bin", "C:/game/save.bin"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn star_stays_in_segment() {
+        assert!(matches("/saves/*", "/saves/a.bin"));
+        assert!(matches("/saves/*", "/saves/.hidden"));
+        assert!(!matches("/saves/*", "/saves"));
+        assert!(!matches("/saves/*", "/saves/sub/a.bin"));
+        assert!(!matches("/saves/*", "/savesx/a.bin"));
+        assert!(matches("/saves/*.bin", "/saves/slot1.bin"));
+        assert!(matches("/saves/*.bin", "/saves/.bin"));
+        assert!(!matches("/saves/*.bin", "/saves/slot1.bin.bak"));
+        assert!(matches("/saves/slot*.bin", "/saves/slot.bin"));
+        assert!(matches("/saves/*slot*", "/saves/myslot1"));
+        assert!(matches("/a/*/c", "/a/b/c"));
+        assert!(!matches("/a/*/c", "/a/c"));
+        assert!(!matches("/a/*/c", "/a/b/b/c"));
+        // 段内的 `**` 等价于 `*`
+        assert!(matches("/a/b**c", "/a/bxyzc"));
+        assert!(!matches("/a/b**c", "/a/bx/yc"));
+    }
+
+    #[test]
+    fn star_backtracking() {
+        assert!(matches("*a*b*c", "xxaxxbxxbxxc"));
+        assert!(matches("*aab", "aaaab"));
+        assert!(!matches("*aab", "aaaaba"));
+        assert!(matches("a*a*a", "aaa"));
+        assert!(!matches("a*a*a", "aa"));
+        assert!(matches("*.tar.gz", "backup.2024.tar.gz"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(matches("/logs/log?.txt", "/logs/log1.txt"));
+        assert!(!matches("/logs/log?.txt", "/logs/log.txt"));
+        assert!(!matches("/logs/log?.txt", "/logs/log12.txt"));
+        assert!(!matches("/logs/?", "/logs/a/b"));
+        assert!(matches("/logs/??", "/logs/日志"));
+    }
+
+    #[test]
+    fn globstar() {
+        assert!(matches("/game/**", "/game"));
+        assert!(matches("/game/**", "/game/a"));
+        assert!(matches("/game/**", "/game/a/b/c.txt"));
+        assert!(!matches("/game/**", "/gamex/a"));
+        assert!(!matches("/game/**", "/"));
+        assert!(matches("/game/**/save.bin", "/game/save.bin"));
+        assert!(matches("/game/**/save.bin", "/game/a/b/save.bin"));
+        assert!(!matches("/game/**/save.bin", "/game/a/b/save.bin.bak"));
+        assert!(matches("/game/**/saves/**", "/game/x/saves/y/z"));
+        assert!(matches("/game/**/**/a", "/game/a"));
+        assert!(matches("**", "/any/path"));
+        assert!(matches("**", "C:/any/path"));
+        assert!(matches("**/*.lua", "/scripts/deep/mod.lua"));
+        assert!(!matches("**/*.lua", "/scripts/deep/mod.luac"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(matches("/slot[0-9].bin", "/slot3.bin"));
+        assert!(!matches("/slot[0-9].bin", "/slotx.bin"));
+        assert!(matches("/slot[!0-9].bin", "/slotx.bin"));
+        assert!(matches("/slot[^0-9].bin", "/slotx.bin"));
+        assert!(!matches("/slot[!0-9].bin", "/slot3.bin"));
+        assert!(matches("/[abc]", "/b"));
+        assert!(!matches("/[abc]", "/d"));
+        assert!(matches("/[]]", "/]"));
+        assert!(matches("/[!]]", "/a"));
+        assert!(!matches("/[!]]", "/]"));
+        assert!(matches("/a[-]b", "/a-b"));
+        assert!(matches("/[a-]", "/-"));
+        assert!(!matches("/[a-z]", "/A"));
+    }
+
+    #[test]
+    fn brace_alternatives() {
+        assert!(matches("/cfg/*.{json,toml}", "/cfg/a.json"));
+        assert!(matches("/cfg/*.{json,toml}", "/cfg/a.toml"));
+        assert!(!matches("/cfg/*.{json,toml}", "/cfg/a.yaml"));
+        assert!(matches("/{saves,logs}/**", "/logs/a/b"));
+        assert!(!matches("/{saves,logs}/**", "/mods/a"));
+        // 嵌套与空选项
+        assert!(matches("/a{b,c{d,e}}f", "/acef"));
+        assert!(matches("/a{b,c{d,e}}f", "/abf"));
+        assert!(!matches("/a{b,c{d,e}}f", "/acf"));
+        assert!(matches("/log{,.old}", "/log"));
+        assert!(matches("/log{,.old}", "/log.old"));
+        // 选项中可以包含分隔符
+        assert!(matches("/game/{a/b,c}/x", "/game/a/b/x"));
+        assert!(matches("/game/{a/b,c}/x", "/game/c/x"));
+        // 多组选项
+        assert!(matches("/{a,b}/{c,d}", "/b/c"));
+        // 字符类中的 `{` 不是选项
+        assert!(matches("/[{]a,b}", "/{a,b}"));
+        // 未配对的 `}` 视为普通字符
+        assert!(matches("/a}b", "/a}b"));
+    }
+
+    #[test]
+    fn extension_filters() {
+        let pattern = PathPattern::with_options("/data/**/*.{png,jpg,jpeg}", UNIX).unwrap();
+        assert!(pattern.matches("/data/a.png"));
+        assert!(pattern.matches("/data/x/y/b.jpeg"));
+        assert!(!pattern.matches("/data/x/y/b.gif"));
+        assert!(!pattern.matches("/data/x/png"));
+        assert!(!pattern.matches("/data"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert_eq!(
+            PathPattern::with_options("/a/{b,c", UNIX).unwrap_err(),
+            PatternError::UnclosedBrace
+        );
+        assert_eq!(
+            PathPattern::with_options("/a/[bc", UNIX).unwrap_err(),
+            PatternError::UnclosedBracket
+        );
+        assert_eq!(
+            PathPattern::with_options("/a/{[}", UNIX).unwrap_err(),
+            PatternError::UnclosedBracket
+        );
+        let huge = "/{a,b,c,d}".repeat(5);
+        assert_eq!(
+            PathPattern::with_options(&huge, UNIX).unwrap_err(),
+            PatternError::TooManyAlternatives
+        );
+        assert!(PathPattern::with_options(&"/{a,b,c,d}".repeat(4), UNIX).is_ok());
+    }
+
+    #[test]
+    fn separators_and_trailing_slashes() {
+        assert!(matches("/game//saves/", "/game/saves"));
+        assert!(matches("/game/saves", "/game//saves/"));
+        assert!(matches("/game/saves/*", "/game/saves//a"));
+        assert!(matches("/", "/"));
+        assert!(!matches("/", "/a"));
+        // Unix 下 `\` 是文件名中的普通字符
+        assert!(!matches("/game/*", "/game\\a\\b"));
+        assert!(matches("/game/*", "/game/a\\b"));
+    }
+
+    #[test]
+    fn windows_rules() {
+        assert!(matches_windows(
+            "C:/Game/Saves/**",
+            "C:\\Game\\Saves\\a.bin"
+        ));
+        assert!(matches_windows(
+            "C:\\Game\\Saves/**",
+            "C:/Game/Saves/sub/a.bin"
+        ));
+        assert!(matches_windows("C:/Game/Saves/*", "c:\\game\\SAVES\\A.BIN"));
+        assert!(!matches_windows(
+            "C:/Game/Saves/*",
+            "C:\\Game\\Saves\\sub\\a.bin"
+        ));
+        assert!(matches_windows(
+            "C:/Game/*.{JSON,toml}",
+            "C:/game/config.json"
+        ));
+        assert!(matches_windows("C:/Game/[A-C]*", "C:/game/bob"));
+        assert!(matches_windows("C:/Game/**", "\\\\?\\C:\\Game\\a.txt"));
+        assert!(matches_windows(
+            "//server/share/**",
+            "\\\\?\\UNC\\server\\share\\dir\\a.txt"
+        ));
+        assert!(!matches_windows(
+            "/server/share/**",
+            "\\\\server\\share\\a.txt"
+        ));
+        assert!(matches_windows("C:/Game/", "C:\\Game\\"));
+        // 大小写敏感时不匹配
+        assert!(!matches("C:/Game/Saves/*", "c:/game/saves/a.bin"));
+    }
+
+    #[test]
+    fn escape_special_characters() {
+        let dir = "/games/Foo {Deluxe} [v1.0]/saves*?";
+        let pattern = format!("{}/**", escape(dir));
+        assert!(matches(
+            &pattern,
+            "/games/Foo {Deluxe} [v1.0]/saves*?/a.bin"
+        ));
+        assert!(!matches(&pattern, "/games/Foo Deluxe v/saves12/a.bin"));
+        assert!(matches(&escape("/a/b,c"), "/a/b,c"));
+        assert!(matches(&escape("/a/]"), "/a/]"));
+    }
+}
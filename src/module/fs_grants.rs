@@ -0,0 +1,631 @@
+//! 文件系统授权记录及其持久化
+//!
+//! 授权按服务名保存到 eglib 配置文件，单次授权只在当前会话有效，不会保存。
+
+use std::{collections::HashMap, path::Path};
+
+use bitflags::bitflags;
+use mlua::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::util;
+
+use super::fs_glob::PathPattern;
+
+pub(super) const GRANTS_FILE: &str = "reframework/data/eglib/fs_grants.json";
+/// 服务私有数据目录的上级目录
+///
+/// 与授权文件、扫描缓存等内部文件分开，任何服务名都不会对应到这些文件。
+pub(super) const DATA_ROOT: &str = "reframework/data/eglib/services";
+/// 服务数据目录名的最大长度
+const MAX_DIR_NAME: usize = 64;
+/// 匹配任意路径的模式，用于拒绝服务的所有请求
+pub(super) const ANY_PATH: &str = "**";
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(super) struct Permissions: u8 {
+        const READ = 0b01;
+        const WRITE = 0b10;
+    }
+}
+
+impl IntoLua for Permissions {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        self.to_string().into_lua(lua)
+    }
+}
+
+impl std::fmt::Display for Permissions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.contains(Permissions::READ) {
+            f.write_str("r")?;
+        }
+        if self.contains(Permissions::WRITE) {
+            f.write_str("w")?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Permissions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Permissions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Permissions::parse(&s)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid permissions: {}", s)))
+    }
+}
+
+impl Permissions {
+    fn from_char(c: char) -> Option<Permissions> {
+        match c {
+            'r' => Some(Permissions::READ),
+            'w' => Some(Permissions::WRITE),
+            _ => None,
+        }
+    }
+
+    pub(super) fn parse(s: &str) -> Option<Permissions> {
+        let mut perm = Permissions::empty();
+        for c in s.chars() {
+            if let Some(p) = Permissions::from_char(c) {
+                perm |= p;
+            } else {
+                return None;
+            }
+        }
+        Some(perm)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Access {
+    pub path: String,
+    pub permissions: Permissions,
+    /// 过期时间（Unix 毫秒），None 表示不过期
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// 单次授权，使用一次后失效
+    #[serde(skip)]
+    pub once: bool,
+}
+
+impl IntoLua for Access {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let table = lua.create_table()?;
+        table.set("path", self.path.into_lua(lua)?)?;
+        table.set("permissions", self.permissions.into_lua(lua)?)?;
+        table.set("expires_at", self.expires_at)?;
+        table.set("once", self.once)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl Access {
+    pub(super) fn new(path: String, permissions: Permissions) -> Self {
+        Self {
+            path,
+            permissions,
+            expires_at: None,
+            once: false,
+        }
+    }
+
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    fn matches(&self, path: &str, perm: Permissions, now: i64) -> bool {
+        !self.is_expired(now) && self.permissions.contains(perm) && path_matches(&self.path, path)
+    }
+
+    /// 作为拒绝记录时是否拒绝该访问，拒绝任一请求的权限即视为拒绝
+    fn denies(&self, path: &str, perm: Permissions, now: i64) -> bool {
+        !self.is_expired(now) && self.permissions.intersects(perm) && path_matches(&self.path, path)
+    }
+
+    /// 路径与权限均相同
+    fn same_grant(&self, other: &Access) -> bool {
+        self.path == other.path && self.permissions == other.permissions
+    }
+}
+
+/// 检查路径是否匹配授权路径模式，模式语法见 `fs_glob`
+///
+/// 文件夹授权 `dir/*` 同时包含文件夹本身，以便读取目录内容。
+fn path_matches(pattern: &str, target: &str) -> bool {
+    let matches = |pattern: &str| match PathPattern::new(pattern) {
+        Ok(pattern) => pattern.matches(target),
+        Err(e) => {
+            log::warn!("Invalid grant pattern {}: {}", pattern, e);
+            false
+        }
+    };
+    matches(pattern) || pattern.strip_suffix("/*").is_some_and(matches)
+}
+
+/// 单个服务的授权状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct GrantState {
+    #[serde(default)]
+    pub acceptions: Vec<Access>,
+    #[serde(default)]
+    pub rejections: Vec<Access>,
+}
+
+impl IntoLua for GrantState {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let table = lua.create_table()?;
+        table.set("acceptions", self.acceptions.into_lua(lua)?)?;
+        table.set("rejections", self.rejections.into_lua(lua)?)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl GrantState {
+    fn is_empty(&self) -> bool {
+        self.acceptions.is_empty() && self.rejections.is_empty()
+    }
+
+    /// 检查权限，返回匹配的授权在 `acceptions` 中的位置
+    ///
+    /// 拒绝记录只阻止新的请求，不影响已有的授权。
+    pub(super) fn check(&self, path: &str, perm: Permissions, now: i64) -> Option<usize> {
+        self.acceptions
+            .iter()
+            .position(|a| a.matches(path, perm, now))
+    }
+
+    /// 用户是否拒绝了对 `path` 的访问
+    pub(super) fn is_denied(&self, path: &str, perm: Permissions, now: i64) -> bool {
+        self.rejections.iter().any(|r| r.denies(path, perm, now))
+    }
+
+    /// 添加拒绝记录，已有相同记录时忽略
+    pub(super) fn reject(&mut self, access: Access) {
+        if !self.rejections.iter().any(|r| r.same_grant(&access)) {
+            self.rejections.push(access);
+        }
+    }
+
+    /// 添加授权，已有相同授权时更新其期限
+    pub(super) fn accept(&mut self, access: Access) {
+        match self.acceptions.iter_mut().find(|a| a.same_grant(&access)) {
+            // 单次授权不会覆盖已有的授权
+            Some(_) if access.once => {}
+            Some(existing) => *existing = access,
+            None => self.acceptions.push(access),
+        }
+    }
+
+    /// 移除路径为 `path` 的授权，返回是否有授权被移除
+    pub(super) fn revoke(&mut self, path: &str) -> bool {
+        let len = self.acceptions.len();
+        self.acceptions.retain(|a| a.path != path);
+        self.acceptions.len() != len
+    }
+
+    /// 移除路径为 `path` 的拒绝记录，返回是否有记录被移除
+    pub(super) fn remove_rejection(&mut self, path: &str) -> bool {
+        let len = self.rejections.len();
+        self.rejections.retain(|r| r.path != path);
+        self.rejections.len() != len
+    }
+
+    /// 移除过期的记录
+    fn prune(&mut self, now: i64) {
+        self.acceptions.retain(|a| !a.is_expired(now));
+        self.rejections.retain(|r| !r.is_expired(now));
+    }
+
+    /// 需要保存的记录，不含单次授权与已过期的记录
+    fn persisted(&self, now: i64) -> GrantState {
+        let keep = |a: &&Access| !a.once && !a.is_expired(now);
+        GrantState {
+            acceptions: self.acceptions.iter().filter(keep).cloned().collect(),
+            rejections: self.rejections.iter().filter(keep).cloned().collect(),
+        }
+    }
+}
+
+/// 服务私有数据目录的名称
+///
+/// 仅由小写字母、数字、`_` 与 `-` 组成的名称保持不变；其余名称替换其他字符，
+/// 并附加原名称的哈希，使不同的服务名不会对应同一目录。
+pub(super) fn data_dir_name(service_name: &str) -> String {
+    let plain = !service_name.is_empty()
+        && service_name.len() <= MAX_DIR_NAME
+        && service_name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-')
+        && !is_reserved_name(service_name);
+    if plain {
+        return service_name.to_string();
+    }
+
+    let sanitized = service_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_DIR_NAME)
+        .collect::<String>();
+    // `~` 不会出现在保持不变的名称中
+    format!("{}~{:016x}", sanitized, fnv1a(service_name.as_bytes()))
+}
+
+/// Windows 保留的设备名
+fn is_reserved_name(name: &str) -> bool {
+    match name {
+        "con" | "prn" | "aux" | "nul" => true,
+        _ => {
+            let (prefix, digit) = name.split_at(name.len().min(3));
+            matches!(prefix, "com" | "lpt")
+                && digit.len() == 1
+                && digit.as_bytes()[0].is_ascii_digit()
+        }
+    }
+}
+
+/// FNV-1a 哈希，结果不随编译器版本变化
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// 读取保存的授权，文件不存在或无法解析时返回空表
+pub(super) fn load_grants(path: impl AsRef<Path>, now: i64) -> HashMap<String, GrantState> {
+    let path = path.as_ref();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to read fs grants {}: {}", path.display(), e);
+            }
+            return HashMap::new();
+        }
+    };
+    match serde_json::from_str::<HashMap<String, GrantState>>(&content) {
+        Ok(mut granted) => {
+            for state in granted.values_mut() {
+                state.prune(now);
+            }
+            granted
+        }
+        Err(e) => {
+            log::warn!("Failed to parse fs grants {}: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+/// 保存授权，跳过单次授权与已过期的记录
+pub(super) fn save_grants(
+    path: impl AsRef<Path>,
+    granted: &HashMap<String, GrantState>,
+    now: i64,
+) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let persisted = granted
+        .iter()
+        .map(|(name, state)| (name, state.persisted(now)))
+        .filter(|(_, state)| !state.is_empty())
+        .collect::<HashMap<_, _>>();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(&persisted)?;
+    // 写入中途崩溃时保留旧文件，避免授权全部丢失
+    util::write_atomic(path, content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fs_glob::escape;
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn access(path: &str, perm: &str) -> Access {
+        Access::new(path.to_string(), Permissions::parse(perm).unwrap())
+    }
+
+    #[test]
+    fn check_expiry_and_rejections() {
+        let mut state = GrantState::default();
+        state.accept(access("C:/game/saves/**", "rw"));
+        state.accept(Access {
+            expires_at: Some(NOW - 1),
+            ..access("C:/game/mods/*", "r")
+        });
+        state
+            .rejections
+            .push(access("C:/game/saves/secret/**", "r"));
+
+        assert_eq!(
+            state.check("C:/game/saves/a/b.bin", Permissions::WRITE, NOW),
+            Some(0)
+        );
+        assert_eq!(
+            state.check("C:/game/mods/a.lua", Permissions::READ, NOW),
+            None
+        );
+        // 拒绝记录不影响已有的授权
+        assert_eq!(
+            state.check("C:/game/saves/secret/key", Permissions::READ, NOW),
+            Some(0)
+        );
+        assert_eq!(
+            state.check("C:/game/other/key", Permissions::READ, NOW),
+            None
+        );
+    }
+
+    #[test]
+    fn folder_grants() {
+        let mut state = GrantState::default();
+        state.accept(access("C:/game/mods/*", "r"));
+        state.accept(access("C:/game/cfg/*.{json,toml}", "rw"));
+
+        // 非递归的文件夹授权包含文件夹本身及其中的文件
+        assert!(
+            state
+                .check("C:/game/mods", Permissions::READ, NOW)
+                .is_some()
+        );
+        assert!(
+            state
+                .check("C:/game/mods/a.lua", Permissions::READ, NOW)
+                .is_some()
+        );
+        assert!(
+            state
+                .check("C:/game/mods/sub/a.lua", Permissions::READ, NOW)
+                .is_none()
+        );
+        assert!(
+            state
+                .check("C:/game/modsx/a.lua", Permissions::READ, NOW)
+                .is_none()
+        );
+
+        assert!(
+            state
+                .check("C:/game/cfg/a.toml", Permissions::WRITE, NOW)
+                .is_some()
+        );
+        assert!(
+            state
+                .check("C:/game/cfg/a.lua", Permissions::WRITE, NOW)
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_escape() {
+        use crate::{test_util::TempDir, util::resolve_path};
+
+        let root = TempDir::new("grant_links");
+        root.create_dir("granted");
+        root.create_dir("outside");
+        root.write("outside/secret.txt", "secret");
+        root.write("granted/a.txt", "a");
+        root.symlink("granted/link_out", "../outside");
+        root.symlink("granted/link_in", "a.txt");
+
+        let granted = resolve_path(root.path("granted")).unwrap();
+        let mut state = GrantState::default();
+        state.accept(access(
+            &format!("{}/**", escape(&granted.to_string_lossy())),
+            "rw",
+        ));
+        let check = |path: &str| {
+            let resolved = resolve_path(root.path(path)).unwrap();
+            state
+                .check(&resolved.to_string_lossy(), Permissions::READ, NOW)
+                .is_some()
+        };
+
+        assert!(check("granted/a.txt"));
+        assert!(check("granted/link_in"));
+        assert!(check("granted/new/file.txt"));
+        assert!(!check("granted/link_out/secret.txt"));
+        assert!(!check("granted/link_out/new.txt"));
+        assert!(!check("granted/../outside/secret.txt"));
+    }
+
+    #[test]
+    fn deny_requests() {
+        let mut state = GrantState::default();
+        state.reject(access("C:/game/saves/**", "r"));
+        state.reject(access("C:/game/saves/**", "r"));
+        assert_eq!(state.rejections.len(), 1);
+
+        // 请求包含被拒绝的权限
+        assert!(state.is_denied(
+            "C:/game/saves/**",
+            Permissions::READ | Permissions::WRITE,
+            NOW
+        ));
+        assert!(state.is_denied("C:/game/saves/a.bin", Permissions::READ, NOW));
+        assert!(!state.is_denied("C:/game/saves/a.bin", Permissions::WRITE, NOW));
+        assert!(!state.is_denied("C:/game/mods/a.lua", Permissions::READ, NOW));
+
+        // 拒绝任意路径后，已有的授权仍然有效
+        state.accept(access("D:/granted/**", "w"));
+        state.reject(access(ANY_PATH, "w"));
+        assert!(state.is_denied("D:/any/file.txt", Permissions::WRITE, NOW));
+        assert_eq!(
+            state.check("D:/granted/file.txt", Permissions::WRITE, NOW),
+            Some(0)
+        );
+
+        assert!(state.remove_rejection(ANY_PATH));
+        assert!(!state.remove_rejection(ANY_PATH));
+        assert!(!state.is_denied("D:/any/file.txt", Permissions::WRITE, NOW));
+        assert!(state.is_denied("C:/game/saves/a.bin", Permissions::READ, NOW));
+    }
+
+    #[test]
+    fn accept_updates_existing() {
+        let mut state = GrantState::default();
+        state.accept(Access {
+            expires_at: Some(NOW),
+            ..access("C:/a.txt", "r")
+        });
+        state.accept(access("C:/a.txt", "r"));
+        assert_eq!(state.acceptions.len(), 1);
+        assert_eq!(state.acceptions[0].expires_at, None);
+
+        // 单次授权不覆盖长期授权
+        state.accept(Access {
+            once: true,
+            ..access("C:/a.txt", "r")
+        });
+        assert!(!state.acceptions[0].once);
+
+        assert!(state.revoke("C:/a.txt"));
+        assert!(!state.revoke("C:/a.txt"));
+    }
+
+    #[test]
+    fn sanitize_data_dir_names() {
+        assert_eq!(data_dir_name("my_mod"), "my_mod");
+        assert_eq!(data_dir_name("mod-2"), "mod-2");
+
+        let names = [
+            "MyMod",
+            "mymod",
+            "a/b",
+            "a_b",
+            "a\\b",
+            "../evil",
+            "..",
+            ".",
+            "",
+            "con",
+            "COM1",
+            "com1",
+            "com10",
+            "模组",
+            "a b",
+            "fs_grants.json",
+        ];
+        let dirs = names
+            .iter()
+            .map(|name| data_dir_name(name))
+            .collect::<Vec<_>>();
+        for (name, dir) in names.iter().zip(&dirs) {
+            assert!(
+                dir.bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_-~".contains(&b)),
+                "{} -> {}",
+                name,
+                dir
+            );
+        }
+        // 不同的服务名对应不同的目录
+        for (i, a) in dirs.iter().enumerate() {
+            for b in &dirs[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert_eq!(data_dir_name("com10"), "com10");
+        assert!(data_dir_name("MyMod").starts_with("mymod~"));
+        assert!(data_dir_name("../evil").starts_with("___evil~"));
+        assert!(data_dir_name("con").starts_with("con~"));
+        assert!(data_dir_name("com1").starts_with("com1~"));
+
+        let long = "x".repeat(100);
+        assert_eq!(data_dir_name(&long).len(), MAX_DIR_NAME + 17);
+    }
+
+    #[test]
+    fn data_dirs_exclude_internal_files() {
+        use super::super::memory::SCAN_CACHE_DIR;
+
+        let root = util::normalize_path(DATA_ROOT);
+        let internal = [GRANTS_FILE, SCAN_CACHE_DIR].map(util::normalize_path);
+        for path in &internal {
+            assert!(!path.starts_with(&root), "{}", path.display());
+        }
+
+        let names = [
+            "scan_cache",
+            "fs_grants.json",
+            "services",
+            "..",
+            "../scan_cache",
+            "../../eglib",
+            ".",
+            "",
+        ];
+        for name in names {
+            let dir = util::normalize_path(Path::new(DATA_ROOT).join(data_dir_name(name)));
+            assert_eq!(dir.parent(), Some(root.as_path()), "{}", name);
+            for path in &internal {
+                assert!(
+                    !path.starts_with(&dir) && !dir.starts_with(path),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn persist_grants() {
+        let path =
+            std::env::temp_dir().join(format!("eglib_fs_grants_{}.json", std::process::id()));
+        let mut state = GrantState::default();
+        state.accept(access("C:/keep.txt", "rw"));
+        state.accept(Access {
+            once: true,
+            ..access("C:/once.txt", "r")
+        });
+        state.accept(Access {
+            expires_at: Some(NOW + 1000),
+            ..access("C:/later.txt", "r")
+        });
+        state.rejections.push(access("C:/denied.txt", "w"));
+        let mut granted = HashMap::new();
+        granted.insert("my_mod".to_string(), state);
+        granted.insert("empty".to_string(), GrantState::default());
+
+        save_grants(&path, &granted, NOW).unwrap();
+        let loaded = load_grants(&path, NOW);
+        assert_eq!(loaded.len(), 1);
+        let state = &loaded["my_mod"];
+        let paths = state
+            .acceptions
+            .iter()
+            .map(|a| a.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["C:/keep.txt", "C:/later.txt"]);
+        assert_eq!(
+            state.acceptions[0].permissions,
+            Permissions::READ | Permissions::WRITE
+        );
+        assert_eq!(state.rejections.len(), 1);
+
+        // 过期的授权在加载时移除
+        let loaded = load_grants(&path, NOW + 1000);
+        assert_eq!(loaded["my_mod"].acceptions.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_grants(&path, NOW).is_empty());
+    }
+}
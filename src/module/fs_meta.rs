@@ -0,0 +1,228 @@
+//! 文件元数据与文件操作
+
+use std::{
+    fs::{File, Metadata},
+    io,
+    path::Path,
+    time::SystemTime,
+};
+
+use mlua::prelude::*;
+
+/// `stat` 的结果，时间为 Unix 毫秒时间戳
+#[derive(Debug)]
+pub(super) struct FileStat {
+    pub size: u64,
+    pub mtime: Option<i64>,
+    /// 创建时间，文件系统不支持时为 `None`
+    pub ctime: Option<i64>,
+    pub is_dir: bool,
+    pub is_file: bool,
+    pub is_symlink: bool,
+    pub readonly: bool,
+}
+
+impl FileStat {
+    /// `is_symlink` 单独传入，`metadata` 可以是链接目标的元数据
+    pub(super) fn new(metadata: &Metadata, is_symlink: bool) -> Self {
+        Self {
+            size: metadata.len(),
+            mtime: unix_millis(metadata.modified()),
+            ctime: unix_millis(metadata.created()),
+            is_dir: metadata.is_dir(),
+            is_file: metadata.is_file(),
+            is_symlink,
+            readonly: metadata.permissions().readonly(),
+        }
+    }
+}
+
+impl IntoLua for FileStat {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let table = lua.create_table()?;
+        table.set("size", self.size)?;
+        table.set("mtime", self.mtime)?;
+        table.set("ctime", self.ctime)?;
+        table.set("is_dir", self.is_dir)?;
+        table.set("is_file", self.is_file)?;
+        table.set("is_symlink", self.is_symlink)?;
+        table.set("readonly", self.readonly)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+fn unix_millis(time: io::Result<SystemTime>) -> Option<i64> {
+    let duration = time.ok()?.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    i64::try_from(duration.as_millis()).ok()
+}
+
+/// 目录项的类型，链接按其目标分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EntryKind {
+    Dir,
+    File,
+    /// 悬空链接、目标不可访问的链接、管道、设备等
+    Other,
+}
+
+/// 获取目录项的类型
+///
+/// 只有 `follow_link` 对链接返回 `true` 时才读取其目标，否则链接归为 `Other`，
+/// 不透露目标是否存在及其类型。
+pub(super) fn entry_kind(path: &Path, follow_link: impl FnOnce(&Path) -> bool) -> EntryKind {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_symlink() && follow_link(path) => std::fs::metadata(path),
+        Ok(metadata) if metadata.is_symlink() => return EntryKind::Other,
+        result => result,
+    };
+    match metadata {
+        Ok(metadata) if metadata.is_dir() => EntryKind::Dir,
+        Ok(metadata) if metadata.is_file() => EntryKind::File,
+        _ => EntryKind::Other,
+    }
+}
+
+/// 更新文件的修改时间，文件不存在时创建空文件
+pub(super) fn touch(path: &Path) -> io::Result<()> {
+    let file = File::options().create(true).append(true).open(path)?;
+    file.set_modified(SystemTime::now())
+}
+
+/// 设置或清除只读属性
+///
+/// Unix 上清除只读时只恢复所有者的写权限，而不是对所有用户开放写入。
+pub(super) fn set_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if readonly {
+            mode & !0o222
+        } else {
+            mode | 0o200
+        });
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(readonly);
+    std::fs::set_permissions(path, permissions)
+}
+
+/// 移动文件或目录，链接本身被移动
+///
+/// 跨文件系统时无法重命名，文件改为复制后删除原文件，目录返回错误。
+pub(super) fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e)
+            if e.kind() == io::ErrorKind::CrossesDevices
+                && std::fs::symlink_metadata(from)?.is_file() =>
+        {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn stat_file_and_dir() {
+        let dir = TempDir::new("stat_file_and_dir");
+        std::fs::write(dir.path("a.txt"), "hello").unwrap();
+
+        let stat = FileStat::new(&std::fs::metadata(dir.path("a.txt")).unwrap(), false);
+        assert_eq!(stat.size, 5);
+        assert!(stat.is_file && !stat.is_dir && !stat.readonly);
+        assert!(stat.mtime.unwrap() > 0);
+
+        let stat = FileStat::new(&std::fs::metadata(dir.root()).unwrap(), false);
+        assert!(stat.is_dir && !stat.is_file);
+    }
+
+    #[test]
+    fn touch_creates_and_updates() {
+        let dir = TempDir::new("touch_creates_and_updates");
+        let path = dir.path("a.txt");
+
+        touch(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+
+        std::fs::write(&path, "data").unwrap();
+        let past = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+        touch(&path).unwrap();
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert!(metadata.modified().unwrap() > past);
+        // 内容保持不变
+        assert_eq!(std::fs::read(&path).unwrap(), b"data");
+    }
+
+    #[test]
+    fn toggle_readonly() {
+        let dir = TempDir::new("toggle_readonly");
+        let path = dir.path("a.txt");
+        std::fs::write(&path, "data").unwrap();
+
+        set_readonly(&path, true).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().permissions().readonly());
+        set_readonly(&path, false).unwrap();
+        assert!(!std::fs::metadata(&path).unwrap().permissions().readonly());
+        std::fs::write(&path, "written").unwrap();
+    }
+
+    #[test]
+    fn move_files_and_dirs() {
+        let dir = TempDir::new("move_files_and_dirs");
+        std::fs::write(dir.path("a.txt"), "a").unwrap();
+        std::fs::create_dir(dir.path("sub")).unwrap();
+        std::fs::write(dir.path("sub/b.txt"), "b").unwrap();
+
+        move_path(&dir.path("a.txt"), &dir.path("sub/c.txt")).unwrap();
+        move_path(&dir.path("sub"), &dir.path("moved")).unwrap();
+
+        assert!(!dir.path("a.txt").exists() && !dir.path("sub").exists());
+        assert_eq!(std::fs::read(dir.path("moved/c.txt")).unwrap(), b"a");
+        assert_eq!(std::fs::read(dir.path("moved/b.txt")).unwrap(), b"b");
+        assert!(move_path(&dir.path("missing"), &dir.path("other")).is_err());
+    }
+
+    #[test]
+    fn classify_entries() {
+        let dir = TempDir::new("classify_entries");
+        std::fs::write(dir.path("a.txt"), "a").unwrap();
+        std::fs::create_dir(dir.path("sub")).unwrap();
+
+        let follow = |_: &Path| true;
+        assert_eq!(entry_kind(&dir.path("a.txt"), follow), EntryKind::File);
+        assert_eq!(entry_kind(&dir.path("sub"), follow), EntryKind::Dir);
+        assert_eq!(entry_kind(&dir.path("missing"), follow), EntryKind::Other);
+
+        #[cfg(unix)]
+        {
+            dir.symlink("link_dir", "sub");
+            dir.symlink("link_file", "a.txt");
+            dir.symlink("dangling", "missing");
+            assert_eq!(entry_kind(&dir.path("link_dir"), follow), EntryKind::Dir);
+            assert_eq!(entry_kind(&dir.path("link_file"), follow), EntryKind::File);
+            assert_eq!(entry_kind(&dir.path("dangling"), follow), EntryKind::Other);
+
+            // 不允许访问目标时不读取目标
+            let deny = |_: &Path| false;
+            assert_eq!(entry_kind(&dir.path("link_dir"), deny), EntryKind::Other);
+            assert_eq!(entry_kind(&dir.path("link_file"), deny), EntryKind::Other);
+            assert_eq!(entry_kind(&dir.path("sub"), deny), EntryKind::Dir);
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use mlua::prelude::*;
 
 use crate::error::{Error, Result};
-use crate::memory::MemoryUtils;
+use crate::memory::{HexdumpOptions, MemoryUtils, RegionFilter, hexdump, pointer_label};
 use crate::module::LuaModule;
 
+use super::memory;
+
+/// `hexdump` 单次转储的最大字节数
+const MAX_HEXDUMP_LEN: usize = 1 << 20;
+
 /// 指针包装对象，可用于内存读写
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct LuaPtr {
@@ -208,6 +213,91 @@ impl LuaUserData for LuaPtr {
 
             Ok(new_ptr)
         });
+
+        // 内存查看
+        // 十六进制转储，不可读的字节显示为 ??
+        methods.add_method(
+            "hexdump",
+            |lua, this, (len, options): (usize, Option<LuaTable>)| {
+                if len > MAX_HEXDUMP_LEN {
+                    return Err(Error::InvalidValue("len <= 1 MiB", len.to_string()).into_lua_err());
+                }
+                let options = match options {
+                    Some(options) => options,
+                    None => lua.create_table()?,
+                };
+                let cols = options.get::<Option<usize>>("cols")?.unwrap_or(16);
+                if !(1..=64).contains(&cols) {
+                    return Err(
+                        Error::InvalidValue("1 <= cols <= 64", cols.to_string()).into_lua_err()
+                    );
+                }
+                let dump_options = HexdumpOptions {
+                    cols,
+                    ascii: options.get::<Option<bool>>("ascii")?.unwrap_or(true),
+                };
+                let annotate_ptrs = options
+                    .get::<Option<bool>>("annotate_ptrs")?
+                    .unwrap_or(false);
+
+                let address = this.to_usize();
+                let data = MemoryUtils::read_lossy(address, len);
+                if !annotate_ptrs {
+                    return Ok(hexdump(address, &data, &dump_options, |_| None));
+                }
+                let modules = MemoryUtils::modules().into_lua_err()?;
+                let regions = MemoryUtils::regions(&RegionFilter::default())
+                    .into_lua_err()?
+                    .into_iter()
+                    .map(|(region, _)| region)
+                    .collect::<Vec<_>>();
+                Ok(hexdump(address, &data, &dump_options, |value| {
+                    pointer_label(value, &modules, &regions)
+                }))
+            },
+        );
+        // 描述地址所在的区域、模块、节与 RTTI 类
+        methods.add_method("describe", |lua, this, ()| {
+            let address = this.to_usize();
+            let table = lua.create_table()?;
+            table.set("address", *this)?;
+
+            let region = MemoryUtils::region_at(address);
+            let readable = region.as_ref().is_some_and(|r| r.is_readable());
+            table.set("readable", readable)?;
+            table.set("writable", region.as_ref().is_some_and(|r| r.is_writable()))?;
+            table.set(
+                "executable",
+                region.as_ref().is_some_and(|r| r.is_executable()),
+            )?;
+            if let Some(region) = &region {
+                table.set("region", memory::region_to_lua(lua, region)?)?;
+            }
+
+            let modules = MemoryUtils::modules().into_lua_err()?;
+            if let Some(module) = modules.iter().find(|m| m.contains(address)) {
+                let rva = address - module.base;
+                table.set("module", module.name.clone())?;
+                table.set("module_offset", rva)?;
+                let section = MemoryUtils::pin_image(module)
+                    .ok()
+                    .flatten()
+                    .and_then(|pinned| {
+                        pinned
+                            .image()
+                            .sections()
+                            .iter()
+                            .find(|s| s.contains(rva as u32))
+                            .map(|s| s.name())
+                    });
+                table.set("section", section)?;
+            }
+
+            if readable {
+                table.set("rtti", MemoryUtils::rtti_class_name(address))?;
+            }
+            Ok(table)
+        });
     }
 }
 
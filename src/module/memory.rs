@@ -1,23 +1,49 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
 
 use mlua::prelude::*;
+use parking_lot::Mutex;
 
 use crate::error::{Error, Result};
-use crate::memory::MemoryUtils;
+use crate::memory::{
+    FunctionInfo, MemoryError, MemoryRegion, MemoryUtils, ModuleInfo, Pattern, RegionFilter,
+    RuntimeFunction, ScanCache, SignatureDb, SignatureReport, Snapshot, find_module,
+};
+use crate::util;
 
-use super::{LuaModule, luaptr::LuaPtr};
+use super::{
+    EgLib, LuaModule, callback,
+    cstruct::{SharedCTypeRegistry, StructView},
+    fs::FsService,
+    luaptr::LuaPtr,
+    module_handle::ModuleHandle,
+    patch_group::{PatchGroup, PatchGroupEntry, PatchGroupHandle},
+    patch_registry::{self, PatchOwner},
+    promise::{LuaTaskHandle, Promise, PromiseModule},
+    protect::{self, ProtectGuard},
+    snapshot::LuaSnapshot,
+};
 
-struct MemoryPatch {
-    address: usize,
-    size: usize,
-    backup: Vec<u8>,
-}
+pub(super) const SCAN_CACHE_DIR: &str = "reframework/data/eglib/scan_cache";
+/// `snapshot` 单次复制的最大字节数
+const MAX_SNAPSHOT_SIZE: usize = 16 << 20;
+
+/// 进程内所有 Lua 状态共享的扫描缓存，按模块名（小写）区分，首次使用时加载
+///
+/// 文件读写与特征码校验不持有此锁。
+static SCAN_CACHES: LazyLock<Mutex<HashMap<String, ScanCache>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 串行化缓存文件的写入与删除，避免旧的快照覆盖新的内容，须在 `SCAN_CACHES` 之前获取
+static SCAN_CACHE_SAVE: Mutex<()> = Mutex::new(());
 
 pub struct MemoryModule {
-    patches: HashMap<usize, MemoryPatch>,
-    scan_cache: HashMap<String, usize>,
-    module_base: usize,
-    module_size: usize,
+    /// 在补丁注册表中标识本模块写入的补丁
+    owner_state: u64,
+    patch_groups: BTreeMap<String, PatchGroup>,
+    main_module: Option<ModuleInfo>,
+    ctypes: SharedCTypeRegistry,
 }
 
 impl LuaModule for MemoryModule {
@@ -30,18 +56,92 @@ impl LuaModule for MemoryModule {
 impl LuaUserData for MemoryModule {
     fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
         methods.add_method_mut("new_ptr", |_, _, ptr: LuaPtr| Ok(ptr));
-        methods.add_method_mut("patch", |_, this, (ptr, bytes): (LuaPtr, Vec<u8>)| {
-            this.new_patch(ptr.to_usize(), &bytes).into_lua_err()?;
-            Ok(())
-        });
-        methods.add_method_mut("patch_nop", |_, this, (ptr, size): (LuaPtr, usize)| {
-            this.new_patch_nop(ptr.to_usize(), size).into_lua_err()?;
-            Ok(())
-        });
+        methods.add_method_mut(
+            "patch",
+            |lua, this, (ptr, bytes, options): (LuaPtr, Vec<u8>, Option<LuaTable>)| {
+                let owner = this.patch_owner(lua);
+                let overlapped = this
+                    .new_patch(&owner, ptr.to_usize(), &bytes, allow_overlap(&options)?)
+                    .into_lua_err()?;
+                patch_owners_to_lua(lua, &overlapped)
+            },
+        );
+        methods.add_method_mut(
+            "patch_nop",
+            |lua, this, (ptr, size, options): (LuaPtr, usize, Option<LuaTable>)| {
+                let owner = this.patch_owner(lua);
+                let overlapped = this
+                    .new_patch(
+                        &owner,
+                        ptr.to_usize(),
+                        &vec![0x90; size],
+                        allow_overlap(&options)?,
+                    )
+                    .into_lua_err()?;
+                patch_owners_to_lua(lua, &overlapped)
+            },
+        );
         methods.add_method_mut("restore_patch", |_, this, ptr: LuaPtr| {
             let success = this.restore_patch(ptr.to_usize()).into_lua_err()?;
             Ok(success)
         });
+        // 扫描特征码并按替换模式修补，`??` 保留原始字节
+        methods.add_method_mut(
+            "patch_pattern",
+            |lua, this, (find, replace, options): (String, String, Option<LuaTable>)| {
+                let all = match &options {
+                    Some(options) => options.get::<Option<bool>>("all")?.unwrap_or(false),
+                    None => false,
+                };
+                let owner = this.patch_owner(lua);
+                let (addresses, overlapped) = this
+                    .new_patch_pattern(&owner, &find, &replace, all, allow_overlap(&options)?)
+                    .into_lua_err()?;
+                let ptrs = addresses
+                    .into_iter()
+                    .map(|addr| LuaPtr::new(addr as u64))
+                    .collect::<Vec<_>>();
+                Ok((ptrs, patch_owners_to_lua(lua, &overlapped)?))
+            },
+        );
+        // 创建补丁组，默认不启用
+        methods.add_method_mut(
+            "patch_group",
+            |_,
+             this,
+             (name, entries, options): (String, Vec<PatchGroupEntry>, Option<LuaTable>)| {
+                this.new_patch_group(&name, entries, allow_overlap(&options)?)
+                    .into_lua_err()?;
+                Ok(PatchGroupHandle::new(name))
+            },
+        );
+        methods.add_method("list_patch_groups", |lua, this, ()| {
+            let list = lua.create_table()?;
+            for (name, group) in &this.patch_groups {
+                let info = lua.create_table()?;
+                info.set("name", name.as_str())?;
+                info.set("enabled", group.is_enabled())?;
+                info.set("count", group.entries().len())?;
+                list.push(info)?;
+            }
+            Ok(list)
+        });
+        // 列出所有 Lua 状态写入的补丁，用于诊断
+        methods.add_method("list_patches", |lua, _, ()| {
+            let list = lua.create_table()?;
+            for (record, stacked) in patch_registry::list_patches() {
+                let info = lua.create_table()?;
+                info.set("id", record.id)?;
+                info.set("ptr", LuaPtr::new(record.address as u64))?;
+                info.set("size", record.size())?;
+                info.set("state", record.owner.state)?;
+                info.set("script", record.owner.script.as_str())?;
+                info.set("group", record.tag.as_deref())?;
+                info.set("stacked", stacked)?;
+                list.push(info)?;
+            }
+            Ok(list)
+        });
         methods.add_method_mut(
             "scan",
             |_, this, (pattern, offset): (String, Option<isize>)| {
@@ -54,147 +154,724 @@ impl LuaUserData for MemoryModule {
                 Ok(result_ptr)
             },
         );
+        // 后台扫描，返回 Promise<LuaPtr> 与任务句柄
+        methods.add_method_mut(
+            "scan_async",
+            |lua, this, (pattern, offset): (String, Option<isize>)| {
+                let options = PatternScanOptions {
+                    pattern,
+                    offset,
+                    start: None,
+                    length: None,
+                    all_matches: false,
+                };
+                this.pattern_scan_async(lua, &options, true).into_lua_err()
+            },
+        );
+        // 后台扫描，返回 Promise<LuaPtr[]> 与任务句柄
+        methods.add_method_mut(
+            "scan_advanced_async",
+            |lua, this, options: PatternScanOptions| {
+                this.pattern_scan_async(lua, &options, false).into_lua_err()
+            },
+        );
+        // 清空持久化扫描缓存
+        methods.add_method("clear_scan_cache", |_, _, ()| {
+            clear_scan_caches().into_lua_err()
+        });
         methods.add_method_mut("scan_advanced", |_, this, options: PatternScanOptions| {
-            let mut matches = this.pattern_scan_advanced(&options).into_lua_err()?;
-            // apply offset
-            if let Some(offset) = options.offset {
-                matches.iter_mut().for_each(|ptr| {
-                    *ptr = (*ptr as isize + offset) as usize;
-                });
-            }
+            let module = this.main_module().into_lua_err()?;
+            let matches = scan_advanced(&module, &options).into_lua_err()?;
             let matches_ptrs = matches
                 .into_iter()
                 .map(|ptr| LuaPtr::new(ptr as u64))
                 .collect::<Vec<_>>();
             Ok(matches_ptrs)
         });
+        // 解析特征码数据库文件，返回 名称 -> 地址 与失败列表
+        methods.add_method_mut(
+            "load_signatures",
+            |lua, this, (service, path): (LuaUserDataRef<FsService>, String)| {
+                let source = service.read_text_file(&path)?;
+                let report = this.load_signatures(&source).into_lua_err()?;
+
+                let resolved = lua.create_table()?;
+                for (name, address) in report.resolved {
+                    resolved.set(name, LuaPtr::new(address as u64))?;
+                }
+                let failed = lua.create_table()?;
+                for (name, error) in report.failed {
+                    let info = lua.create_table()?;
+                    info.set("name", name)?;
+                    info.set("error", error.to_string())?;
+                    failed.push(info)?;
+                }
+                Ok((resolved, failed))
+            },
+        );
+        // 为地址生成主模块内唯一的特征码
+        methods.add_method_mut(
+            "make_signature",
+            |_, this, (ptr, options): (LuaPtr, Option<LuaTable>)| {
+                let (max_len, wildcard_operands) = match options {
+                    Some(options) => (
+                        options.get::<Option<usize>>("max_len")?.unwrap_or(64),
+                        options
+                            .get::<Option<bool>>("wildcard_operands")?
+                            .unwrap_or(true),
+                    ),
+                    None => (64, true),
+                };
+                this.make_signature(ptr.to_usize(), max_len, wildcard_operands)
+                    .into_lua_err()
+            },
+        );
+        // 根据 .pdata 查找包含地址的函数，不在任何函数内时返回 nil
+        methods.add_method_mut("function_at", |lua, this, ptr: LuaPtr| {
+            let Some(info) = this.function_at(ptr.to_usize()).into_lua_err()? else {
+                return Ok(None);
+            };
+            let base = this.main_module().into_lua_err()?.base;
+            let table = runtime_function_to_lua(lua, base, &info.primary)?;
+            let chain = lua.create_table()?;
+            for entry in &info.chain {
+                chain.push(runtime_function_to_lua(lua, base, entry)?)?;
+            }
+            table.set("chain", chain)?;
+            Ok(Some(table))
+        });
+        // 列出主模块中的所有函数
+        methods.add_method_mut("functions", |lua, this, ()| {
+            let functions = this.functions().into_lua_err()?;
+            let base = this.main_module().into_lua_err()?.base;
+            let list = lua.create_table_with_capacity(functions.len(), 0)?;
+            for entry in &functions {
+                list.push(runtime_function_to_lua(lua, base, entry)?)?;
+            }
+            Ok(list)
+        });
+        // 列出已加载的模块，第一个为主模块
+        methods.add_method("modules", |lua, _, ()| {
+            let modules = MemoryUtils::modules().into_lua_err()?;
+            let list = lua.create_table_with_capacity(modules.len(), 0)?;
+            for module in modules {
+                let info = lua.create_table()?;
+                info.set("name", module.name)?;
+                info.set("path", module.path)?;
+                info.set("base", LuaPtr::new(module.base as u64))?;
+                info.set("size", module.size)?;
+                list.push(info)?;
+            }
+            Ok(list)
+        });
+        // 按名称获取模块句柄，忽略大小写，未加载时返回 nil
+        methods.add_method("module", |_, _, name: String| {
+            let modules = MemoryUtils::modules().into_lua_err()?;
+            Ok(find_module(&modules, &name).cloned().map(ModuleHandle::new))
+        });
+        // 遍历进程地址空间，列出满足条件的内存区域
+        methods.add_method("regions", |lua, _, filter: Option<LuaTable>| {
+            let filter = match filter {
+                Some(filter) => RegionFilter {
+                    committed: filter.get("committed")?,
+                    readable: filter.get("readable")?,
+                    writable: filter.get("writable")?,
+                    executable: filter.get("executable")?,
+                    module: filter.get("module")?,
+                },
+                None => RegionFilter::default(),
+            };
+            let regions = MemoryUtils::regions(&filter).into_lua_err()?;
+
+            let list = lua.create_table_with_capacity(regions.len(), 0)?;
+            for (region, module) in regions {
+                let info = region_to_lua(lua, &region)?;
+                info.set("module", module.map(|m| m.name))?;
+                list.push(info)?;
+            }
+            Ok(list)
+        });
+        // 复制一段内存，用于比较前后变化
+        methods.add_method_mut("snapshot", |_, this, (ptr, size): (LuaPtr, usize)| {
+            if size > MAX_SNAPSHOT_SIZE {
+                return Err(Error::InvalidValue("size <= 16 MiB", size.to_string()).into_lua_err());
+            }
+            let address = ptr.to_usize();
+            // 安全读取会检查范围内的每个内存区域
+            let data = MemoryUtils::read(address, size, true).into_lua_err()?;
+            let module_base = this.main_module().into_lua_err()?.base;
+            Ok(LuaSnapshot::new(Snapshot {
+                address,
+                timestamp: chrono::Utc::now().timestamp_millis(),
+                module_base,
+                data,
+            }))
+        });
+        methods.add_method(
+            "load_snapshot",
+            |_, _, (service, path): (LuaUserDataRef<FsService>, String)| {
+                LuaSnapshot::load(&service, &path)
+            },
+        );
+        // 修改页保护，返回的守卫恢复、被回收或卸载时还原
+        methods.add_method(
+            "protect",
+            |_, this, (ptr, size, flags): (LuaPtr, usize, String)| {
+                ProtectGuard::new(this.owner_state, ptr.to_usize(), size, &flags).into_lua_err()
+            },
+        );
+        // 在修改后的页保护下调用 fn，返回后立即还原
+        methods.add_method(
+            "with_protect",
+            |_, this, (ptr, size, flags, function): (LuaPtr, usize, String, LuaFunction)| {
+                let guard = ProtectGuard::new(this.owner_state, ptr.to_usize(), size, &flags)
+                    .into_lua_err()?;
+                let result = function.call::<LuaMultiValue>(());
+                drop(guard);
+                result
+            },
+        );
+        // 将 Lua 函数包装为原生回调，返回跳板地址
+        methods.add_method(
+            "callback",
+            |lua, _, (function, signature): (LuaFunction, String)| {
+                let address =
+                    callback::create_callback(lua, function, &signature).into_lua_err()?;
+                Ok(LuaPtr::new(address as u64))
+            },
+        );
+        methods.add_method("release_callback", |_, _, ptr: LuaPtr| {
+            Ok(callback::release_callback(ptr.to_usize()))
+        });
+        // 解析 C 结构体定义，返回新定义的类型名
+        methods.add_method("cdef", |_, this, source: String| {
+            let defined = this.ctypes.write().define(&source).map_err(Error::from);
+            defined.into_lua_err()
+        });
+        methods.add_method("sizeof", |_, this, type_name: String| {
+            let size = this.ctypes.read().sizeof(&type_name).map_err(Error::from);
+            size.into_lua_err()
+        });
+        methods.add_method(
+            "offsetof",
+            |_, this, (type_name, field_path): (String, String)| {
+                let offset = this
+                    .ctypes
+                    .read()
+                    .offsetof(&type_name, &field_path)
+                    .map_err(Error::from);
+                offset.into_lua_err()
+            },
+        );
+        // 以结构体类型访问指针指向的内存
+        methods.add_method("cast", |_, this, (ptr, type_name): (LuaPtr, String)| {
+            let layout = this.ctypes.read().record(&type_name).ok_or_else(|| {
+                Error::InvalidValue("defined struct or union name", type_name.clone())
+                    .into_lua_err()
+            })?;
+            Ok(StructView::new(this.ctypes.clone(), layout, ptr.to_usize()))
+        });
     }
 }
 
 impl MemoryModule {
     fn new() -> Self {
         Self {
-            patches: HashMap::new(),
-            scan_cache: HashMap::new(),
-            module_base: 0,
-            module_size: 0,
+            owner_state: patch_registry::new_owner_state(),
+            patch_groups: BTreeMap::new(),
+            main_module: None,
+            ctypes: SharedCTypeRegistry::default(),
         }
     }
 
     fn pattern_scan_first_cached(&mut self, pattern: &str) -> Result<usize> {
-        // use cache
-        if let Some(address) = self.scan_cache.get(pattern).copied() {
-            return Ok(address);
-        }
-
-        // scan and cache
-        let result = MemoryUtils::scan_first(self.module_base, self.module_size, pattern)?;
-        self.scan_cache.insert(pattern.to_string(), result);
-        Ok(result)
+        let module = self.main_module()?;
+        scan_first_cached(&module, pattern)
     }
 
-    fn pattern_scan_advanced(&mut self, options: &PatternScanOptions) -> Result<Vec<usize>> {
-        if self.module_base == 0 || self.module_size == 0 {
-            self.update_module_info()?;
-        }
-        let start_address = options
-            .start
-            .map(|ptr| ptr.to_usize())
-            .unwrap_or(self.module_base);
-        let length = options.length.unwrap_or(self.module_size);
-        let matches = if options.all_matches {
-            MemoryUtils::scan_all(start_address, length, &options.pattern)?
+    /// 在后台线程池中扫描，返回 Promise 与可终止扫描的任务句柄
+    ///
+    /// 结果为单个 LuaPtr（`single`）或 LuaPtr 列表，均已应用偏移。
+    fn pattern_scan_async(
+        &mut self,
+        lua: &Lua,
+        options: &PatternScanOptions,
+        single: bool,
+    ) -> Result<(Promise, LuaTaskHandle)> {
+        // 在调用处校验特征码，便于直接报错
+        options
+            .pattern
+            .parse::<Pattern>()
+            .map_err(MemoryError::from)?;
+
+        // 仅 scan_async 使用缓存
+        let module = self.main_module()?;
+        let cached = if single {
+            lookup_scan_cache(&module, &options.pattern)?
         } else {
-            let match_address = MemoryUtils::scan_first(start_address, length, &options.pattern)?;
-            vec![match_address]
+            None
         };
-        Ok(matches)
+        let (start_address, length) = scan_range(&module, options);
+        let pattern = options.pattern.clone();
+        let all = options.all_matches;
+        let offset = options.offset.unwrap_or(0);
+        let lua_weak = lua.weak();
+
+        let (promise, handle) =
+            PromiseModule::new_promise_async_with_handle(lua, move |resolve, reject| {
+                let pattern = pattern.clone();
+                let module = module.clone();
+                let lua_weak = lua_weak.clone();
+
+                Box::pin(async move {
+                    let result = match cached {
+                        Some(address) => Ok(vec![address]),
+                        None => {
+                            let cancel = Arc::new(AtomicBool::new(false));
+                            // 任务被终止时 future 随之丢弃，通知扫描线程退出
+                            let _guard = CancelOnDrop(cancel.clone());
+                            tokio::task::spawn_blocking(move || {
+                                let result = MemoryUtils::scan_parallel(
+                                    start_address,
+                                    length,
+                                    &pattern,
+                                    all,
+                                    &cancel,
+                                );
+                                if single && let Ok(matches) = &result {
+                                    store_scan_cache(&module, &pattern, matches[0]);
+                                }
+                                result.map_err(|e| e.to_string())
+                            })
+                            .await
+                            .unwrap_or_else(|e| Err(e.to_string()))
+                        }
+                    };
+
+                    let Some(lua) = lua_weak.try_upgrade() else {
+                        return;
+                    };
+                    EgLib::run_with_global_lock(&lua, |_lua| {
+                        let call_result = match result {
+                            Ok(matches) => {
+                                let mut ptrs = matches
+                                    .into_iter()
+                                    .map(|ptr| LuaPtr::new((ptr as isize + offset) as u64));
+                                if single {
+                                    resolve.call::<()>(ptrs.next())
+                                } else {
+                                    resolve.call::<()>(ptrs.collect::<Vec<_>>())
+                                }
+                            }
+                            Err(e) => reject.call::<()>(e),
+                        };
+                        if let Err(e) = call_result {
+                            log::error!("Scan: calling Promise callbacks error: {}", e);
+                        }
+                    });
+                })
+            })?;
+
+        Ok((promise, handle))
     }
 
-    fn update_module_info(&mut self) -> Result<()> {
-        let (base, size) = unsafe { MemoryUtils::get_base_module_space()? };
-        self.module_base = base;
-        self.module_size = size;
-        Ok(())
+    fn load_signatures(&mut self, source: &str) -> Result<SignatureReport> {
+        let db = SignatureDb::from_json(source)?;
+        let module = self.main_module()?;
+        Ok(db.resolve_all(module.base, module.size))
     }
 
-    fn new_patch(&mut self, address: usize, data: &[u8]) -> Result<()> {
-        if self.is_patch_exists(address, data.len()) {
-            return Err(Error::PatchAlreadyExists(address));
+    fn make_signature(
+        &mut self,
+        address: usize,
+        max_len: usize,
+        wildcard_operands: bool,
+    ) -> Result<String> {
+        let module = self.main_module()?;
+        Ok(MemoryUtils::make_signature(
+            module.base,
+            module.size,
+            address,
+            max_len,
+            wildcard_operands,
+        )?)
+    }
+
+    fn function_at(&mut self, address: usize) -> Result<Option<FunctionInfo>> {
+        let module = self.main_module()?;
+        // SAFETY: 主模块在进程生命周期内不会被卸载
+        Ok(unsafe { MemoryUtils::function_at(module.base, module.size, address) }?)
+    }
+
+    fn functions(&mut self) -> Result<Vec<RuntimeFunction>> {
+        let module = self.main_module()?;
+        // SAFETY: 主模块不会被卸载
+        Ok(unsafe { MemoryUtils::functions(module.base, module.size) }?)
+    }
+
+    /// 主模块信息，首次使用时查询
+    fn main_module(&mut self) -> Result<ModuleInfo> {
+        if let Some(module) = &self.main_module {
+            return Ok(module.clone());
         }
+        let module = MemoryUtils::main_module()?;
+        self.main_module = Some(module.clone());
+        Ok(module)
+    }
 
-        let backup = MemoryUtils::patch(address, data)?;
-        self.patches.insert(
-            address,
-            MemoryPatch {
-                address,
-                size: data.len(),
-                backup,
-            },
-        );
+    /// 当前调用者对应的补丁所有者
+    pub(super) fn patch_owner(&self, lua: &Lua) -> PatchOwner {
+        // 第 0 层为当前方法本身，第 1 层为调用它的 Lua 函数
+        let script = lua
+            .inspect_stack(1)
+            .and_then(|debug| debug.source().short_src.map(|s| s.into_owned()))
+            .unwrap_or_else(|| "<unknown>".to_string());
+        PatchOwner {
+            state: self.owner_state,
+            script,
+        }
+    }
 
-        Ok(())
+    /// 写入补丁，返回被重叠补丁的其他所有者
+    fn new_patch(
+        &mut self,
+        owner: &PatchOwner,
+        address: usize,
+        data: &[u8],
+        allow_overlap: bool,
+    ) -> Result<Vec<PatchOwner>> {
+        let (_, overlapped) =
+            patch_registry::apply_patch(owner, address, data, None, allow_overlap)?;
+        Ok(overlapped)
     }
 
-    fn new_patch_nop(&mut self, address: usize, size: usize) -> Result<()> {
-        if self.is_patch_exists(address, size) {
-            return Err(Error::PatchAlreadyExists(address));
+    /// 扫描 `find` 并在每个匹配处写入 `replace`，返回各补丁的起始地址
+    ///
+    /// 替换模式首尾的通配符不计入补丁范围，因此返回地址可能位于匹配地址之后。
+    fn new_patch_pattern(
+        &mut self,
+        owner: &PatchOwner,
+        find: &str,
+        replace: &str,
+        all: bool,
+        allow_overlap: bool,
+    ) -> Result<(Vec<usize>, Vec<PatchOwner>)> {
+        let find_pattern = find.parse::<Pattern>().map_err(MemoryError::from)?;
+        let replace_pattern = replace.parse::<Pattern>().map_err(MemoryError::from)?;
+        if replace_pattern.len() > find_pattern.len() {
+            return Err(Error::InvalidValue(
+                "replace pattern not longer than find pattern",
+                replace.to_string(),
+            ));
         }
+        let span = replace_pattern.fixed_span().ok_or_else(|| {
+            Error::InvalidValue("replace pattern with fixed bytes", replace.to_string())
+        })?;
 
-        let backup = MemoryUtils::patch_repeat(address, 0x90, size)?;
-        self.patches.insert(
-            address,
-            MemoryPatch {
-                address,
-                size,
-                backup,
-            },
-        );
+        let options = PatternScanOptions {
+            pattern: find.to_string(),
+            offset: None,
+            start: None,
+            length: None,
+            all_matches: all,
+        };
+        let module = self.main_module()?;
+        let matches = scan_advanced(&module, &options)?;
 
-        Ok(())
+        // 先检查全部匹配，避免部分写入
+        for &address in &matches {
+            let start = address + span.start;
+            patch_registry::check_overlap(
+                self.owner_state,
+                start..(start + span.len()),
+                allow_overlap,
+            )?;
+        }
+
+        let mut patched = Vec::with_capacity(matches.len());
+        let mut overlapped: Vec<PatchOwner> = vec![];
+        for address in matches {
+            let result = MemoryUtils::read(address, replace_pattern.len(), true)
+                .map_err(Error::from)
+                .and_then(|original| {
+                    let data = replace_pattern.overlay(&original);
+                    self.new_patch(
+                        owner,
+                        address + span.start,
+                        &data[span.clone()],
+                        allow_overlap,
+                    )
+                });
+            let owners = match result {
+                Ok(owners) => owners,
+                Err(e) => {
+                    for &address in patched.iter().rev() {
+                        if let Err(e) = self.restore_patch(address) {
+                            log::error!("Failed to restore patch at 0x{:x}: {}", address, e);
+                        }
+                    }
+                    return Err(e);
+                }
+            };
+            for owner in owners {
+                if !overlapped.contains(&owner) {
+                    overlapped.push(owner);
+                }
+            }
+            patched.push(address + span.start);
+        }
+
+        Ok((patched, overlapped))
     }
 
+    /// 恢复本模块在该地址写入的补丁
     fn restore_patch(&mut self, address: usize) -> Result<bool> {
-        if let Some(patch) = self.patches.remove(&address) {
-            MemoryUtils::patch(patch.address, &patch.backup)?;
-            return Ok(true);
+        match patch_registry::find_patch(self.owner_state, address) {
+            Some(id) => patch_registry::restore_patch(id),
+            None => Ok(false),
         }
-        Ok(false)
     }
 
-    fn is_patch_exists(&mut self, address: usize, size: usize) -> bool {
-        for patch in self.patches.values() {
-            let range1 = patch.address..(patch.address + patch.size);
-            let range2 = address..(address + size);
-            if self.range_overlaps(range1, range2) {
-                return true;
-            }
+    fn new_patch_group(
+        &mut self,
+        name: &str,
+        entries: Vec<PatchGroupEntry>,
+        allow_overlap: bool,
+    ) -> Result<()> {
+        if self.patch_groups.contains_key(name) {
+            return Err(Error::PatchGroupAlreadyExists(name.to_string()));
         }
-        false
+        self.patch_groups
+            .insert(name.to_string(), PatchGroup::new(entries, allow_overlap)?);
+        Ok(())
     }
 
-    fn range_overlaps(
-        &self,
-        range1: std::ops::Range<usize>,
-        range2: std::ops::Range<usize>,
-    ) -> bool {
-        range1.start < range2.end && range2.start < range1.end
+    pub(super) fn enable_patch_group(
+        &mut self,
+        name: &str,
+        owner: &PatchOwner,
+    ) -> Result<Vec<PatchOwner>> {
+        let group = self
+            .patch_groups
+            .get_mut(name)
+            .ok_or_else(|| Error::PatchGroupNotFound(name.to_string()))?;
+        group.enable(owner, name)
+    }
+
+    pub(super) fn disable_patch_group(&mut self, name: &str) -> Result<()> {
+        let group = self
+            .patch_groups
+            .get_mut(name)
+            .ok_or_else(|| Error::PatchGroupNotFound(name.to_string()))?;
+        group.disable()
+    }
+
+    pub(super) fn is_patch_group_enabled(&self, name: &str) -> Result<bool> {
+        let group = self
+            .patch_groups
+            .get(name)
+            .ok_or_else(|| Error::PatchGroupNotFound(name.to_string()))?;
+        Ok(group.is_enabled())
     }
 }
 
 impl Drop for MemoryModule {
     fn drop(&mut self) {
         log::debug!("[DEBUG] MemoryModule dropped");
-        let addresses = self.patches.keys().copied().collect::<Vec<_>>();
-        for addr in addresses {
-            if let Err(e) = self.restore_patch(addr) {
-                log::error!("Failed to restore patch at 0x{:x}: {}", addr, e);
-            };
+        for (name, group) in self.patch_groups.iter_mut() {
+            if let Err(e) = group.disable() {
+                log::error!("Failed to disable patch group '{}': {}", name, e);
+            }
+        }
+        patch_registry::restore_owner_patches(self.owner_state);
+        protect::restore_owner_protections(self.owner_state);
+    }
+}
+
+/// 补丁选项中的 `allow_overlap`，默认不允许与其他脚本的补丁重叠
+fn allow_overlap(options: &Option<LuaTable>) -> LuaResult<bool> {
+    match options {
+        Some(options) => Ok(options
+            .get::<Option<bool>>("allow_overlap")?
+            .unwrap_or(false)),
+        None => Ok(false),
+    }
+}
+
+/// 将补丁所有者列表转换为 `{script, state}` 列表
+pub(super) fn patch_owners_to_lua(lua: &Lua, owners: &[PatchOwner]) -> LuaResult<LuaTable> {
+    let list = lua.create_table_with_capacity(owners.len(), 0)?;
+    for owner in owners {
+        let info = lua.create_table()?;
+        info.set("script", owner.script.as_str())?;
+        info.set("state", owner.state)?;
+        list.push(info)?;
+    }
+    Ok(list)
+}
+
+/// 将内存区域转换为 `{base, size, allocation_base, protect, state, type}`
+pub(super) fn region_to_lua(lua: &Lua, region: &MemoryRegion) -> LuaResult<LuaTable> {
+    let info = lua.create_table()?;
+    info.set("base", LuaPtr::new(region.base as u64))?;
+    info.set("size", region.size)?;
+    info.set(
+        "allocation_base",
+        LuaPtr::new(region.allocation_base as u64),
+    )?;
+    info.set("protect", region.protect)?;
+    info.set("state", region.state_name())?;
+    info.set("type", region.kind_name())?;
+    Ok(info)
+}
+
+/// 将函数条目转换为 `{begin, end, unwind_info}`，地址为绝对地址
+fn runtime_function_to_lua(
+    lua: &Lua,
+    module_base: usize,
+    entry: &RuntimeFunction,
+) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set(
+        "begin",
+        LuaPtr::new((module_base + entry.begin as usize) as u64),
+    )?;
+    table.set(
+        "end",
+        LuaPtr::new((module_base + entry.end as usize) as u64),
+    )?;
+    table.set(
+        "unwind_info",
+        LuaPtr::new((module_base + entry.unwind_info as usize) as u64),
+    )?;
+    Ok(table)
+}
+
+/// 扫描模块，查找第一个匹配，优先使用该模块的扫描缓存
+pub(super) fn scan_first_cached(module: &ModuleInfo, pattern: &str) -> Result<usize> {
+    if let Some(address) = lookup_scan_cache(module, pattern)? {
+        return Ok(address);
+    }
+
+    let result = MemoryUtils::scan_first(module.base, module.size, pattern)?;
+    store_scan_cache(module, pattern, result);
+    Ok(result)
+}
+
+/// 查询模块的共享扫描缓存，命中且与特征码匹配时返回地址
+fn lookup_scan_cache(module: &ModuleInfo, pattern: &str) -> Result<Option<usize>> {
+    let key = module.name.to_ascii_lowercase();
+    let cached = SCAN_CACHES.lock().get(&key).map(|cache| cache.get(pattern));
+    let offset = match cached {
+        Some(offset) => offset,
+        None => {
+            // 在锁外读取缓存文件，其他线程先完成加载时使用已有的缓存
+            let image = MemoryUtils::image_identity(module.base)?;
+            let path = util::normalize_path(format!("{}/{}.json", SCAN_CACHE_DIR, key));
+            let cache = ScanCache::load(path, image);
+            SCAN_CACHES
+                .lock()
+                .entry(key.clone())
+                .or_insert(cache)
+                .get(pattern)
         }
+    };
+
+    let Some(offset) = offset else {
+        return Ok(None);
+    };
+    let address = module.base + offset;
+    if offset < module.size && MemoryUtils::pattern_matches_at(address, pattern).unwrap_or(false) {
+        return Ok(Some(address));
+    }
+    log::debug!(
+        "Stale scan cache entry for pattern '{}' in {}",
+        pattern,
+        module.name
+    );
+    // 校验期间条目可能已被更新，只删除校验过的旧条目
+    if let Some(cache) = SCAN_CACHES.lock().get_mut(&key)
+        && cache.get(pattern) == Some(offset)
+    {
+        cache.remove(pattern);
+    }
+    Ok(None)
+}
+
+/// 写入模块的共享扫描缓存并保存到文件，缓存未加载时忽略
+fn store_scan_cache(module: &ModuleInfo, pattern: &str, address: usize) {
+    let _save = SCAN_CACHE_SAVE.lock();
+    let snapshot = {
+        let mut caches = SCAN_CACHES.lock();
+        let Some(cache) = caches.get_mut(&module.name.to_ascii_lowercase()) else {
+            return;
+        };
+        cache.insert(pattern, address - module.base);
+        cache.snapshot()
+    };
+    if let Err(e) = snapshot
+        .map_err(std::io::Error::from)
+        .and_then(|s| s.write())
+    {
+        log::warn!("Failed to save scan cache: {}", e);
+    }
+}
+
+/// 清空所有模块的扫描缓存并删除缓存文件
+fn clear_scan_caches() -> Result<()> {
+    let _save = SCAN_CACHE_SAVE.lock();
+    let mut caches = SCAN_CACHES.lock();
+    for cache in caches.values_mut() {
+        cache.clear()?;
+    }
+    // 尚未加载的缓存文件直接删除
+    match std::fs::remove_dir_all(util::normalize_path(SCAN_CACHE_DIR)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// 扫描范围，默认为整个模块
+fn scan_range(module: &ModuleInfo, options: &PatternScanOptions) -> (usize, usize) {
+    let start_address = options
+        .start
+        .map(|ptr| ptr.to_usize())
+        .unwrap_or(module.base);
+    let length = options.length.unwrap_or(module.size);
+    (start_address, length)
+}
+
+/// 按选项扫描，返回已应用偏移的地址
+pub(super) fn scan_advanced(
+    module: &ModuleInfo,
+    options: &PatternScanOptions,
+) -> Result<Vec<usize>> {
+    let (start_address, length) = scan_range(module, options);
+    let mut matches = if options.all_matches {
+        MemoryUtils::scan_all(start_address, length, &options.pattern)?
+    } else {
+        let match_address = MemoryUtils::scan_first(start_address, length, &options.pattern)?;
+        vec![match_address]
+    };
+    // apply offset
+    if let Some(offset) = options.offset {
+        matches.iter_mut().for_each(|ptr| {
+            *ptr = (*ptr as isize + offset) as usize;
+        });
+    }
+    Ok(matches)
+}
+
+/// 丢弃时置位取消标志
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
     }
 }
 
-struct PatternScanOptions {
+pub(super) struct PatternScanOptions {
     pattern: String,
     offset: Option<isize>,
     start: Option<LuaPtr>,
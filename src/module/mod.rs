@@ -1,8 +1,21 @@
+mod callback;
+mod cstruct;
+mod file_handle;
 mod fs;
+mod fs_atomic;
+mod fs_format;
+mod fs_glob;
+mod fs_grants;
+mod fs_meta;
 mod http;
 mod luaptr;
 mod memory;
+mod module_handle;
+mod patch_group;
+mod patch_registry;
 mod promise;
+mod protect;
+mod snapshot;
 mod time;
 
 use std::{collections::HashMap, sync::LazyLock};
@@ -14,7 +27,6 @@ use reframework_api_rs::RefAPI;
 
 const LIB_MODULE_NAME: &str = "eglib";
 const LUA_SCRIPT: &str = include_str!("script.lua");
-const KEY_PRIVILEGED: &str = "__privileged";
 
 static PRIVILEGED: LazyLock<Mutex<Privileged>> = LazyLock::new(|| Mutex::new(Privileged::new()));
 
@@ -40,23 +52,24 @@ impl LuaModule for EgLib {
         fs::FsModule::register_library(lua, &core_table)?;
         http::HttpModule::register_library(lua, &core_table)?;
         // privileged instructions
-        core_table.set(KEY_PRIVILEGED, lua.create_userdata(Privileged::new())?)?;
         core_table.set(
             "__get_privileged_key",
             lua.create_function(|_, _: ()| Ok(PRIVILEGED.lock().get_key()))?,
         )?;
         core_table.set(
             "__with_privileged",
-            lua.create_function(|lua, (key, cb): (String, LuaFunction)| {
-                let module = EgLib::get_module(lua)?;
-                let mut privileged = module.get::<LuaUserDataRefMut<Privileged>>(KEY_PRIVILEGED)?;
-                if !privileged.validate_key(&key) {
-                    return Err(LuaError::external("Invalid key"));
+            lua.create_function(|_, (key, cb): (String, LuaFunction)| {
+                {
+                    let mut privileged = PRIVILEGED.lock();
+                    if !privileged.validate_key(&key) {
+                        return Err(LuaError::external("Invalid key"));
+                    }
+                    privileged.privileged = true;
                 }
 
-                privileged.privileged = true;
+                // 回调中的特权函数需要读取状态，调用期间不持有锁
                 let result = cb.call::<()>(());
-                privileged.privileged = false;
+                PRIVILEGED.lock().privileged = false;
 
                 result
             })?,
@@ -98,6 +111,9 @@ impl EgLib {
         let mut state = self.lua_state.lock();
         state.take();
         self.remove_all_task_handles();
+        callback::release_all_callbacks();
+        protect::restore_all_protections();
+        file_handle::close_all_files();
     }
 
     /// 添加一个tokio任务句柄
@@ -136,6 +152,11 @@ impl EgLib {
         f(lua)
     }
 
+    /// 当前是否处于 `__with_privileged` 回调中
+    fn is_privileged() -> bool {
+        PRIVILEGED.lock().privileged
+    }
+
     fn get_module(lua: &Lua) -> LuaResult<LuaTable> {
         let globals = lua.globals();
         globals.get(LIB_MODULE_NAME)
@@ -148,8 +169,6 @@ struct Privileged {
     privileged: bool,
 }
 
-impl LuaUserData for Privileged {}
-
 impl Privileged {
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
                             abcdefghijklmnopqrstuvwxyz\
@@ -188,6 +207,6 @@ impl Privileged {
     }
 
     fn validate_key(&self, key: &str) -> bool {
-        self.key == key
+        !self.key.is_empty() && self.key == key
     }
 }
@@ -0,0 +1,134 @@
+use mlua::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::memory::{MemoryUtils, ModuleInfo, PinnedImage};
+
+use super::{
+    luaptr::LuaPtr,
+    memory::{self, PatternScanOptions},
+};
+
+/// 已加载模块的句柄
+pub struct ModuleHandle {
+    info: ModuleInfo,
+}
+
+impl LuaUserData for ModuleHandle {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.info.name.clone()));
+        fields.add_field_method_get("path", |_, this| Ok(this.info.path.clone()));
+        fields.add_field_method_get("base", |_, this| Ok(LuaPtr::new(this.info.base as u64)));
+        fields.add_field_method_get("size", |_, this| Ok(this.info.size));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method(
+            "scan",
+            |_, this, (pattern, offset): (String, Option<isize>)| {
+                this.ensure_loaded().into_lua_err()?;
+                let result = memory::scan_first_cached(&this.info, &pattern).into_lua_err()?;
+                let result = (result as isize + offset.unwrap_or(0)) as usize;
+                Ok(LuaPtr::new(result as u64))
+            },
+        );
+        methods.add_method("scan_advanced", |_, this, options: PatternScanOptions| {
+            this.ensure_loaded().into_lua_err()?;
+            let matches = memory::scan_advanced(&this.info, &options).into_lua_err()?;
+            Ok(matches
+                .into_iter()
+                .map(|ptr| LuaPtr::new(ptr as u64))
+                .collect::<Vec<_>>())
+        });
+        // PE 头信息与节表
+        methods.add_method("image", |lua, this, ()| {
+            let pinned = this.image().into_lua_err()?;
+            let image = pinned.image();
+            let base = this.info.base;
+
+            let table = lua.create_table()?;
+            table.set("timestamp", image.timestamp())?;
+            table.set("checksum", image.checksum())?;
+            table.set("size_of_image", image.size_of_image())?;
+            table.set(
+                "entry_point",
+                LuaPtr::new((base + image.entry_point() as usize) as u64),
+            )?;
+            let sections = lua.create_table()?;
+            for section in image.sections() {
+                let info = lua.create_table()?;
+                info.set("name", section.name())?;
+                info.set(
+                    "base",
+                    LuaPtr::new((base + section.virtual_address as usize) as u64),
+                )?;
+                info.set("size", section.virtual_size)?;
+                info.set("characteristics", section.characteristics)?;
+                sections.push(info)?;
+            }
+            table.set("sections", sections)?;
+            Ok(table)
+        });
+        // 按名称查找导出函数，转发的导出返回 nil 与转发目标
+        methods.add_method("export", |_, this, name: String| {
+            let pinned = this.image().into_lua_err()?;
+            let image = pinned.image();
+            let Some(export) = image.export(&name).into_lua_err()? else {
+                return Ok((None, None));
+            };
+            if export.forwarder.is_some() {
+                return Ok((None, export.forwarder));
+            }
+            let address = this.info.base + export.rva as usize;
+            Ok((Some(LuaPtr::new(address as u64)), None))
+        });
+        methods.add_method("exports", |lua, this, ()| {
+            let pinned = this.image().into_lua_err()?;
+            let image = pinned.image();
+            let exports = image.exports().into_lua_err()?;
+            let list = lua.create_table_with_capacity(exports.len(), 0)?;
+            for export in exports {
+                let info = lua.create_table()?;
+                info.set("name", export.name)?;
+                if export.forwarder.is_none() {
+                    let address = this.info.base + export.rva as usize;
+                    info.set("ptr", LuaPtr::new(address as u64))?;
+                }
+                info.set("forwarder", export.forwarder)?;
+                list.push(info)?;
+            }
+            Ok(list)
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!(
+                "Module({} @ 0x{:x})",
+                this.info.name, this.info.base
+            ))
+        });
+    }
+}
+
+impl ModuleHandle {
+    pub fn new(info: ModuleInfo) -> Self {
+        Self { info }
+    }
+
+    /// 确认模块仍已加载，避免访问已卸载的内存
+    fn ensure_loaded(&self) -> Result<()> {
+        let loaded = MemoryUtils::modules()?
+            .iter()
+            .any(|m| m.base == self.info.base && m.name == self.info.name);
+        if !loaded {
+            return Err(Error::ModuleNotLoaded(self.info.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// 固定模块并解析其 PE 映像，返回值存活期间模块不会被卸载
+    fn image(&self) -> Result<PinnedImage> {
+        let image = MemoryUtils::pin_image(&self.info)?
+            .ok_or_else(|| Error::ModuleNotLoaded(self.info.name.clone()))?;
+        // 固定后再确认，排除同一地址上加载了其他模块的情况
+        self.ensure_loaded()?;
+        Ok(image)
+    }
+}
@@ -0,0 +1,276 @@
+//! 具名补丁组，可整体启用或禁用
+
+use mlua::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::memory::MemoryUtils;
+
+use super::{
+    EgLib,
+    luaptr::LuaPtr,
+    memory::{self, MemoryModule},
+    patch_registry::{self, PatchOwner},
+};
+
+/// 补丁组中的单个补丁
+pub struct PatchGroupEntry {
+    address: usize,
+    bytes: Vec<u8>,
+    /// 写入前需匹配的原始字节
+    expect: Option<Vec<u8>>,
+    /// 启用后在补丁注册表中的 ID
+    patch_id: Option<u64>,
+}
+
+impl FromLua for PatchGroupEntry {
+    fn from_lua(value: LuaValue, _lua: &Lua) -> LuaResult<Self> {
+        let table = value.as_table().ok_or(LuaError::FromLuaConversionError {
+            from: "table",
+            to: "PatchGroupEntry".to_string(),
+            message: None,
+        })?;
+        // 支持 {ptr, bytes} 与 {ptr = ..., bytes = ...} 两种形式
+        let ptr = match table.get::<Option<LuaPtr>>(1)? {
+            Some(ptr) => ptr,
+            None => table.get::<Option<LuaPtr>>("ptr")?.ok_or_else(|| {
+                LuaError::external("PatchGroupEntry missing field 'ptr' (or [1])")
+            })?,
+        };
+        let bytes = match table.get::<Option<Vec<u8>>>(2)? {
+            Some(bytes) => bytes,
+            None => table.get::<Option<Vec<u8>>>("bytes")?.ok_or_else(|| {
+                LuaError::external("PatchGroupEntry missing field 'bytes' (or [2])")
+            })?,
+        };
+        let expect = table.get::<Option<Vec<u8>>>("expect")?;
+        if bytes.is_empty() {
+            return Err(LuaError::external("PatchGroupEntry 'bytes' is empty"));
+        }
+        if ptr.to_usize().checked_add(bytes.len()).is_none() {
+            return Err(LuaError::external(
+                "PatchGroupEntry 'bytes' extends past the end of the address space",
+            ));
+        }
+        if let Some(expect) = &expect
+            && expect.len() != bytes.len()
+        {
+            return Err(LuaError::external(
+                "PatchGroupEntry 'expect' must have the same length as 'bytes'",
+            ));
+        }
+
+        Ok(PatchGroupEntry {
+            address: ptr.to_usize(),
+            bytes,
+            expect,
+            patch_id: None,
+        })
+    }
+}
+
+impl PatchGroupEntry {
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.address..(self.address + self.bytes.len())
+    }
+}
+
+/// 补丁组
+pub struct PatchGroup {
+    entries: Vec<PatchGroupEntry>,
+    enabled: bool,
+    /// 是否允许与其他脚本的补丁重叠
+    allow_overlap: bool,
+}
+
+impl PatchGroup {
+    /// 创建补丁组，组内补丁的范围不可重叠，否则禁用时无法恢复正确的原始字节
+    pub fn new(entries: Vec<PatchGroupEntry>, allow_overlap: bool) -> Result<Self> {
+        let mut ranges = entries.iter().map(|e| e.range()).collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+        if let Some(pair) = ranges.windows(2).find(|pair| pair[0].end > pair[1].start) {
+            return Err(Error::PatchGroupOverlap(pair[0].start, pair[1].start));
+        }
+
+        Ok(Self {
+            entries,
+            enabled: false,
+            allow_overlap,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn entries(&self) -> &[PatchGroupEntry] {
+        &self.entries
+    }
+
+    /// 校验所有补丁的原始字节
+    pub fn verify(&self) -> Result<()> {
+        for entry in &self.entries {
+            let Some(expect) = &entry.expect else {
+                continue;
+            };
+            let current = MemoryUtils::read(entry.address, expect.len(), true)?;
+            if &current != expect {
+                return Err(Error::PatchVerifyFailed(
+                    entry.address,
+                    format_bytes(expect),
+                    format_bytes(&current),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入所有补丁，任一失败时回滚已写入的补丁，返回被重叠补丁的其他所有者
+    pub fn enable(&mut self, owner: &PatchOwner, name: &str) -> Result<Vec<PatchOwner>> {
+        if self.enabled {
+            return Ok(vec![]);
+        }
+        for entry in &self.entries {
+            patch_registry::check_overlap(owner.state, entry.range(), self.allow_overlap)?;
+        }
+        self.verify()?;
+
+        let mut overlapped: Vec<PatchOwner> = vec![];
+        for i in 0..self.entries.len() {
+            let entry = &mut self.entries[i];
+            let result = patch_registry::apply_patch(
+                owner,
+                entry.address,
+                &entry.bytes,
+                Some(name),
+                self.allow_overlap,
+            );
+            match result {
+                Ok((id, owners)) => {
+                    entry.patch_id = Some(id);
+                    for owner in owners {
+                        if !overlapped.contains(&owner) {
+                            overlapped.push(owner);
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.rollback();
+                    return Err(e);
+                }
+            }
+        }
+
+        self.enabled = true;
+        Ok(overlapped)
+    }
+
+    /// 恢复所有补丁的原始字节
+    pub fn disable(&mut self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.rollback();
+        self.enabled = false;
+        Ok(())
+    }
+
+    /// 逆序恢复已写入的补丁
+    fn rollback(&mut self) {
+        for entry in self.entries.iter_mut().rev() {
+            let Some(id) = entry.patch_id.take() else {
+                continue;
+            };
+            if let Err(e) = patch_registry::restore_patch(id) {
+                log::error!("Failed to restore patch at 0x{:x}: {}", entry.address, e);
+            }
+        }
+    }
+}
+
+/// Lua 侧的补丁组句柄，操作时从 MemoryModule 中查找对应补丁组
+pub struct PatchGroupHandle {
+    name: String,
+}
+
+impl LuaUserData for PatchGroupHandle {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("enable", |lua, this, ()| {
+            let mut memory = memory_module(lua)?;
+            let owner = memory.patch_owner(lua);
+            let overlapped = memory
+                .enable_patch_group(&this.name, &owner)
+                .into_lua_err()?;
+            memory::patch_owners_to_lua(lua, &overlapped)
+        });
+        methods.add_method("disable", |lua, this, ()| {
+            let mut memory = memory_module(lua)?;
+            memory.disable_patch_group(&this.name).into_lua_err()
+        });
+        methods.add_method("is_enabled", |lua, this, ()| {
+            let memory = memory_module(lua)?;
+            memory.is_patch_group_enabled(&this.name).into_lua_err()
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("PatchGroup({})", this.name))
+        });
+    }
+}
+
+impl PatchGroupHandle {
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+}
+
+fn memory_module(lua: &Lua) -> LuaResult<LuaUserDataRefMut<MemoryModule>> {
+    EgLib::get_module(lua)?.get::<LuaUserDataRefMut<MemoryModule>>("memory")
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(address: usize, len: usize) -> PatchGroupEntry {
+        PatchGroupEntry {
+            address,
+            bytes: vec![0x90; len],
+            expect: None,
+            patch_id: None,
+        }
+    }
+
+    #[test]
+    fn test_reject_overlapping_entries() {
+        assert!(
+            PatchGroup::new(
+                vec![entry(0x1000, 4), entry(0x1004, 2), entry(0x0ff0, 16)],
+                false
+            )
+            .is_ok()
+        );
+
+        let result = PatchGroup::new(
+            vec![entry(0x1000, 4), entry(0x2000, 1), entry(0x1003, 2)],
+            false,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::PatchGroupOverlap(0x1000, 0x1003))
+        ));
+
+        // 相同地址
+        assert!(PatchGroup::new(vec![entry(0x1000, 1), entry(0x1000, 1)], false).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+//! 进程级补丁注册表
+//!
+//! 每个 Lua 状态各自持有一个 `MemoryModule`，补丁统一登记于此，
+//! 以便跨状态、跨脚本检测重叠，并按写入顺序正确恢复。
+
+use std::ops::Range;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::memory::MemoryUtils;
+
+static PATCH_REGISTRY: LazyLock<Mutex<PatchRegistry>> = LazyLock::new(Default::default);
+static NEXT_OWNER_STATE: AtomicU64 = AtomicU64::new(1);
+
+/// 分配新的补丁所有者状态 ID，每个 `MemoryModule` 一个
+pub fn new_owner_state() -> u64 {
+    NEXT_OWNER_STATE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 补丁所有者
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchOwner {
+    /// 所属 `MemoryModule` 的状态 ID
+    pub state: u64,
+    /// 发起修补的脚本
+    pub script: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchRecord {
+    pub id: u64,
+    pub address: usize,
+    /// 写入前的字节，被更新的补丁覆盖时会随之调整
+    backup: Vec<u8>,
+    pub owner: PatchOwner,
+    /// 补丁来源标记，如补丁组名称
+    pub tag: Option<String>,
+}
+
+impl PatchRecord {
+    pub fn size(&self) -> usize {
+        self.backup.len()
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.address..(self.address + self.backup.len())
+    }
+}
+
+#[derive(Default)]
+pub struct PatchRegistry {
+    /// 按写入顺序排列
+    records: Vec<PatchRecord>,
+    next_id: u64,
+}
+
+impl PatchRegistry {
+    pub fn records(&self) -> &[PatchRecord] {
+        &self.records
+    }
+
+    /// 与指定范围重叠的补丁
+    pub fn overlapping(&self, range: Range<usize>) -> impl Iterator<Item = &PatchRecord> {
+        self.records
+            .iter()
+            .filter(move |r| range_overlaps(&r.range(), &range))
+    }
+
+    /// 检查新补丁与已有补丁的重叠，返回重叠补丁的其他所有者
+    ///
+    /// 与同一所有者状态的补丁重叠总是错误；与其他状态的重叠仅在 `allow_overlap` 时允许。
+    pub fn check_overlap(
+        &self,
+        state: u64,
+        range: Range<usize>,
+        allow_overlap: bool,
+    ) -> Result<Vec<PatchOwner>> {
+        let mut owners: Vec<PatchOwner> = vec![];
+        for other in self.overlapping(range.clone()) {
+            if other.owner.state == state {
+                return Err(Error::PatchAlreadyExists(range.start));
+            }
+            if !owners.contains(&other.owner) {
+                owners.push(other.owner.clone());
+            }
+        }
+        if !owners.is_empty() && !allow_overlap {
+            let names = owners
+                .iter()
+                .map(|o| format!("'{}' (state {})", o.script, o.state))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Error::PatchOverlap(range.start, names));
+        }
+        Ok(owners)
+    }
+
+    pub fn find(&self, state: u64, address: usize) -> Option<&PatchRecord> {
+        self.records
+            .iter()
+            .find(|r| r.owner.state == state && r.address == address)
+    }
+
+    fn insert(
+        &mut self,
+        address: usize,
+        backup: Vec<u8>,
+        owner: PatchOwner,
+        tag: Option<String>,
+    ) -> u64 {
+        self.next_id += 1;
+        self.records.push(PatchRecord {
+            id: self.next_id,
+            address,
+            backup,
+            owner,
+            tag,
+        });
+        self.next_id
+    }
+
+    /// 移除补丁记录，返回需要写回内存的字节
+    ///
+    /// 被更新补丁覆盖的字节不直接写回，而是交给其上方最近的补丁作为备份，
+    /// 这样无论恢复顺序如何，最终都能还原为最初的字节。
+    fn remove(&mut self, id: u64) -> Option<Vec<(usize, Vec<u8>)>> {
+        let index = self.records.iter().position(|r| r.id == id)?;
+        let record = self.records.remove(index);
+
+        let mut writes: Vec<(usize, Vec<u8>)> = Vec::new();
+        for (offset, &byte) in record.backup.iter().enumerate() {
+            let address = record.address + offset;
+            // 移除后，index 之后的记录均比该补丁更新
+            let above = self.records[index..]
+                .iter_mut()
+                .find(|r| r.range().contains(&address));
+            match above {
+                Some(above) => above.backup[address - above.address] = byte,
+                None => match writes.last_mut() {
+                    Some((start, bytes)) if *start + bytes.len() == address => bytes.push(byte),
+                    _ => writes.push((address, vec![byte])),
+                },
+            }
+        }
+        Some(writes)
+    }
+}
+
+/// 写入补丁并登记，返回补丁 ID 与被重叠补丁的所有者
+///
+/// 同一所有者状态内的重叠视为错误；不同状态间的重叠需显式允许，之后按栈顺序恢复。
+pub fn apply_patch(
+    owner: &PatchOwner,
+    address: usize,
+    data: &[u8],
+    tag: Option<&str>,
+    allow_overlap: bool,
+) -> Result<(u64, Vec<PatchOwner>)> {
+    let mut registry = PATCH_REGISTRY.lock();
+    let range = address..(address + data.len());
+    let overlapped = registry.check_overlap(owner.state, range, allow_overlap)?;
+    for other in &overlapped {
+        log::warn!(
+            "Patch at 0x{:x} by '{}' overlaps patches by '{}' (state {})",
+            address,
+            owner.script,
+            other.script,
+            other.state
+        );
+    }
+
+    let backup = MemoryUtils::patch(address, data)?;
+    let id = registry.insert(address, backup, owner.clone(), tag.map(str::to_string));
+    Ok((id, overlapped))
+}
+
+/// 恢复补丁，补丁不存在时返回 false
+pub fn restore_patch(id: u64) -> Result<bool> {
+    let mut registry = PATCH_REGISTRY.lock();
+    let Some(writes) = registry.remove(id) else {
+        return Ok(false);
+    };
+    for (address, bytes) in writes {
+        MemoryUtils::patch(address, &bytes)?;
+    }
+    Ok(true)
+}
+
+/// 查找指定状态在该地址处写入的补丁
+pub fn find_patch(state: u64, address: usize) -> Option<u64> {
+    PATCH_REGISTRY.lock().find(state, address).map(|r| r.id)
+}
+
+/// 检查范围能否写入补丁，参见 [`PatchRegistry::check_overlap`]
+pub fn check_overlap(
+    state: u64,
+    range: Range<usize>,
+    allow_overlap: bool,
+) -> Result<Vec<PatchOwner>> {
+    PATCH_REGISTRY
+        .lock()
+        .check_overlap(state, range, allow_overlap)
+}
+
+/// 按写入逆序恢复指定状态的全部补丁
+pub fn restore_owner_patches(state: u64) {
+    let ids = PATCH_REGISTRY
+        .lock()
+        .records()
+        .iter()
+        .rev()
+        .filter(|r| r.owner.state == state)
+        .map(|r| (r.id, r.address))
+        .collect::<Vec<_>>();
+    for (id, address) in ids {
+        if let Err(e) = restore_patch(id) {
+            log::error!("Failed to restore patch at 0x{:x}: {}", address, e);
+        }
+    }
+}
+
+/// 当前所有补丁，附带是否与其他补丁重叠
+pub fn list_patches() -> Vec<(PatchRecord, bool)> {
+    let registry = PATCH_REGISTRY.lock();
+    registry
+        .records()
+        .iter()
+        .map(|r| {
+            let stacked = registry.overlapping(r.range()).any(|o| o.id != r.id);
+            (r.clone(), stacked)
+        })
+        .collect()
+}
+
+fn range_overlaps(range1: &Range<usize>, range2: &Range<usize>) -> bool {
+    range1.start < range2.end && range2.start < range1.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(state: u64) -> PatchOwner {
+        PatchOwner {
+            state,
+            script: format!("script{}", state),
+        }
+    }
+
+    /// 模拟内存，按注册表的方式写入与恢复
+    struct FakeMemory {
+        base: usize,
+        bytes: Vec<u8>,
+        registry: PatchRegistry,
+    }
+
+    impl FakeMemory {
+        fn new(bytes: &[u8]) -> Self {
+            Self {
+                base: 0x1000,
+                bytes: bytes.to_vec(),
+                registry: PatchRegistry::default(),
+            }
+        }
+
+        fn patch(&mut self, state: u64, address: usize, data: &[u8]) -> u64 {
+            let start = address - self.base;
+            let backup = self.bytes[start..start + data.len()].to_vec();
+            self.bytes[start..start + data.len()].copy_from_slice(data);
+            self.registry.insert(address, backup, owner(state), None)
+        }
+
+        fn restore(&mut self, id: u64) {
+            for (address, bytes) in self.registry.remove(id).unwrap() {
+                let start = address - self.base;
+                self.bytes[start..start + bytes.len()].copy_from_slice(&bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn restore_in_reverse_order() {
+        let mut mem = FakeMemory::new(&[1, 2, 3, 4]);
+        let a = mem.patch(1, 0x1000, &[0xa, 0xa]);
+        let b = mem.patch(2, 0x1001, &[0xb, 0xb]);
+        assert_eq!(mem.bytes, [0xa, 0xb, 0xb, 4]);
+
+        mem.restore(b);
+        assert_eq!(mem.bytes, [0xa, 0xa, 3, 4]);
+        mem.restore(a);
+        assert_eq!(mem.bytes, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn restore_out_of_order() {
+        let mut mem = FakeMemory::new(&[1, 2, 3, 4]);
+        let a = mem.patch(1, 0x1000, &[0xa, 0xa]);
+        let b = mem.patch(2, 0x1001, &[0xb, 0xb]);
+
+        // 先恢复底层补丁，不应覆盖上层补丁
+        mem.restore(a);
+        assert_eq!(mem.bytes, [1, 0xb, 0xb, 4]);
+        mem.restore(b);
+        assert_eq!(mem.bytes, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn restore_middle_of_stack() {
+        let mut mem = FakeMemory::new(&[1, 2, 3]);
+        let a = mem.patch(1, 0x1000, &[0xa, 0xa, 0xa]);
+        let b = mem.patch(2, 0x1001, &[0xb]);
+        let c = mem.patch(3, 0x1000, &[0xc, 0xc, 0xc]);
+
+        mem.restore(b);
+        assert_eq!(mem.bytes, [0xc, 0xc, 0xc]);
+        mem.restore(c);
+        assert_eq!(mem.bytes, [0xa, 0xa, 0xa]);
+        mem.restore(a);
+        assert_eq!(mem.bytes, [1, 2, 3]);
+    }
+
+    #[test]
+    fn remove_merges_contiguous_writes() {
+        let mut mem = FakeMemory::new(&[1, 2, 3, 4, 5]);
+        let a = mem.patch(1, 0x1000, &[0xa; 5]);
+        mem.patch(2, 0x1002, &[0xb]);
+
+        let writes = mem.registry.remove(a).unwrap();
+        assert_eq!(writes, vec![(0x1000, vec![1, 2]), (0x1003, vec![4, 5])]);
+        assert!(mem.registry.remove(a).is_none());
+    }
+
+    #[test]
+    fn find_by_owner_state() {
+        let mut mem = FakeMemory::new(&[0; 8]);
+        let a = mem.patch(1, 0x1000, &[0xa]);
+        mem.patch(2, 0x1004, &[0xb]);
+
+        assert_eq!(mem.registry.find(1, 0x1000).map(|r| r.id), Some(a));
+        assert!(mem.registry.find(2, 0x1000).is_none());
+        assert_eq!(mem.registry.overlapping(0x1000..0x1008).count(), 2);
+    }
+
+    #[test]
+    fn overlap_with_other_owners() {
+        let mut mem = FakeMemory::new(&[0; 8]);
+        mem.patch(1, 0x1000, &[0xa; 2]);
+        mem.patch(2, 0x1001, &[0xb; 2]);
+        mem.patch(2, 0x1004, &[0xb]);
+
+        // 同一状态总是错误
+        assert!(matches!(
+            mem.registry.check_overlap(1, 0x1001..0x1002, true),
+            Err(Error::PatchAlreadyExists(0x1001))
+        ));
+
+        // 其他状态默认报错，错误中列出所有者
+        let Err(Error::PatchOverlap(address, owners)) =
+            mem.registry.check_overlap(3, 0x1000..0x1005, false)
+        else {
+            panic!("expected overlap error");
+        };
+        assert_eq!(address, 0x1000);
+        assert_eq!(owners, "'script1' (state 1), 'script2' (state 2)");
+
+        // 允许时返回去重后的所有者
+        let owners = mem.registry.check_overlap(3, 0x1000..0x1005, true).unwrap();
+        assert_eq!(owners, vec![owner(1), owner(2)]);
+        assert!(
+            mem.registry
+                .check_overlap(3, 0x1006..0x1008, false)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use std::pin::Pin;
+use std::{pin::Pin, sync::Arc};
 
 use mlua::prelude::*;
+use parking_lot::Mutex;
 
 use crate::{module::EgLib, util};
 
@@ -95,6 +96,22 @@ impl PromiseModule {
     /// });
     /// ```
     pub fn new_promise_async<F>(lua: &Lua, executor: F) -> LuaResult<Promise>
+    where
+        F: Fn(LuaFunction, LuaFunction) -> Pin<Box<dyn Future<Output = ()> + Send>>
+            + Send
+            + 'static,
+    {
+        let (promise, _) = Self::new_promise_async_with_handle(lua, executor)?;
+        Ok(promise)
+    }
+
+    /// 同 [`Self::new_promise_async`]，额外返回可终止异步任务的句柄。
+    ///
+    /// 任务被终止后 promise 不会再被 resolve 或 reject。
+    pub fn new_promise_async_with_handle<F>(
+        lua: &Lua,
+        executor: F,
+    ) -> LuaResult<(Promise, LuaTaskHandle)>
     where
         F: Fn(LuaFunction, LuaFunction) -> Pin<Box<dyn Future<Output = ()> + Send>>
             + Send
@@ -102,10 +119,15 @@ impl PromiseModule {
     {
         let promise_module = Self::get_promise_module(lua)?;
 
+        // Promise.new 同步调用执行器，执行器返回前即可取得句柄
+        let task_handle = Arc::new(Mutex::new(None));
+        let task_handle_inner = task_handle.clone();
         let executor_lua =
             lua.create_function(move |lua, (resolve, reject): (LuaFunction, LuaFunction)| {
                 let future = executor(resolve, reject);
-                Self::new_async_task(lua, future, |_, _| {}).map_err(LuaError::runtime)?;
+                let handle =
+                    Self::new_async_task(lua, future, |_, _| {}).map_err(LuaError::runtime)?;
+                task_handle_inner.lock().replace(handle);
 
                 Ok(())
             });
@@ -113,7 +135,11 @@ impl PromiseModule {
         let promise_new_fn = promise_module.get::<LuaFunction>("new")?;
         let promise = promise_new_fn.call::<LuaTable>(executor_lua)?;
 
-        Ok(promise)
+        let handle = task_handle
+            .lock()
+            .take()
+            .ok_or_else(|| LuaError::runtime("Failed to start promise task"))?;
+        Ok((promise, handle))
     }
 }
 
@@ -0,0 +1,376 @@
+//! 进程级页保护修改记录
+//!
+//! 每次通过 Lua 修改的页保护都登记于此，释放守卫、状态销毁或卸载时恢复，
+//! 避免重载脚本后内存仍保持可写可执行。
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use mlua::prelude::*;
+use parking_lot::Mutex;
+
+use crate::error::Result;
+use crate::memory::{MemoryError, MemoryUtils, protection_from_flags, protection_to_flags};
+
+use super::luaptr::LuaPtr;
+
+const PAGE_SIZE: usize = 0x1000;
+
+static PROTECTIONS: LazyLock<Mutex<Protections>> = LazyLock::new(Default::default);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 修改页保护的后端，测试中以模拟实现替代
+trait Protector {
+    /// 设置 `[address, address + size)` 的页保护，返回首页原来的保护
+    fn protect(&self, address: usize, size: usize, protect: u32) -> Result<u32>;
+}
+
+struct SystemProtector;
+
+impl Protector for SystemProtector {
+    fn protect(&self, address: usize, size: usize, protect: u32) -> Result<u32> {
+        Ok(MemoryUtils::set_protection(address, size, protect)?)
+    }
+}
+
+struct ProtectRecord {
+    id: u64,
+    /// 所属 `MemoryModule` 的状态 ID
+    state: u64,
+    /// 受影响的页范围
+    pages: Range<usize>,
+    protect: u32,
+}
+
+#[derive(Default)]
+struct Protections {
+    /// 按修改顺序排列
+    records: Vec<ProtectRecord>,
+    /// 页地址 -> 首次修改前的保护，只包含仍被修改的页
+    originals: BTreeMap<usize, u32>,
+}
+
+impl Protections {
+    /// 逐页修改保护并记录每页的原保护，返回首页修改前的保护
+    fn apply(&mut self, protector: &impl Protector, record: ProtectRecord) -> Result<u32> {
+        let mut changed = Vec::new();
+        for page in record.pages.clone().step_by(PAGE_SIZE) {
+            match protector.protect(page, PAGE_SIZE, record.protect) {
+                Ok(old) => changed.push((page, old)),
+                Err(e) => {
+                    // 撤销已修改的页
+                    for (page, old) in changed.into_iter().rev() {
+                        let _ = protector.protect(page, PAGE_SIZE, old);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        for &(page, old) in &changed {
+            self.originals.entry(page).or_insert(old);
+        }
+        self.records.push(record);
+        Ok(changed.first().map_or(0, |&(_, old)| old))
+    }
+
+    /// 恢复一条修改记录，记录已恢复时返回 false
+    ///
+    /// 每页恢复为仍覆盖该页的最后一条修改设置的保护，没有其他修改时恢复为原保护。
+    /// 被之后的修改覆盖的页保持不变。
+    fn restore(&mut self, protector: &impl Protector, id: u64) -> bool {
+        let Some(index) = self.records.iter().position(|r| r.id == id) else {
+            return false;
+        };
+        let record = self.records.remove(index);
+
+        let mut changes = Vec::new();
+        for page in record.pages.step_by(PAGE_SIZE) {
+            let latest = self.records.iter().rposition(|r| r.pages.contains(&page));
+            let protect = match latest {
+                Some(latest) if latest >= index => continue,
+                Some(latest) => self.records[latest].protect,
+                None => match self.originals.remove(&page) {
+                    Some(original) => original,
+                    None => continue,
+                },
+            };
+            changes.push((page, protect));
+        }
+        apply_changes(protector, changes);
+        true
+    }
+
+    /// 将所有页恢复为原保护
+    fn restore_all(&mut self, protector: &impl Protector) {
+        self.records.clear();
+        let changes = std::mem::take(&mut self.originals).into_iter().collect();
+        apply_changes(protector, changes);
+    }
+}
+
+/// 按页写入保护，相邻且保护相同的页合并为一次调用
+fn apply_changes(protector: &impl Protector, changes: Vec<(usize, u32)>) {
+    let mut runs: Vec<(Range<usize>, u32)> = Vec::new();
+    for (page, protect) in changes {
+        match runs.last_mut() {
+            Some((range, last)) if range.end == page && *last == protect => {
+                range.end += PAGE_SIZE;
+            }
+            _ => runs.push((page..page + PAGE_SIZE, protect)),
+        }
+    }
+    for (range, protect) in runs {
+        if let Err(e) = protector.protect(range.start, range.len(), protect) {
+            log::error!("Failed to reset memory protection: {}", e);
+        }
+    }
+}
+
+/// `[address, address + size)` 所在的页范围
+fn page_range(address: usize, size: usize) -> Option<Range<usize>> {
+    let end = address
+        .checked_add(size)?
+        .checked_next_multiple_of(PAGE_SIZE)?;
+    Some(address / PAGE_SIZE * PAGE_SIZE..end)
+}
+
+/// 页保护守卫，恢复后不再生效
+pub struct ProtectGuard {
+    id: u64,
+    address: usize,
+    size: usize,
+    old_protect: u32,
+}
+
+impl LuaUserData for ProtectGuard {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("address", |_, this| Ok(LuaPtr::new(this.address as u64)));
+        fields.add_field_method_get("size", |_, this| Ok(this.size));
+        fields.add_field_method_get("old_protect", |_, this| {
+            Ok(protection_to_flags(this.old_protect))
+        });
+        fields.add_field_method_get("active", |_, this| Ok(this.is_active()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("restore", |_, this, ()| Ok(restore(this.id)));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!(
+                "ProtectGuard(0x{:x}, {} bytes, {})",
+                this.address,
+                this.size,
+                if this.is_active() {
+                    "active"
+                } else {
+                    "restored"
+                }
+            ))
+        });
+    }
+}
+
+impl Drop for ProtectGuard {
+    fn drop(&mut self) {
+        restore(self.id);
+    }
+}
+
+impl ProtectGuard {
+    /// 修改页保护并登记到 `state` 名下
+    pub fn new(state: u64, address: usize, size: usize, flags: &str) -> Result<Self> {
+        let protect = protection_from_flags(flags)
+            .ok_or_else(|| MemoryError::InvalidProtection(flags.to_string()))?;
+        let pages = match page_range(address, size) {
+            Some(pages) if size != 0 => pages,
+            _ => return Err(MemoryError::InvalidSize(size).into()),
+        };
+        MemoryUtils::check_page_commit(address)?;
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let record = ProtectRecord {
+            id,
+            state,
+            pages,
+            protect,
+        };
+        let old_protect = PROTECTIONS.lock().apply(&SystemProtector, record)?;
+        Ok(Self {
+            id,
+            address,
+            size,
+            old_protect,
+        })
+    }
+
+    fn is_active(&self) -> bool {
+        PROTECTIONS.lock().records.iter().any(|r| r.id == self.id)
+    }
+}
+
+/// 恢复一条修改记录，记录已恢复时返回 false
+fn restore(id: u64) -> bool {
+    PROTECTIONS.lock().restore(&SystemProtector, id)
+}
+
+/// 按修改顺序的逆序恢复 `state` 的所有修改
+pub fn restore_owner_protections(state: u64) {
+    let ids = PROTECTIONS
+        .lock()
+        .records
+        .iter()
+        .rev()
+        .filter(|r| r.state == state)
+        .map(|r| r.id)
+        .collect::<Vec<_>>();
+    for id in ids {
+        restore(id);
+    }
+}
+
+/// 将所有修改过的页恢复为首次修改前的保护
+pub fn restore_all_protections() {
+    let mut protections = PROTECTIONS.lock();
+    log::debug!(
+        "restore_all_protections: count={}",
+        protections.records.len()
+    );
+    protections.restore_all(&SystemProtector);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    const R: u32 = 0x02;
+    const RW: u32 = 0x04;
+    const RX: u32 = 0x20;
+    const RWX: u32 = 0x40;
+
+    /// 模拟的页保护，未设置的页为只读
+    #[derive(Default)]
+    struct FakeProtector {
+        pages: RefCell<BTreeMap<usize, u32>>,
+    }
+
+    impl FakeProtector {
+        fn get(&self, page: usize) -> u32 {
+            self.pages
+                .borrow()
+                .get(&(page * PAGE_SIZE))
+                .copied()
+                .unwrap_or(R)
+        }
+
+        /// 第 `first..=last` 页的保护
+        fn range(&self, first: usize, last: usize) -> Vec<u32> {
+            (first..=last).map(|page| self.get(page)).collect()
+        }
+    }
+
+    impl Protector for FakeProtector {
+        fn protect(&self, address: usize, size: usize, protect: u32) -> Result<u32> {
+            let mut pages = self.pages.borrow_mut();
+            let old = pages.get(&address).copied().unwrap_or(R);
+            for page in (address..address + size).step_by(PAGE_SIZE) {
+                pages.insert(page, protect);
+            }
+            Ok(old)
+        }
+    }
+
+    /// 修改第 `first..=last` 页
+    fn apply(
+        protections: &mut Protections,
+        protector: &FakeProtector,
+        id: u64,
+        first: usize,
+        last: usize,
+        protect: u32,
+    ) -> u32 {
+        let record = ProtectRecord {
+            id,
+            state: 0,
+            pages: first * PAGE_SIZE..(last + 1) * PAGE_SIZE,
+            protect,
+        };
+        protections.apply(protector, record).unwrap()
+    }
+
+    #[test]
+    fn page_ranges() {
+        assert_eq!(page_range(0x1010, 0x10), Some(0x1000..0x2000));
+        assert_eq!(page_range(0x1ff0, 0x20), Some(0x1000..0x3000));
+        assert_eq!(page_range(0x2000, 0x1000), Some(0x2000..0x3000));
+        assert_eq!(page_range(usize::MAX - 0x10, 0x20), None);
+    }
+
+    #[test]
+    fn restore_nested_in_any_order() {
+        let protector = FakeProtector::default();
+        let mut protections = Protections::default();
+
+        assert_eq!(apply(&mut protections, &protector, 1, 1, 1, RWX), R);
+        assert_eq!(apply(&mut protections, &protector, 2, 1, 1, RW), RWX);
+
+        // 较早的修改先恢复时，页保持较新的修改
+        assert!(protections.restore(&protector, 1));
+        assert_eq!(protector.get(1), RW);
+        assert!(protections.restore(&protector, 2));
+        assert_eq!(protector.get(1), R);
+        assert!(!protections.restore(&protector, 2));
+        assert!(protections.originals.is_empty());
+    }
+
+    #[test]
+    fn restore_partial_overlap() {
+        let protector = FakeProtector::default();
+        protector.protect(3 * PAGE_SIZE, PAGE_SIZE, RX).unwrap();
+        let mut protections = Protections::default();
+
+        apply(&mut protections, &protector, 1, 1, 2, RWX);
+        apply(&mut protections, &protector, 2, 2, 3, RW);
+        assert_eq!(protector.range(1, 3), vec![RWX, RW, RW]);
+
+        // 第 1 页只被较早的修改覆盖，立即恢复
+        protections.restore(&protector, 1);
+        assert_eq!(protector.range(1, 3), vec![R, RW, RW]);
+
+        // 各页恢复为各自的原保护
+        protections.restore(&protector, 2);
+        assert_eq!(protector.range(1, 3), vec![R, R, RX]);
+        assert!(protections.originals.is_empty());
+    }
+
+    #[test]
+    fn restore_newer_falls_back_to_older() {
+        let protector = FakeProtector::default();
+        let mut protections = Protections::default();
+
+        apply(&mut protections, &protector, 1, 1, 3, RWX);
+        apply(&mut protections, &protector, 2, 2, 4, RW);
+
+        // 仍被较早修改覆盖的页恢复为其保护，其余页恢复原保护
+        protections.restore(&protector, 2);
+        assert_eq!(protector.range(1, 4), vec![RWX, RWX, RWX, R]);
+        protections.restore(&protector, 1);
+        assert_eq!(protector.range(1, 4), vec![R, R, R, R]);
+    }
+
+    #[test]
+    fn restore_all_pages() {
+        let protector = FakeProtector::default();
+        protector.protect(2 * PAGE_SIZE, PAGE_SIZE, RX).unwrap();
+        let mut protections = Protections::default();
+
+        apply(&mut protections, &protector, 1, 1, 2, RWX);
+        apply(&mut protections, &protector, 2, 2, 3, RW);
+        protections.restore_all(&protector);
+
+        assert_eq!(protector.range(1, 3), vec![R, RX, R]);
+        assert!(protections.records.is_empty() && protections.originals.is_empty());
+    }
+}
@@ -25,6 +25,16 @@ local function parse_permissions(perm_str)
     return table.concat(permissions, " | ")
 end
 
+local function format_grant(state)
+    local info = " [" .. parse_permissions(state.permissions) .. "]"
+    if state.once then
+        info = info .. " (once)"
+    elseif state.expires_at then
+        info = info .. " (expires " .. os.date("%Y-%m-%d %H:%M", state.expires_at // 1000) .. ")"
+    end
+    return info
+end
+
 re.on_draw_ui(function()
     if not imgui.tree_node("Eglib") then
         return
@@ -35,15 +45,32 @@ re.on_draw_ui(function()
         if imgui.tree_node("Granted File System Access") then
             for service_name, states in pairs(data) do
                 imgui.text(service_name)
-                if states.acceptions then
+                imgui.same_line()
+                if imgui.button("Reset##" .. service_name) then
+                    eglib.__with_privileged(privilege_key, function()
+                        eglib.fs:clear_access(service_name)
+                    end)
+                end
+                if #states.acceptions > 0 then
                     imgui.text("- Accepted")
                     for _, state in ipairs(states.acceptions) do
-                        imgui.text("  - " .. state.path)
+                        imgui.text("  - " .. state.path .. format_grant(state))
+                        imgui.same_line()
+                        if imgui.button("Revoke##" .. service_name .. state.path) then
+                            eglib.fs:revoke(service_name, state.path)
+                        end
                     end
-                elseif states.rejections then
+                end
+                if #states.rejections > 0 then
                     imgui.text("- Rejected")
                     for _, state in ipairs(states.rejections) do
-                        imgui.text("  - " .. state.path)
+                        imgui.text("  - " .. state.path .. format_grant(state))
+                        imgui.same_line()
+                        if imgui.button("Remove##" .. service_name .. state.path) then
+                            eglib.__with_privileged(privilege_key, function()
+                                eglib.fs:remove_rejection(service_name, state.path)
+                            end)
+                        end
                     end
                 end
             end
@@ -51,5 +78,17 @@ re.on_draw_ui(function()
         end
     end
 
+    local groups = eglib.memory:list_patch_groups()
+    if imgui.tree_node("Memory Patch Groups") then
+        if #groups == 0 then
+            imgui.text("No patch groups")
+        end
+        for _, group in ipairs(groups) do
+            local state = group.enabled and "Enabled" or "Disabled"
+            imgui.text(string.format("%s (%d patches) - %s", group.name, group.count, state))
+        end
+        imgui.tree_pop()
+    end
+
     imgui.tree_pop()
 end)
@@ -0,0 +1,103 @@
+use mlua::prelude::*;
+
+use crate::memory::{Snapshot, TypedValue, typed_values};
+
+use super::{fs::FsService, luaptr::LuaPtr};
+
+/// 内存快照对象
+pub struct LuaSnapshot(Snapshot);
+
+impl LuaUserData for LuaSnapshot {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("address", |_, this| Ok(LuaPtr::new(this.0.address as u64)));
+        fields.add_field_method_get("size", |_, this| Ok(this.0.size()));
+        fields.add_field_method_get("timestamp", |_, this| Ok(this.0.timestamp));
+        fields.add_field_method_get("module_base", |_, this| {
+            Ok(LuaPtr::new(this.0.module_base as u64))
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        // 列出从本快照到 other 的变化
+        methods.add_method("diff", |lua, this, other: LuaUserDataRef<LuaSnapshot>| {
+            let changes = this.0.diff(&other.0);
+            let list = lua.create_table_with_capacity(changes.len(), 0)?;
+            for change in changes {
+                let info = lua.create_table()?;
+                info.set("address", LuaPtr::new(change.address as u64))?;
+                info.set("size", change.before.len())?;
+                let before = values_to_lua(lua, &change.before)?;
+                let after = values_to_lua(lua, &change.after)?;
+
+                // 按包含变化的对齐窗口解释数值
+                if let Some((address, width)) = change.typed_window()
+                    && let (Some(old), Some(new)) =
+                        (this.0.get(address, width), other.0.get(address, width))
+                {
+                    set_typed_values(&before, old)?;
+                    set_typed_values(&after, new)?;
+                }
+                info.set("before", before)?;
+                info.set("after", after)?;
+                list.push(info)?;
+            }
+            Ok(list)
+        });
+        methods.add_method("scan", |_, this, pattern: String| {
+            let matches = this.0.scan(&pattern).into_lua_err()?;
+            Ok(matches
+                .into_iter()
+                .map(|address| LuaPtr::new(address as u64))
+                .collect::<Vec<_>>())
+        });
+        methods.add_method(
+            "save",
+            |_, this, (service, path): (LuaUserDataRef<FsService>, String)| {
+                service.write_binary_file(&path, &this.0.to_bytes())
+            },
+        );
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!(
+                "Snapshot(0x{:x}, {} bytes)",
+                this.0.address,
+                this.0.size()
+            ))
+        });
+    }
+}
+
+impl LuaSnapshot {
+    pub fn new(snapshot: Snapshot) -> Self {
+        Self(snapshot)
+    }
+
+    /// 通过 FsService 读取快照文件
+    pub fn load(service: &FsService, path: &str) -> LuaResult<Self> {
+        let bytes = service.read_binary_file(path)?;
+        let snapshot = Snapshot::from_bytes(&bytes).into_lua_err()?;
+        Ok(Self(snapshot))
+    }
+}
+
+/// `{ bytes = {...} }`，数值字段随后按宽度写入
+fn values_to_lua(lua: &Lua, bytes: &[u8]) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("bytes", bytes.to_vec())?;
+    Ok(table)
+}
+
+fn set_typed_values(table: &LuaTable, bytes: &[u8]) -> LuaResult<()> {
+    for (name, value) in typed_values(bytes) {
+        let value = match value {
+            TypedValue::Int(v) => LuaValue::Integer(v),
+            // 超出 Lua 整数范围时以浮点数表示
+            TypedValue::UInt(v) => match i64::try_from(v) {
+                Ok(v) => LuaValue::Integer(v),
+                Err(_) => LuaValue::Number(v as f64),
+            },
+            TypedValue::Float(v) => LuaValue::Number(v),
+        };
+        table.set(name, value)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! 测试共用的辅助工具
+
+use std::path::{Path, PathBuf};
+
+/// 测试用临时目录，离开作用域时删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// 名称需在测试间唯一，同名的旧目录会先被删除
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("eglib_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        // 真实路径，临时目录本身可能是链接
+        Self(std::fs::canonicalize(root).unwrap())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        std::fs::read_to_string(self.path(path)).unwrap()
+    }
+
+    /// 根目录下的文件名，已排序
+    pub fn entries(&self) -> Vec<String> {
+        let mut entries = std::fs::read_dir(&self.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
+    }
+
+    pub fn create_dir(&self, path: &str) {
+        std::fs::create_dir_all(self.path(path)).unwrap();
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        std::fs::write(self.path(path), content).unwrap();
+    }
+
+    #[cfg(unix)]
+    pub fn symlink(&self, link: &str, target: &str) {
+        std::os::unix::fs::symlink(target, self.path(link)).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
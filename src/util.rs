@@ -1,4 +1,9 @@
-use std::path::{Component, Path, PathBuf};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use rand::RngCore;
 
@@ -33,16 +38,129 @@ pub fn normalize_path(input: impl AsRef<Path>) -> PathBuf {
         normalized.push(component.as_os_str());
     }
 
-    // remove \\? prefix
-    let path_str = normalized.to_str().unwrap();
-    if path_str.starts_with("\\\\?\\") {
-        let path = PathBuf::from(path_str.trim_start_matches("\\\\?\\"));
+    strip_verbatim(normalized)
+}
+
+/// 去除 Windows 扩展路径前缀 `\\?\`
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let Some(path_str) = path.to_str() else {
         return path;
+    };
+    if let Some(rest) = path_str.strip_prefix("\\\\?\\UNC\\") {
+        PathBuf::from(format!("\\\\{}", rest))
+    } else if let Some(rest) = path_str.strip_prefix("\\\\?\\") {
+        PathBuf::from(rest)
+    } else {
+        path
+    }
+}
+
+/// 符号链接的最大解析层数
+const MAX_LINK_DEPTH: usize = 40;
+
+/// 解析路径的真实位置，跟随符号链接与 junction
+///
+/// 路径不存在时解析最近的已存在的上级目录，再拼接剩余部分，用于检查将要创建的文件。
+pub fn resolve_path(input: impl AsRef<Path>) -> io::Result<PathBuf> {
+    resolve_with_depth(&normalize_path(input), 0)
+}
+
+/// 解析路径的上级目录，最后一级不跟随链接，用于操作链接本身
+pub fn resolve_parent(input: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let normalized = normalize_path(input);
+    match (normalized.parent(), normalized.file_name()) {
+        (Some(parent), Some(name)) => Ok(resolve_with_depth(parent, 0)?.join(name)),
+        _ => resolve_with_depth(&normalized, 0),
+    }
+}
+
+fn resolve_with_depth(path: &Path, depth: usize) -> io::Result<PathBuf> {
+    if depth > MAX_LINK_DEPTH {
+        return Err(io::Error::other(format!(
+            "Too many levels of symbolic links: {}",
+            path.display()
+        )));
+    }
+    match std::fs::canonicalize(path) {
+        Ok(resolved) => return Ok(strip_verbatim(resolved)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
     }
 
-    normalized
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(path.to_path_buf());
+    };
+    // 指向不存在位置的链接，写入时会创建其目标，因此按目标解析
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+        let target = std::fs::read_link(path)?;
+        return resolve_with_depth(&normalize_path(parent.join(target)), depth + 1);
+    }
+    Ok(resolve_with_depth(parent, depth)?.join(name))
 }
 
+static TEMP_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// 原子地写入文件：先写入同目录下的临时文件并同步到磁盘，再重命名覆盖目标文件
+///
+/// 写入中途崩溃时目标文件保持原有内容，最多残留一个临时文件。
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let (temp_path, mut file) = create_temp(path)?;
+    let result = (|| {
+        file.write_all(data)?;
+        // 保留原文件的权限
+        #[cfg(unix)]
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+        return result;
+    }
+    sync_parent(path);
+    Ok(())
+}
+
+/// 在目标文件同目录下创建临时文件，保证重命名不跨越文件系统
+fn create_temp(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    loop {
+        let temp_path = path.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            name,
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        match File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 同步父目录，使重命名本身落盘
+#[cfg(unix)]
+fn sync_parent(path: &Path) {
+    let Some(parent) = path.parent() else {
+        return;
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+/// Windows 上无法以此方式打开目录，依赖重命名本身的持久性
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) {}
+
 /// 生成随机u64 ID
 pub fn new_random_id() -> u64 {
     rand::rng().next_u64()
@@ -50,6 +168,9 @@ pub fn new_random_id() -> u64 {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(unix)]
+    use crate::test_util::TempDir;
+
     use super::*;
 
     #[test]
@@ -60,4 +181,105 @@ mod tests {
             normalize_path(Path::new("\\\\?\\C:\\foo\\bar\\..")).display()
         );
     }
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(
+            strip_verbatim(PathBuf::from("\\\\?\\C:\\foo")),
+            PathBuf::from("C:\\foo")
+        );
+        assert_eq!(
+            strip_verbatim(PathBuf::from("\\\\?\\UNC\\server\\share")),
+            PathBuf::from("\\\\server\\share")
+        );
+        assert_eq!(
+            strip_verbatim(PathBuf::from("/home/user")),
+            PathBuf::from("/home/user")
+        );
+    }
+
+    /// 在临时目录中创建授权目录与外部目录
+    #[cfg(unix)]
+    fn temp_tree(name: &str) -> TempDir {
+        let tree = TempDir::new(name);
+        tree.create_dir("granted/sub");
+        tree.create_dir("outside");
+        tree.write("granted/inside.txt", "inside");
+        tree.write("outside/secret.txt", "secret");
+        tree
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_symlinks() {
+        let tree = temp_tree("resolve_symlinks");
+        tree.symlink("granted/link_out", "../outside");
+        tree.symlink("granted/link_in", "inside.txt");
+        tree.symlink("granted/sub/link_up", "..");
+
+        assert_eq!(
+            resolve_path(tree.path("granted/link_out/secret.txt")).unwrap(),
+            tree.path("outside/secret.txt")
+        );
+        assert_eq!(
+            resolve_path(tree.path("granted/link_in")).unwrap(),
+            tree.path("granted/inside.txt")
+        );
+        assert_eq!(
+            resolve_path(tree.path("granted/sub/link_up/inside.txt")).unwrap(),
+            tree.path("granted/inside.txt")
+        );
+        // 不存在的文件按已存在的上级目录解析
+        assert_eq!(
+            resolve_path(tree.path("granted/new/dir/a.txt")).unwrap(),
+            tree.path("granted/new/dir/a.txt")
+        );
+        assert_eq!(
+            resolve_path(tree.path("granted/link_out/new.txt")).unwrap(),
+            tree.path("outside/new.txt")
+        );
+        // `..` 按字面处理，不会经过链接的目标
+        assert_eq!(
+            resolve_path(tree.path("granted/link_out/../inside.txt")).unwrap(),
+            tree.path("granted/inside.txt")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_dangling_and_looping_links() {
+        let tree = temp_tree("resolve_dangling");
+        tree.symlink("granted/dangling", "../outside/missing.txt");
+        tree.symlink("granted/chain", "dangling");
+        tree.symlink("granted/loop_a", "loop_b");
+        tree.symlink("granted/loop_b", "loop_a");
+
+        assert_eq!(
+            resolve_path(tree.path("granted/dangling")).unwrap(),
+            tree.path("outside/missing.txt")
+        );
+        assert_eq!(
+            resolve_path(tree.path("granted/chain")).unwrap(),
+            tree.path("outside/missing.txt")
+        );
+        assert!(resolve_path(tree.path("granted/loop_a")).is_err());
+        assert!(resolve_path(tree.path("granted/loop_a/a.txt")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_parent_keeps_link() {
+        let tree = temp_tree("resolve_parent");
+        tree.symlink("granted/link_out", "../outside");
+        tree.symlink("link_granted", "granted");
+
+        assert_eq!(
+            resolve_parent(tree.path("granted/link_out")).unwrap(),
+            tree.path("granted/link_out")
+        );
+        assert_eq!(
+            resolve_parent(tree.path("link_granted/link_out")).unwrap(),
+            tree.path("granted/link_out")
+        );
+    }
 }